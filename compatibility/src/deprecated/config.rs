@@ -1,30 +1,29 @@
 //! Configs for all 0L apps.
+//! Kept so v5 0L.toml files can be read and migrated. The parts which needed
+//! the retired v5 node config and prompts use the current equivalents.
 
 use anyhow::{bail, Error};
 use diem_config::config::NodeConfig;
-use diem_global_constants::{CONFIG_FILE, NODE_HOME};
 use diem_types::{
-    account_address::AccountAddress, chain_id::{NamedChain, MODE_0L},
-    transaction::authenticator::AuthenticationKey, waypoint::Waypoint,
+    chain_id::NamedChain, transaction::authenticator::AuthenticationKey, waypoint::Waypoint,
 };
-use dirs;
-use reqwest::{blocking::Client, Url};
+use libra_types::core_types::mode_ol::MODE_0L;
+use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::{self, json};
-use std::{
-    fs::{self, File},
-    io::{Read, Write},
-    net::Ipv4Addr,
-    path::PathBuf,
-    str::FromStr,
-};
+use std::{fs, io::Write, net::Ipv4Addr, path::PathBuf, str::FromStr};
+use url::Url;
+
+use crate::version_five::legacy_address_v5::LegacyAddressV5;
 
-use crate::dialogue::{what_home, what_ip, what_statement, what_vfn_ip};
+// from the v5 diem_global_constants
+const CONFIG_FILE: &str = "0L.toml";
+const NODE_HOME: &str = ".0L";
 
 const BASE_WAYPOINT: &str = "0:683185844ef67e5c8eeaa158e635de2a4c574ce7bbb7f41f787d38db2d623ae2";
 
 /// MinerApp Configuration
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 // #[serde(deny_unknown_fields)]
 pub struct AppCfg {
     /// Workspace config
@@ -34,16 +33,13 @@ pub struct AppCfg {
     /// Chain Info for all users
     pub chain_info: ChainInfo,
     /// Transaction configurations
+    #[serde(default)]
     pub tx_configs: TxConfigs,
 }
 
 /// Get a AppCfg object from toml file
 pub fn parse_toml(path: PathBuf) -> Result<AppCfg, Error> {
-    // let mut config_toml = path.to_str().unwrap().to_owned()).expect("could not parse app config from file");
-    let mut toml_buf = "".to_string();
-    let mut file = File::open(&path)?;
-    file.read_to_string(&mut toml_buf)?;
-
+    let toml_buf = fs::read_to_string(path)?;
     let cfg: AppCfg = toml::from_str(&toml_buf)?;
     Ok(cfg)
 }
@@ -68,8 +64,13 @@ impl AppCfg {
             Ok(file) => {
                 let json: serde_json::Value =
                     serde_json::from_reader(file).expect("could not parse JSON in key_store.json");
-                match ajson::get(&json.to_string(), "*/waypoint.value") {
-                    Some(value) => value.to_string().parse(),
+                // the waypoint is at */waypoint.value
+                let found = json.as_object().and_then(|o| {
+                    o.values()
+                        .find_map(|v| v["waypoint"]["value"].as_str().map(str::to_owned))
+                });
+                match found {
+                    Some(value) => value.parse(),
                     // If nothing is found in key_store.json fallback
                     // to base_waypoint in toml
                     _ => match self.chain_info.base_waypoint {
@@ -113,9 +114,10 @@ impl AppCfg {
     }
 
     /// Get where node key_store.json stored.
+    #[allow(clippy::too_many_arguments)]
     pub fn init_app_configs(
         authkey: AuthenticationKey,
-        account: AccountAddress,
+        account: LegacyAddressV5,
         upstream_peer: &Option<Url>,
         config_path: &Option<PathBuf>,
         base_epoch: &Option<u64>,
@@ -130,24 +132,21 @@ impl AppCfg {
         default_config.profile.auth_key = authkey;
         default_config.profile.account = account;
 
+        // the v5 prompts for these went away with the v5 tools, the defaults
+        // are used instead
         // Get statement which goes into genesis block
-        default_config.profile.statement = match statement {
-            Some(s) => s,
-            None => what_statement(),
-        };
-
-        default_config.profile.ip = match ip {
-            Some(i) => i,
-            None => what_ip().unwrap(),
-        };
+        if let Some(s) = statement {
+            default_config.profile.statement = s;
+        }
 
-        default_config.profile.vfn_ip = match ip {
-            Some(i) => Some(i),
-            None => what_vfn_ip().ok(),
-        };
+        if let Some(i) = ip {
+            default_config.profile.ip = i;
+            default_config.profile.vfn_ip = Some(i);
+        }
 
-        default_config.workspace.node_home =
-            config_path.clone().unwrap_or_else(|| what_home(None, None));
+        if let Some(p) = config_path {
+            default_config.workspace.node_home.clone_from(p);
+        }
 
         if let Some(u) = upstream_peer {
             default_config.profile.upstream_nodes = vec![u.to_owned()]
@@ -158,7 +157,7 @@ impl AppCfg {
         };
 
         if source_path.is_some() {
-          todo!("source_path is not implemented, using deprecated paths");
+            todo!("source_path is not implemented, using deprecated paths");
             // default_config.workspace.source_path = source_path.clone();
             // default_config.workspace.stdlib_bin_path = Some(
             //     source_path
@@ -179,7 +178,7 @@ impl AppCfg {
         }
 
         // skip questionnaire if CI
-        if MODE_0L.is_test() {
+        if *MODE_0L == NamedChain::TESTING {
             default_config.save_file()?;
 
             return Ok(default_config);
@@ -213,25 +212,21 @@ impl AppCfg {
         source_path: Option<PathBuf>,
     ) -> AppCfg {
         let config_path = swarm_path.join(&node_home).join("node.yaml");
-        let config = NodeConfig::load(&config_path)
+        let config = NodeConfig::load_from_path(&config_path)
             .unwrap_or_else(|_| panic!("Failed to load NodeConfig from file: {:?}", &config_path));
 
         // upstream configs
         let upstream_config_path = swarm_path.join(&node_home).join("node.yaml");
-        let upstream_config = NodeConfig::load(&upstream_config_path).unwrap_or_else(|_| {
-            panic!(
-                "Failed to load NodeConfig from file: {:?}",
-                &upstream_config_path
-            )
-        });
-        let upstream_url = Url::parse(
-            format!(
-                "http://localhost:{}",
-                upstream_config.json_rpc.address.port()
-            )
-            .as_str(),
-        )
-        .unwrap();
+        let upstream_config =
+            NodeConfig::load_from_path(&upstream_config_path).unwrap_or_else(|_| {
+                panic!(
+                    "Failed to load NodeConfig from file: {:?}",
+                    &upstream_config_path
+                )
+            });
+        let upstream_url =
+            Url::parse(format!("http://localhost:{}", upstream_config.api.address.port()).as_str())
+                .unwrap();
 
         let mut cfg = AppCfg {
             workspace: Workspace::default(),
@@ -257,10 +252,10 @@ impl AppCfg {
         let toml = toml::to_string(&self)?;
         let home_path = &self.workspace.node_home.clone();
         // create home path if doesn't exist, usually only in dev/ci environments.
-        fs::create_dir_all(&home_path)?;
+        fs::create_dir_all(home_path)?;
         let toml_path = home_path.join(CONFIG_FILE);
         let mut file = fs::File::create(&toml_path)?;
-        file.write(&toml.as_bytes())?;
+        file.write_all(toml.as_bytes())?;
 
         println!(
             "\nhost configs initialized, file saved to: {:?}",
@@ -270,24 +265,9 @@ impl AppCfg {
     }
 }
 
-/// Default configuration settings.
-///
-/// Note: if your needs are as simple as below, you can
-/// use `#[derive(Default)]` on OlCliConfig instead.
-impl Default for AppCfg {
-    fn default() -> Self {
-        Self {
-            workspace: Workspace::default(),
-            profile: Profile::default(),
-            chain_info: ChainInfo::default(),
-            tx_configs: TxConfigs::default(),
-        }
-    }
-}
-
 /// Information about the Chain to mined for
 #[derive(Clone, Debug, Deserialize, Serialize)]
-/// #[serde(deny_unknown_fields)]
+// #[serde(deny_unknown_fields)]
 pub struct Workspace {
     /// home directory of the diem node, may be the same as miner.
     pub node_home: PathBuf,
@@ -350,7 +330,7 @@ impl Default for ChainInfo {
 // #[serde(deny_unknown_fields)]
 pub struct Profile {
     /// The 0L account for the Miner and prospective validator. This is derived from auth_key
-    pub account: AccountAddress,
+    pub account: LegacyAddressV5,
 
     /// Miner Authorization Key for 0L Blockchain. Note: not the same as public key, nor account.
     pub auth_key: AuthenticationKey,
@@ -364,10 +344,12 @@ pub struct Profile {
     /// ip address of the validator fullnodee
     pub vfn_ip: Option<Ipv4Addr>,
 
-    // /// Node URL and and port to submit transactions. Defaults to localhost:8080
-    // pub default_node: Option<Url>,
+    /// Node URL and and port to submit transactions, in early v5 configs
+    #[serde(default)]
+    pub default_node: Option<Url>,
 
     /// Other nodes to connect for fallback connections
+    #[serde(default)]
     pub upstream_nodes: Vec<Url>,
 
     /// Link to another delay tower.
@@ -377,7 +359,7 @@ pub struct Profile {
 impl Default for Profile {
     fn default() -> Self {
         Self {
-            account: AccountAddress::from_hex_literal("0x0").unwrap(),
+            account: LegacyAddressV5::from_hex_literal("0x0").unwrap(),
             auth_key: AuthenticationKey::from_str(
                 "0000000000000000000000000000000000000000000000000000000000000000",
             )
@@ -385,7 +367,7 @@ impl Default for Profile {
             statement: "Protests rage across the nation".to_owned(),
             ip: "0.0.0.0".parse().unwrap(),
             vfn_ip: "0.0.0.0".parse().ok(),
-            // default_node: Some("http://localhost:8080".parse().expect("parse url")),
+            default_node: None,
             upstream_nodes: vec!["http://localhost:8080".parse().expect("parse url")],
             tower_link: None,
         }
@@ -428,17 +410,13 @@ pub struct TxConfigs {
 impl TxConfigs {
     /// get the user txs cost preferences for given transaction type
     pub fn get_cost(&self, tx_type: TxType) -> TxCost {
-        let ref baseline = self.baseline_cost.clone();
         let cost = match tx_type {
-            TxType::Critical => self.critical_txs_cost.as_ref().unwrap_or_else(|| baseline),
-            TxType::Mgmt => self
-                .management_txs_cost
-                .as_ref()
-                .unwrap_or_else(|| baseline),
-            TxType::Miner => self.miner_txs_cost.as_ref().unwrap_or_else(|| baseline),
-            TxType::Cheap => self.cheap_txs_cost.as_ref().unwrap_or_else(|| baseline),
+            TxType::Critical => &self.critical_txs_cost,
+            TxType::Mgmt => &self.management_txs_cost,
+            TxType::Miner => &self.miner_txs_cost,
+            TxType::Cheap => &self.cheap_txs_cost,
         };
-        cost.to_owned()
+        cost.as_ref().unwrap_or(&self.baseline_cost).to_owned()
     }
 }
 
@@ -495,11 +473,11 @@ fn default_cheap_txs_cost() -> Option<TxCost> {
 /// Get swarm configs from swarm files, swarm must be running
 pub fn get_swarm_rpc_url(mut swarm_path: PathBuf) -> (Url, Waypoint) {
     swarm_path.push("0/node.yaml");
-    let config = NodeConfig::load(&swarm_path)
+    let config = NodeConfig::load_from_path(&swarm_path)
         .unwrap_or_else(|_| panic!("Failed to load NodeConfig from file: {:?}", &swarm_path));
 
-    let url = Url::parse(format!("http://localhost:{}", config.json_rpc.address.port()).as_str())
-        .unwrap();
+    let url =
+        Url::parse(format!("http://localhost:{}", config.api.address.port()).as_str()).unwrap();
     let waypoint = config.base.waypoint.waypoint();
 
     (url, waypoint)
@@ -508,11 +486,17 @@ pub fn get_swarm_rpc_url(mut swarm_path: PathBuf) -> (Url, Waypoint) {
 /// Get swarm configs from swarm files, swarm must be running
 pub fn get_swarm_backup_service_url(mut swarm_path: PathBuf, swarm_id: u8) -> Result<Url, Error> {
     swarm_path.push(format!("{}/node.yaml", swarm_id));
-    let config = NodeConfig::load(&swarm_path)
+    let config = NodeConfig::load_from_path(&swarm_path)
         .unwrap_or_else(|_| panic!("Failed to load NodeConfig from file: {:?}", &swarm_path));
 
-    let url =
-        Url::parse(format!("http://localhost:{}", config.storage.address.port()).as_str()).unwrap();
+    let url = Url::parse(
+        format!(
+            "http://localhost:{}",
+            config.storage.backup_service_address.port()
+        )
+        .as_str(),
+    )
+    .unwrap();
 
     Ok(url)
 }
@@ -526,7 +510,7 @@ struct EpochJSON {
 pub fn bootstrap_waypoint_from_upstream(url: &mut Url) -> Result<(u64, Waypoint), Error> {
     url.set_port(Some(3030)).unwrap();
     let epoch_url = url.join("epoch.json").unwrap();
-    let g_res = reqwest::blocking::get(&epoch_url.to_string())?;
+    let g_res = reqwest::blocking::get(epoch_url.as_str())?;
     if g_res.status().is_success() {
         let txt = g_res.text()?;
         let epoch: EpochJSON = serde_json::from_str(&txt)?;
@@ -540,6 +524,7 @@ pub fn bootstrap_waypoint_from_upstream(url: &mut Url) -> Result<(u64, Waypoint)
 }
 
 #[derive(Serialize, Deserialize, Debug)]
+#[allow(dead_code)]
 struct WaypointRpc {
     result: Option<serde_json::Value>,
 }
@@ -553,14 +538,11 @@ pub fn bootstrap_waypoint_from_rpc(url: Url) -> Result<Waypoint, Error> {
 
     // let json: WaypointRpc = serde_json::from_value(resp.json().unwrap()).unwrap();
     let parsed: serde_json::Value = resp.json()?;
-    match &parsed["result"] {
-        serde_json::Value::Object(r) => {
-            if let serde_json::Value::String(waypoint) = &r["waypoint"] {
-                let w: Waypoint = waypoint.parse()?;
-                return Ok(w);
-            }
+    if let serde_json::Value::Object(r) = &parsed["result"] {
+        if let serde_json::Value::String(waypoint) = &r["waypoint"] {
+            let w: Waypoint = waypoint.parse()?;
+            return Ok(w);
         }
-        _ => {}
     }
     bail!("could not get waypoint from json-rpc, url: {:?} ", url)
 }
@@ -0,0 +1,4 @@
+//! Types of the retired 0L tools. Only the client config is built, the
+//! other files are kept for reference.
+
+pub mod config;
@@ -1,3 +1,4 @@
+pub mod deprecated;
 pub mod legacy_recovery_v6;
 pub mod sdk;
 pub mod version_five;
@@ -1,18 +1,37 @@
 use crate::{
-    config_wizard,
+    config_spec::{self, ConfigSpec},
+    config_wizard, doctor,
     get_genesis_artifacts::{download_genesis, get_genesis_waypoint},
     interactive::options::{fix_config, FixOptions},
-    make_yaml_public_fullnode::init_fullnode_yaml,
+    make_yaml_public_fullnode::{init_fullnode_yaml, FN_FILENAME},
+    make_yaml_validator::NODE_YAML_FILE,
+    migrate,
+    node_yaml_builder::NodeYamlOpts,
+    playlist_cli::PlaylistSub,
+    seed_discovery,
     validator_config::{validator_dialogue, vfn_dialogue},
+    vfn_pairing::{self, SignedVfnPairing, PAIRING_FILE},
 };
 use anyhow::{bail, Context, Result};
 use clap::Parser;
+use diem_crypto::{bls12381, ValidCryptoMaterialStringExt};
+use diem_genesis::config::HostAndPort;
+use diem_types::network_address::DnsName;
 use libra_types::{
-    core_types::network_playlist::NetworkPlaylist,
-    exports::{AccountAddress, AuthenticationKey, NamedChain},
+    core_types::{
+        app_cfg::{default_file_path, AddressBookEntry, AppCfg, NamedNetwork, CONFIG_FILE_NAME},
+        network_playlist::NetworkPlaylist,
+    },
+    exports::{AccountAddress, AuthenticationKey, Client, NamedChain},
     global_config_dir, ol_progress,
+    type_extensions::client_ext::ClientExt,
+};
+use libra_wallet::{
+    keys::VFN_FILE as VFN_IDENTITY_FILE,
+    secret_source::SecretSource,
+    utils::read_operator_file,
+    validator_files::{set_operator_key, OperatorFile, OPERATOR_FILE, OPERATOR_KEY_FILE},
 };
-use libra_wallet::{utils::read_operator_file, validator_files::OPERATOR_FILE};
 use std::path::PathBuf;
 use url::Url;
 
@@ -22,9 +41,13 @@ use url::Url;
 pub struct ConfigCli {
     #[clap(subcommand)]
     subcommand: Option<ConfigSub>,
-    /// Path for configs if not the default $HOME/.libra
-    #[clap(short('f'), long)]
-    path: Option<PathBuf>,
+    /// Directory for configs if not the default $HOME/.libra
+    #[clap(short('f'), long, alias = "path")]
+    home: Option<PathBuf>,
+    /// the libra-cli-config.yaml to use, defaults to the one in the config
+    /// directory
+    #[clap(long)]
+    config_path: Option<PathBuf>,
     /// optional. Which network to use as the default. Defaults to MAINNET other options: TESTNET, TESTING, DEVNET
     #[clap(short, long)]
     chain_name: Option<NamedChain>,
@@ -46,6 +69,10 @@ enum ConfigSub {
         /// use a private key to initialize. Warning: intended for testing only.
         #[clap(long)]
         test_private_key: Option<String>,
+        /// read the mnemonic or private key from: prompt, env:VAR, fd:N,
+        /// file:PATH, or keystore[:DIR]. Otherwise the account is chosen interactively
+        #[clap(long, conflicts_with("test_private_key"))]
+        secret_source: Option<SecretSource>,
 
         #[clap(long)]
         /// override the default fullnodes URLs
@@ -54,6 +81,17 @@ enum ConfigSub {
         #[clap(long, conflicts_with("fullnode_url"))]
         playlist_url: Option<Url>,
     },
+    /// Apply a declarative spec of profiles, networks, playlists, tx costs
+    /// and node settings, without prompts. Applying the same spec twice
+    /// changes nothing.
+    Apply {
+        /// the spec yaml
+        #[clap(short('f'), long("file"))]
+        spec: PathBuf,
+        /// only show what would change
+        #[clap(long)]
+        dry_run: bool,
+    },
     /// replace API URL, reset an address, remove a profile.
     Fix {
         /// optional, reset the address from mnemonic. Will also lookup on the chain for the actual address if you forgot it, or rotated your authkey.
@@ -70,12 +108,109 @@ enum ConfigSub {
     /// Show the addresses and configs on this device
     View {},
 
+    /// Migrate a config from an older version (e.g. the v5 0L.toml) to a
+    /// libra-cli-config.yaml. Lists every field which could not be carried
+    /// over, and backs up the original.
+    Migrate {
+        /// the old config file, defaults to 0L.toml in the config path
+        legacy_file: Option<PathBuf>,
+        /// optional, autopay batch files to take payees from. An
+        /// autopay_batch.json next to the old config is always read
+        #[clap(long)]
+        autopay_file: Vec<PathBuf>,
+        /// overwrite an existing libra-cli-config.yaml
+        #[clap(long)]
+        force: bool,
+    },
+
+    /// Add or remove named addresses and watch-only accounts. Entries can be
+    /// used in place of an address in `txs` and `query` as `@nickname`
+    AddressBook {
+        /// nickname of an entry to add or update, requires --account
+        #[clap(short, long, requires("account"))]
+        add: Option<String>,
+        /// the address of the entry being added
+        #[clap(long)]
+        account: Option<AccountAddress>,
+        /// include the account in `libra query portfolio`
+        #[clap(short, long)]
+        watch: bool,
+        /// optional description of the account
+        #[clap(short, long)]
+        note: Option<String>,
+        /// nickname of an entry to remove
+        #[clap(short, long, conflicts_with("add"))]
+        remove: Option<String>,
+    },
+
+    /// Add a named network (e.g. mainnet, testnet, a local swarm) with its own
+    /// playlist and default account. Requires --chain-name
+    AddNetwork {
+        /// name to select the network by
+        name: String,
+        /// a single fullnode URL for this network
+        #[clap(short('u'), long)]
+        fullnode_url: Option<Url>,
+        /// a URL for a network playlist to load nodes from
+        #[clap(long, conflicts_with("fullnode_url"))]
+        playlist_url: Option<Url>,
+        /// optional, profile to use by default on this network
+        #[clap(short, long)]
+        default_profile: Option<String>,
+    },
+    /// Switch all tools to use a named network
+    UseNetwork {
+        /// name of a network added with `add-network`
+        name: String,
+    },
+
+    /// Check a node's config files before starting it: the yaml parses, the
+    /// files it references exist with safe permissions, the waypoints agree,
+    /// the keys match the chain, and the ports and peers are reachable.
+    Doctor {
+        /// path to the node yaml, defaults to validator.yaml in the config path
+        #[clap(long)]
+        node_yaml: Option<PathBuf>,
+        /// optional, a node to check the on-chain validator config against.
+        /// Otherwise the libra-cli-config.yaml is used if present
+        #[clap(short('u'), long)]
+        url: Option<Url>,
+        /// skip the on-chain checks
+        #[clap(long)]
+        offline: bool,
+    },
+
+    /// Validator fullnode (VFN) setup
+    #[clap(subcommand)]
+    Vfn(VfnSub),
+
+    /// Sign, verify, or trust the keys of network playlists
+    #[clap(subcommand)]
+    Playlist(PlaylistSub),
+
     // COMMIT NOTE: we haven't used vendor tooling configs for anything.
     /// Generate validators' config file
     ValidatorInit {
         // just make the VFN file
         #[clap(short, long)]
         vfn: bool,
+        /// read the mnemonic from: prompt, env:VAR, fd:N, file:PATH, or keystore[:DIR]
+        #[clap(long)]
+        secret_source: Option<SecretSource>,
+    },
+
+    /// Record an operator key in operator.yaml, so the owner's mnemonic can
+    /// be kept offline. The validator account in the registration files is
+    /// unchanged. The operator key is the mnemonic's operator key unless
+    /// another is given. Then delegate on chain with
+    /// `libra txs validator delegate`
+    ValidatorOperator {
+        /// read the owner's mnemonic from: prompt, env:VAR, fd:N, file:PATH, or keystore[:DIR]
+        #[clap(long)]
+        secret_source: Option<SecretSource>,
+        /// use another key as the operator key
+        #[clap(long, conflicts_with = "secret_source")]
+        operator_secret_source: Option<SecretSource>,
     },
 
     /// Generate a fullnode dir and add fullnode.yaml from template
@@ -83,10 +218,88 @@ enum ConfigSub {
         /// path to libra config and data files defaults to $HOME/.libra
         #[clap(long)]
         home_path: Option<PathBuf>,
+        /// keep all history, disables pruning
+        #[clap(long)]
+        archive: bool,
+        #[clap(flatten)]
+        node: NodeYamlOpts,
+    },
+
+    /// Find seed peers among the fullnodes published by the current
+    /// validator set, and write the reachable ones into fullnode.yaml
+    DiscoverSeeds {
+        /// path to the fullnode yaml, defaults to fullnode.yaml in the config path
+        #[clap(long)]
+        fullnode_yaml: Option<PathBuf>,
+        /// optional, the node to read the validator set from. Otherwise the
+        /// libra-cli-config.yaml is used
+        #[clap(short('u'), long)]
+        url: Option<Url>,
+        /// the most seed peers to keep
+        #[clap(long, default_value_t = seed_discovery::MAX_SEEDS)]
+        max: usize,
+        /// only show the ranked seeds
+        #[clap(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum VfnSub {
+    /// Pair a validator and its VFN with a signed bundle
+    #[clap(subcommand)]
+    Pair(PairSub),
+}
+
+#[derive(clap::Subcommand)]
+enum PairSub {
+    /// On the validator host. Export a pairing bundle signed with the
+    /// validator's consensus key
+    Export {
+        /// optional, the validator's public host. Defaults to the host in operator.yaml
+        #[clap(long)]
+        host: Option<DnsName>,
+        /// optional, the VFN's public host and port, e.g. vfn.example.com:6182.
+        /// Updates operator.yaml if it changed, publish it on chain with
+        /// `libra txs validator update`
+        #[clap(long)]
+        vfn_host: Option<HostAndPort>,
+        /// where to write the bundle, defaults to vfn-pairing.json in the config path
+        #[clap(short, long)]
+        out: Option<PathBuf>,
+    },
+    /// On the VFN host. Check a pairing bundle and write vfn.yaml with the
+    /// validator as a trusted seed
+    Import {
+        /// the bundle exported on the validator
+        bundle: PathBuf,
+        /// optional, a node to check the bundle's keys against the chain.
+        /// Otherwise the libra-cli-config.yaml is used if present
+        #[clap(short('u'), long)]
+        url: Option<Url>,
+        /// skip checking the keys against the chain. The bundle is then
+        /// checked with --consensus-key, or the key in operator.yaml
+        #[clap(long)]
+        offline: bool,
+        /// the validator's consensus public key, for --offline
+        #[clap(long, requires = "offline")]
+        consensus_key: Option<String>,
     },
 }
 
 impl ConfigCli {
+    /// the config directory, by default $HOME/.libra
+    fn home(&self) -> PathBuf {
+        self.home.clone().unwrap_or_else(global_config_dir)
+    }
+
+    /// the app config file, by default the one in the config directory
+    fn config_file(&self) -> Option<PathBuf> {
+        self.config_path
+            .clone()
+            .or_else(|| self.home.as_ref().map(|h| h.join(CONFIG_FILE_NAME)))
+    }
+
     /// Executes the appropriate subcommand based on user input.
     pub async fn run(&self) -> Result<()> {
         match &self.subcommand {
@@ -99,17 +312,35 @@ impl ConfigCli {
                     reset_address: *reset_address,
                     remove_profile: remove_profile.clone(),
                     fullnode_url: force_url.clone(),
-                    config_path: self.path.clone(),
+                    config_path: self.config_file(),
                     chain_name: self.chain_name,
                 })
                 .await
             }
 
+            Some(ConfigSub::Apply { spec, dry_run }) => {
+                let home = self.home();
+                let spec = ConfigSpec::from_file(spec)?;
+                let diffs = config_spec::apply_spec(&spec, &home, *dry_run).await?;
+                if diffs.is_empty() {
+                    println!("already up to date");
+                }
+                for d in diffs {
+                    println!("--- {}", d.path.display());
+                    d.lines.iter().for_each(|l| println!("{l}"));
+                }
+                if *dry_run {
+                    println!("dry run, nothing was written");
+                }
+                Ok(())
+            }
+
             // Initialize configuration wizard
             Some(ConfigSub::Init {
                 force_address,
                 force_authkey,
                 test_private_key,
+                secret_source,
                 fullnode_url: url,
                 playlist_url,
             }) => {
@@ -128,9 +359,13 @@ impl ConfigCli {
                 config_wizard::wizard(
                     force_authkey.to_owned(),
                     force_address.to_owned(),
-                    self.path.to_owned(),
+                    self.home.to_owned(),
                     self.chain_name.to_owned(),
-                    test_private_key.to_owned(),
+                    SecretSource::from_args(
+                        secret_source.as_ref(),
+                        None,
+                        test_private_key.as_ref(),
+                    ),
                     playlist_url.to_owned(),
                     playlist,
                 )
@@ -140,8 +375,8 @@ impl ConfigCli {
             }
 
             // Initialize validator configuration
-            Some(ConfigSub::ValidatorInit { vfn }) => {
-                let home_dir = self.path.clone().unwrap_or_else(global_config_dir);
+            Some(ConfigSub::ValidatorInit { vfn, secret_source }) => {
+                let home_dir = self.home();
                 if *vfn {
                     vfn_dialogue(&home_dir, None, None).await?;
                     return Ok(());
@@ -158,14 +393,42 @@ impl ConfigCli {
                 // Download genesis block and initialize validators' configuration
                 download_genesis(Some(data_path.clone())).await?;
                 let _ = get_genesis_waypoint(Some(data_path.clone())).await?;
-                validator_dialogue(&data_path, None, self.chain_name).await?;
+                let secret = SecretSource::from_args(secret_source.as_ref(), None, None);
+                validator_dialogue(&data_path, None, self.chain_name, secret.as_ref()).await?;
                 println!("Validators' config initialized.");
                 Ok(())
             }
 
+            Some(ConfigSub::ValidatorOperator {
+                secret_source,
+                operator_secret_source,
+            }) => {
+                let home_dir = self.home();
+                // a key from elsewhere is already saved by its owner
+                let (operator_key, save_key) = match operator_secret_source {
+                    Some(o) => (o.account_key()?, false),
+                    None => {
+                        let owner = SecretSource::from_args(secret_source.as_ref(), None, None)
+                            .unwrap_or(SecretSource::Prompt);
+                        (owner.key_chain()?.child_1_operator.pri_key, true)
+                    }
+                };
+
+                set_operator_key(&home_dir, &operator_key, save_key)?;
+                println!("{OPERATOR_FILE} now records the operator key");
+                if save_key {
+                    println!(
+                        "the operator key is saved to {}, copy it to the node host and use `--secret-source file:PATH`",
+                        home_dir.join(OPERATOR_KEY_FILE).display()
+                    );
+                }
+                println!("NOTE: the owner must now delegate on chain with `libra txs validator delegate --recovery-account <ACCOUNT>`");
+                Ok(())
+            }
+
             // View validator and network configurations
             Some(ConfigSub::View {}) => {
-                let home_dir = self.path.clone().unwrap_or_else(global_config_dir);
+                let home_dir = self.home();
 
                 let public_keys_file = home_dir.join(OPERATOR_FILE);
 
@@ -207,12 +470,217 @@ impl ConfigCli {
                 Ok(())
             }
 
+            Some(ConfigSub::Migrate {
+                legacy_file,
+                autopay_file,
+                force,
+            }) => {
+                let home = self.home();
+                let legacy_file = legacy_file.clone().unwrap_or_else(|| home.join("0L.toml"));
+                let report = migrate::migrate(&legacy_file, &home, autopay_file, *force)?;
+                report.print();
+                Ok(())
+            }
+
+            Some(ConfigSub::AddressBook {
+                add,
+                account,
+                watch,
+                note,
+                remove,
+            }) => {
+                let path = self.config_file().unwrap_or_else(default_file_path);
+                let mut cfg = AppCfg::load(Some(path.clone()))?;
+
+                if let (Some(nickname), Some(acc)) = (add, account) {
+                    let mut entry = AddressBookEntry::new(nickname.to_owned(), *acc, *watch);
+                    entry.note.clone_from(note);
+                    cfg.add_address_book_entry(entry)?;
+                    cfg.save_file_to(&path)?;
+                }
+
+                if let Some(nickname) = remove {
+                    cfg.try_remove_address_book_entry(nickname)?;
+                    cfg.save_file_to(&path)?;
+                }
+
+                println!("{}", serde_json::to_string_pretty(&cfg.address_book)?);
+                Ok(())
+            }
+
+            Some(ConfigSub::AddNetwork {
+                name,
+                fullnode_url,
+                playlist_url,
+                default_profile,
+            }) => {
+                let chain_name = self
+                    .chain_name
+                    .context("adding a network requires --chain-name")?;
+                let path = self.config_file().unwrap_or_else(default_file_path);
+                let mut cfg = AppCfg::load(Some(path.clone()))?;
+
+                let playlist = if let Some(u) = fullnode_url {
+                    NetworkPlaylist::new(Some(u.to_owned()), Some(chain_name))
+                } else if let Some(p) = playlist_url {
+                    NetworkPlaylist::from_verified_playlist_url(
+                        p.to_owned(),
+                        Some(chain_name),
+                        &cfg.trusted_playlist_keys,
+                        cfg.configured_genesis(chain_name),
+                    )
+                    .await?
+                } else {
+                    NetworkPlaylist::default_for_network(Some(chain_name)).await?
+                };
+
+                let mut network = NamedNetwork::new(name.to_owned(), playlist);
+                network.default_profile.clone_from(default_profile);
+                cfg.add_named_network(network);
+                cfg.save_file_to(&path)?;
+                println!("added network '{name}', use it with `libra config use-network {name}`");
+                Ok(())
+            }
+
+            Some(ConfigSub::UseNetwork { name }) => {
+                let path = self.config_file().unwrap_or_else(default_file_path);
+                let mut cfg = AppCfg::load(Some(path.clone()))?;
+                cfg.use_network(name)?;
+                cfg.save_file_to(&path)?;
+                println!(
+                    "now using network '{}' on chain {}",
+                    name,
+                    cfg.active_chain_id()
+                );
+                Ok(())
+            }
+
+            Some(ConfigSub::Doctor {
+                node_yaml,
+                url,
+                offline,
+            }) => {
+                let node_yaml = node_yaml
+                    .clone()
+                    .unwrap_or_else(|| self.home().join(NODE_YAML_FILE));
+
+                let client = if *offline {
+                    None
+                } else if let Some(u) = url {
+                    Some(Client::new(u.to_owned()))
+                } else {
+                    match AppCfg::load(self.config_file()) {
+                        Ok(cfg) => Client::from_libra_config(&cfg, None)
+                            .await
+                            .ok()
+                            .map(|(c, _)| c),
+                        Err(_) => None,
+                    }
+                };
+                if client.is_none() && !offline {
+                    println!("WARN: no node to query, skipping on-chain checks");
+                }
+
+                let report = doctor::run_doctor(&node_yaml, client.as_ref()).await;
+                report.print();
+                if report.has_errors() {
+                    bail!("found problems with {}", node_yaml.display());
+                }
+                Ok(())
+            }
+
+            Some(ConfigSub::Vfn(VfnSub::Pair(PairSub::Export {
+                host,
+                vfn_host,
+                out,
+            }))) => {
+                let home = self.home();
+                let bundle = vfn_pairing::export_pairing(&home, host.to_owned())?;
+                let out = out.clone().unwrap_or_else(|| home.join(PAIRING_FILE));
+                std::fs::write(&out, serde_json::to_string_pretty(&bundle)?)?;
+                println!(
+                    "pairing bundle saved to {}, copy it to the VFN host and run `libra config vfn pair import`",
+                    out.display()
+                );
+
+                let changed = match vfn_host {
+                    Some(h) => vfn_pairing::set_vfn_host(&home, h.to_owned())?,
+                    None => false,
+                };
+                if changed {
+                    // submitted with the txs tool, which signs for the owner's
+                    // account in owner.yaml
+                    println!(
+                        "the VFN host in {OPERATOR_FILE} changed, publish it with `libra txs validator update --operator-file {}`",
+                        home.join(OPERATOR_FILE).display()
+                    );
+                }
+                Ok(())
+            }
+
+            Some(ConfigSub::Vfn(VfnSub::Pair(PairSub::Import {
+                bundle,
+                url,
+                offline,
+                consensus_key,
+            }))) => {
+                let home = self.home();
+                let signed: SignedVfnPairing =
+                    serde_json::from_str(&std::fs::read_to_string(bundle)?)?;
+
+                let validator_key = if *offline {
+                    match consensus_key {
+                        Some(k) => bls12381::PublicKey::from_encoded_string(k)
+                            .context("invalid --consensus-key")?,
+                        None => {
+                            OperatorFile::read(&home)
+                                .context("no key to check the bundle with, use --consensus-key")?
+                                .config
+                                .consensus_public_key
+                        }
+                    }
+                } else {
+                    let client = match url {
+                        Some(u) => Client::new(u.to_owned()),
+                        None => {
+                            let cfg = AppCfg::load(self.config_file()).context(
+                                "no node to check the bundle against, use --url or --offline",
+                            )?;
+                            Client::from_libra_config(&cfg, None).await?.0
+                        }
+                    };
+                    let key = signed.verify_onchain(&client).await?;
+                    println!(
+                        "bundle keys match validator {} on chain",
+                        signed.pairing.validator_account
+                    );
+                    key
+                };
+
+                let path = vfn_pairing::import_pairing(&home, &signed, &validator_key)?;
+                println!("VFN config saved to {}", path.display());
+                if !home.join(VFN_IDENTITY_FILE).exists() {
+                    println!(
+                        "NOTE: copy {} from the validator host to {} before starting the VFN",
+                        VFN_IDENTITY_FILE,
+                        home.display()
+                    );
+                }
+                Ok(())
+            }
+
+            Some(ConfigSub::Playlist(sub)) => sub.run(self.config_file()),
+
             // Initialize fullnode configuration
-            Some(ConfigSub::FullnodeInit { home_path }) => {
+            Some(ConfigSub::FullnodeInit {
+                home_path,
+                archive,
+                node,
+            }) => {
                 download_genesis(home_path.to_owned()).await?;
                 println!("downloaded genesis block");
 
-                let p = init_fullnode_yaml(home_path.to_owned(), true).await?;
+                let p = init_fullnode_yaml(home_path.to_owned(), true, *archive, node).await?;
 
                 println!("config created at {}", p.display());
 
@@ -220,6 +688,46 @@ impl ConfigCli {
 
                 Ok(())
             }
+
+            Some(ConfigSub::DiscoverSeeds {
+                fullnode_yaml,
+                url,
+                max,
+                dry_run,
+            }) => {
+                let fullnode_yaml = fullnode_yaml
+                    .clone()
+                    .unwrap_or_else(|| self.home().join(FN_FILENAME));
+                let client = match url {
+                    Some(u) => Client::new(u.to_owned()),
+                    None => {
+                        let cfg = AppCfg::load(self.config_file())
+                            .context("no node to read the validator set from, use --url")?;
+                        Client::from_libra_config(&cfg, None).await?.0
+                    }
+                };
+
+                let mut probed =
+                    seed_discovery::discover_seeds(&client, &fullnode_yaml, *max, *dry_run).await?;
+                probed.sort_by_key(|c| c.latency_ms.unwrap_or(u64::MAX));
+                for c in &probed {
+                    match (c.latency_ms, &c.error) {
+                        (Some(ms), _) => println!("{ms:>6}ms  {}  {}", c.validator, c.address),
+                        (None, e) => println!(
+                            "  failed  {}  {}: {}",
+                            c.validator,
+                            c.address,
+                            e.as_deref().unwrap_or("unreachable")
+                        ),
+                    }
+                }
+                if *dry_run {
+                    println!("dry run, nothing was written");
+                } else {
+                    println!("seed peers saved to {}", fullnode_yaml.display());
+                }
+                Ok(())
+            }
             _ => {
                 println!("Sometimes I'm right and I can be wrong. My own beliefs are in my song. The butcher, the banker, the drummer and then. Makes no difference what group I'm in.");
 
@@ -0,0 +1,649 @@
+//! Declarative configs for provisioning machines without prompts.
+//! A spec file describes the profiles, networks, playlists, tx costs and
+//! node settings a host should have. Applying it is idempotent: fields the
+//! spec does not mention are left alone, and applying the same spec twice
+//! changes nothing.
+
+use crate::{
+    make_yaml_public_fullnode::{FN_FILENAME, VFN_FILENAME},
+    make_yaml_validator::NODE_YAML_FILE,
+    node_yaml_builder::{
+        save_node_config, validator_vfn_address, NodePreset, NodeYamlBuilder, VfnUpstream,
+        VFN_NETWORK_PORT,
+    },
+};
+use anyhow::Context;
+use diem_config::{
+    config::{InitialSafetyRulesConfig, NodeConfig, Peer, PeerRole, SecureBackend, WaypointConfig},
+    network_id::NetworkId,
+};
+use diem_crypto::{ed25519::Ed25519PublicKey, x25519};
+use diem_types::{
+    account_address::from_identity_public_key, network_address::DnsName, waypoint::Waypoint,
+};
+use libra_types::{
+    core_types::{
+        app_cfg::{
+            AddressBookEntry, AppCfg, NamedNetwork, Profile, TxConfigs, TxCost, CONFIG_FILE_NAME,
+        },
+        network_playlist::NetworkPlaylist,
+    },
+    exports::{AccountAddress, AuthenticationKey, NamedChain},
+};
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use url::Url;
+
+/// The whole spec file. Every section is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigSpec {
+    /// default chain, e.g. "mainnet" or "testnet"
+    pub chain_id: Option<String>,
+    /// default profile, by nickname or address fragment
+    pub default_profile: Option<String>,
+    /// the named network all tools should use
+    pub active_network: Option<String>,
+    #[serde(default)]
+    pub profiles: Vec<ProfileSpec>,
+    /// playlists for the default networks, one per chain
+    #[serde(default)]
+    pub playlists: Vec<PlaylistSpec>,
+    #[serde(default)]
+    pub networks: Vec<NetworkSpec>,
+    pub tx_configs: Option<TxCostsSpec>,
+    #[serde(default)]
+    pub address_book: Vec<AddressBookEntry>,
+    #[serde(default)]
+    pub trusted_playlist_keys: Vec<Ed25519PublicKey>,
+    /// settings for the node yaml on this host
+    pub node: Option<NodeSpec>,
+}
+
+/// An account to add. Keys are never part of a spec.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileSpec {
+    pub account: AccountAddress,
+    /// defaults to the account address, as for accounts which never rotated keys
+    pub auth_key: Option<AuthenticationKey>,
+    pub nickname: Option<String>,
+    pub statement: Option<String>,
+    pub locale: Option<String>,
+}
+
+/// Nodes for a chain, either listed or fetched from a playlist url
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlaylistSpec {
+    pub chain_id: String,
+    #[serde(default)]
+    pub urls: Vec<Url>,
+    pub playlist_url: Option<Url>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkSpec {
+    pub name: String,
+    pub chain_id: String,
+    #[serde(default)]
+    pub urls: Vec<Url>,
+    pub playlist_url: Option<Url>,
+    pub default_profile: Option<String>,
+    pub tx_configs: Option<TxConfigs>,
+}
+
+impl NetworkSpec {
+    fn playlist(&self) -> PlaylistSpec {
+        PlaylistSpec {
+            chain_id: self.chain_id.clone(),
+            urls: self.urls.clone(),
+            playlist_url: self.playlist_url.clone(),
+        }
+    }
+}
+
+/// Only the costs listed are changed
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TxCostsSpec {
+    pub baseline_cost: Option<TxCost>,
+    pub critical_txs_cost: Option<TxCost>,
+    pub management_txs_cost: Option<TxCost>,
+    pub miner_txs_cost: Option<TxCost>,
+    pub cheap_txs_cost: Option<TxCost>,
+}
+
+/// Node yaml settings, see `NodeYamlBuilder`. Unset fields keep the preset's
+/// defaults.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NodeSpec {
+    pub preset: NodePreset,
+    /// defaults to validator.yaml, vfn.yaml or fullnode.yaml in the config path
+    pub file: Option<PathBuf>,
+    pub data_dir: Option<PathBuf>,
+    /// a fixed waypoint, otherwise genesis/waypoint.txt is read
+    pub waypoint: Option<Waypoint>,
+    pub validator_port: Option<u16>,
+    pub vfn_port: Option<u16>,
+    pub public_port: Option<u16>,
+    pub api_enabled: Option<bool>,
+    pub api_address: Option<SocketAddr>,
+    pub ledger_prune_window: Option<u64>,
+    pub state_prune_window: Option<u64>,
+    /// required for the vfn preset
+    pub val_net_pubkey: Option<x25519::PublicKey>,
+    pub val_host: Option<DnsName>,
+}
+
+impl NodeSpec {
+    fn path(&self, home: &Path) -> PathBuf {
+        self.file.clone().unwrap_or_else(|| {
+            home.join(match self.preset {
+                NodePreset::Validator => NODE_YAML_FILE,
+                NodePreset::Vfn => VFN_FILENAME,
+                NodePreset::PublicFullnode | NodePreset::Archive => FN_FILENAME,
+            })
+        })
+    }
+
+    fn builder(&self, home: &Path) -> anyhow::Result<NodeYamlBuilder> {
+        let mut b = NodeYamlBuilder::new(self.preset, home);
+        if let Some(w) = self.waypoint {
+            b = b.waypoint(w);
+        }
+        if let Some(d) = &self.data_dir {
+            b.data_dir.clone_from(d);
+        }
+        b.validator_port = self.validator_port.unwrap_or(b.validator_port);
+        b.vfn_port = self.vfn_port.unwrap_or(b.vfn_port);
+        b.public_port = self.public_port.unwrap_or(b.public_port);
+        b.api_enabled = self.api_enabled.unwrap_or(b.api_enabled);
+        b.api_address = self.api_address.unwrap_or(b.api_address);
+        b.ledger_prune_window = self.ledger_prune_window.or(b.ledger_prune_window);
+        b.state_prune_window = self.state_prune_window.or(b.state_prune_window);
+        if self.preset == NodePreset::Vfn {
+            let pk = self
+                .val_net_pubkey
+                .context("the vfn preset requires val_net_pubkey")?;
+            let host = self
+                .val_host
+                .clone()
+                .context("the vfn preset requires val_host")?;
+            b = b.vfn_upstream(pk, host);
+        }
+        Ok(b)
+    }
+
+    /// Change only the settings the spec lists in an existing node config,
+    /// so seeds and anything else added by hand are kept
+    fn merge_into(&self, config: &mut NodeConfig) -> anyhow::Result<()> {
+        if let Some(d) = &self.data_dir {
+            config.base.data_dir.clone_from(d);
+            if let SecureBackend::OnDiskStorage(s) = &mut config.consensus.safety_rules.backend {
+                s.set_data_dir(d.clone());
+            }
+        }
+        if let Some(w) = self.waypoint {
+            config.base.waypoint = WaypointConfig::FromConfig(w);
+            if let InitialSafetyRulesConfig::FromFile { waypoint, .. } =
+                &mut config.consensus.safety_rules.initial_safety_rules_config
+            {
+                *waypoint = WaypointConfig::FromConfig(w);
+            }
+        }
+
+        let ports = [
+            (NetworkId::Validator, self.validator_port),
+            (NetworkId::Vfn, self.vfn_port),
+            (NetworkId::Public, self.public_port),
+        ];
+        let networks = config
+            .validator_network
+            .iter_mut()
+            .chain(config.full_node_networks.iter_mut());
+        for net in networks {
+            if let Some((_, Some(port))) = ports.iter().find(|(id, _)| *id == net.network_id) {
+                net.listen_address = format!("/ip4/0.0.0.0/tcp/{port}").parse()?;
+            }
+        }
+
+        if let Some(e) = self.api_enabled {
+            config.api.enabled = e;
+        }
+        if let Some(a) = self.api_address {
+            config.api.address = a;
+        }
+
+        let pruner = &mut config.storage.storage_pruner_config;
+        if let Some(w) = self.ledger_prune_window {
+            pruner.ledger_pruner_config.prune_window = w;
+        }
+        if let Some(w) = self.state_prune_window {
+            pruner.state_merkle_pruner_config.prune_window = w;
+        }
+        if self.preset == NodePreset::Archive {
+            pruner.ledger_pruner_config.enable = false;
+            pruner.state_merkle_pruner_config.enable = false;
+            pruner.epoch_snapshot_pruner_config.enable = false;
+        }
+
+        if let (Some(pk), Some(host)) = (self.val_net_pubkey, &self.val_host) {
+            let upstream = VfnUpstream {
+                val_net_pubkey: pk,
+                val_host: host.clone(),
+            };
+            let addr = validator_vfn_address(&upstream, self.vfn_port.unwrap_or(VFN_NETWORK_PORT))?;
+            let vfn_net = config
+                .full_node_networks
+                .iter_mut()
+                .find(|n| n.network_id == NetworkId::Vfn)
+                .context("the node config has no vfn network for the validator")?;
+            vfn_net.seeds.insert(
+                from_identity_public_key(pk),
+                Peer::new(vec![addr], HashSet::from([pk]), PeerRole::Validator),
+            );
+        }
+        Ok(())
+    }
+
+    /// The existing node config with the spec merged in, or a new one built
+    /// from the preset
+    fn config(&self, home: &Path, current: Option<&str>) -> anyhow::Result<NodeConfig> {
+        match current {
+            Some(yaml) => {
+                let mut config: NodeConfig = serde_yaml::from_str(yaml).context(format!(
+                    "cannot parse the node config {}",
+                    self.path(home).display()
+                ))?;
+                self.merge_into(&mut config)?;
+                Ok(config)
+            }
+            None => self.builder(home)?.build(),
+        }
+    }
+}
+
+/// The changes a spec makes to one file
+#[derive(Debug)]
+pub struct FileDiff {
+    pub path: PathBuf,
+    /// unified-style lines, prefixed with "-" or "+"
+    pub lines: Vec<String>,
+}
+
+impl ConfigSpec {
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let s = std::fs::read_to_string(path)
+            .context(format!("cannot read spec file {}", path.display()))?;
+        serde_yaml::from_str(&s).context(format!("invalid spec file {}", path.display()))
+    }
+
+    /// Apply the spec to a config in memory
+    pub async fn apply_to(&self, cfg: &mut AppCfg) -> anyhow::Result<()> {
+        if let Some(c) = &self.chain_id {
+            cfg.workspace.default_chain_id = parse_chain(c)?;
+        }
+
+        for p in &self.profiles {
+            let i = match cfg
+                .user_profiles
+                .iter()
+                .position(|e| e.account == p.account)
+            {
+                Some(i) => i,
+                None => {
+                    let auth = p
+                        .auth_key
+                        .unwrap_or_else(|| AuthenticationKey::new(p.account.into_bytes()));
+                    cfg.user_profiles.push(Profile::new(auth, p.account));
+                    cfg.user_profiles.len() - 1
+                }
+            };
+            let profile = &mut cfg.user_profiles[i];
+            if let Some(a) = p.auth_key {
+                profile.auth_key = a;
+            }
+            if let Some(n) = &p.nickname {
+                profile.nickname.clone_from(n);
+            }
+            if let Some(s) = &p.statement {
+                profile.statement.clone_from(s);
+            }
+            if p.locale.is_some() {
+                profile.locale.clone_from(&p.locale);
+            }
+        }
+        if let Some(d) = &self.default_profile {
+            cfg.get_profile(Some(d.to_owned())).context(format!(
+                "default profile '{d}' is not in the config or spec"
+            ))?;
+            cfg.workspace.default_profile = Some(d.to_owned());
+        }
+
+        // trusted keys first, they apply to the playlists fetched below
+        for k in &self.trusted_playlist_keys {
+            cfg.add_trusted_playlist_key(k.to_owned());
+        }
+
+        for p in &self.playlists {
+            let chain = parse_chain(&p.chain_id)?;
+            let current = cfg
+                .network_playlist
+                .iter()
+                .find(|np| np.chain_name == chain);
+            if let Some(np) = p.resolve(current, &cfg.trusted_playlist_keys).await? {
+                cfg.maybe_add_custom_playlist(&np);
+            }
+        }
+
+        for n in &self.networks {
+            let existing = cfg.networks.iter().find(|e| e.name == n.name);
+            let current = existing.map(|e| &e.playlist);
+            let playlist = match n
+                .playlist()
+                .resolve(current, &cfg.trusted_playlist_keys)
+                .await?
+            {
+                Some(np) => np,
+                None => current
+                    .cloned()
+                    .context("network has no urls or playlist_url")?,
+            };
+            let mut network = NamedNetwork::new(n.name.to_owned(), playlist);
+            network.default_profile = n
+                .default_profile
+                .clone()
+                .or_else(|| existing.and_then(|e| e.default_profile.clone()));
+            network.tx_configs = n
+                .tx_configs
+                .clone()
+                .or_else(|| existing.and_then(|e| e.tx_configs.clone()));
+            cfg.add_named_network(network);
+        }
+        if let Some(name) = &self.active_network {
+            cfg.use_network(name)?;
+        }
+
+        if let Some(t) = &self.tx_configs {
+            let c = &mut cfg.tx_configs;
+            if let Some(b) = &t.baseline_cost {
+                c.baseline_cost = b.clone();
+            }
+            for (spec, cost) in [
+                (&t.critical_txs_cost, &mut c.critical_txs_cost),
+                (&t.management_txs_cost, &mut c.management_txs_cost),
+                (&t.miner_txs_cost, &mut c.miner_txs_cost),
+                (&t.cheap_txs_cost, &mut c.cheap_txs_cost),
+            ] {
+                if spec.is_some() {
+                    cost.clone_from(spec);
+                }
+            }
+        }
+
+        for e in &self.address_book {
+            cfg.add_address_book_entry(e.clone())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl PlaylistSpec {
+    /// The playlist the spec asks for, or `None` if the current one already
+    /// has the same nodes. Comparing the urls only keeps the sync status the
+    /// tools recorded.
+    async fn resolve(
+        &self,
+        current: Option<&NetworkPlaylist>,
+        trusted_keys: &[Ed25519PublicKey],
+    ) -> anyhow::Result<Option<NetworkPlaylist>> {
+        let chain = parse_chain(&self.chain_id)?;
+        let np = if let Some(u) = &self.playlist_url {
+            NetworkPlaylist::from_verified_playlist_url(
+                u.to_owned(),
+                Some(chain),
+                trusted_keys,
+                current.and_then(|p| p.genesis_hash),
+            )
+            .await?
+        } else if !self.urls.is_empty() {
+            let mut np = NetworkPlaylist::new(None, Some(chain));
+            np.nodes = vec![];
+            self.urls.iter().for_each(|u| np.add_url(u.to_owned()));
+            np
+        } else {
+            return Ok(None);
+        };
+
+        let same = current
+            .map(|c| c.chain_name == np.chain_name && c.all_urls().ok() == np.all_urls().ok())
+            .unwrap_or(false);
+        Ok(if same { None } else { Some(np) })
+    }
+}
+
+fn parse_chain(s: &str) -> anyhow::Result<NamedChain> {
+    NamedChain::from_str(s).map_err(|e| anyhow::anyhow!("unknown chain id '{s}': {e}"))
+}
+
+/// Apply a spec to the config in `home`, and to its node yaml if the spec
+/// has a node section. Never prompts. With `dry_run` nothing is written.
+/// Returns the diff of each file which changes.
+pub async fn apply_spec(
+    spec: &ConfigSpec,
+    home: &Path,
+    dry_run: bool,
+) -> anyhow::Result<Vec<FileDiff>> {
+    let cfg_path = home.join(CONFIG_FILE_NAME);
+    let mut cfg = if cfg_path.exists() {
+        AppCfg::load(Some(cfg_path.clone()))?
+    } else {
+        let mut c = AppCfg::default();
+        c.workspace.node_home = home.to_path_buf();
+        c
+    };
+    let before = if cfg_path.exists() {
+        std::fs::read_to_string(&cfg_path)?
+    } else {
+        String::new()
+    };
+
+    spec.apply_to(&mut cfg).await?;
+
+    let mut diffs = vec![];
+    let after = serde_yaml::to_string(&cfg)?;
+    if !same_yaml(&before, &after) {
+        diffs.push(FileDiff {
+            path: cfg_path,
+            lines: line_diff(&before, &after),
+        });
+        if !dry_run {
+            // the file compared above, which need not be the config's node_home
+            std::fs::create_dir_all(home)?;
+            std::fs::write(&cfg_path, &after)?;
+        }
+    }
+
+    if let Some(node) = &spec.node {
+        let path = node.path(home);
+        let current = std::fs::read_to_string(&path).ok();
+        let config = node.config(home, current.as_deref())?;
+        let current = current.unwrap_or_default();
+        let yaml = serde_yaml::to_string(&config)?;
+        if !same_yaml(&current, &yaml) {
+            diffs.push(FileDiff {
+                lines: line_diff(&current, &yaml),
+                path: path.clone(),
+            });
+            if !dry_run {
+                save_node_config(&config, &path, node.preset == NodePreset::Validator)?;
+            }
+        }
+    }
+
+    Ok(diffs)
+}
+
+/// compare the parsed files, so formatting alone is not a change
+fn same_yaml(a: &str, b: &str) -> bool {
+    match (
+        serde_yaml::from_str::<serde_yaml::Value>(a),
+        serde_yaml::from_str::<serde_yaml::Value>(b),
+    ) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// A minimal line diff from the longest common subsequence. The files are
+/// small enough that the quadratic table does not matter.
+fn line_diff(before: &str, after: &str) -> Vec<String> {
+    let a: Vec<&str> = before.lines().collect();
+    let b: Vec<&str> = after.lines().collect();
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut out = vec![];
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            out.push(format!("+ {}", b[j]));
+            j += 1;
+        } else {
+            out.push(format!("- {}", a[i]));
+            i += 1;
+        }
+    }
+    out
+}
+
+#[tokio::test]
+async fn apply_is_idempotent() {
+    let home = diem_temppath::TempPath::new();
+    home.create_as_dir().unwrap();
+    let spec: ConfigSpec = serde_yaml::from_str(
+        r#"
+chain_id: testing
+default_profile: alice
+profiles:
+  - account: "0x87515d94a244235a1433d7117bc0cb154c613c2f4b1e67ca8d98a542ee3f59f5"
+    nickname: alice
+networks:
+  - name: local
+    chain_id: testing
+    urls: ["http://localhost:8080"]
+active_network: local
+tx_configs:
+  baseline_cost:
+    max_gas_unit_for_tx: 5000
+    coin_price_per_unit: 100
+    user_tx_timeout: 5000
+address_book:
+  - nickname: bob
+    account: "0x88e74dfed34420f2ad8032148280a84b"
+    watch: true
+"#,
+    )
+    .unwrap();
+
+    // a dry run writes nothing
+    let diffs = apply_spec(&spec, home.path(), true).await.unwrap();
+    assert_eq!(diffs.len(), 1);
+    assert!(!home.path().join(CONFIG_FILE_NAME).exists());
+
+    let diffs = apply_spec(&spec, home.path(), false).await.unwrap();
+    assert_eq!(diffs.len(), 1);
+    let cfg = AppCfg::load(Some(home.path().join(CONFIG_FILE_NAME))).unwrap();
+    assert_eq!(cfg.active_chain_id(), NamedChain::TESTING);
+    assert_eq!(cfg.get_profile(None).unwrap().nickname, "alice");
+    assert_eq!(cfg.tx_configs.baseline_cost.max_gas_unit_for_tx, 5000);
+    assert!(cfg.resolve_alias("@bob").is_ok());
+
+    // the second run has nothing to do
+    assert!(apply_spec(&spec, home.path(), false)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // typos are caught instead of ignored
+    assert!(serde_yaml::from_str::<ConfigSpec>("chain: testing").is_err());
+}
+
+#[tokio::test]
+async fn apply_keeps_unlisted_fields() {
+    use diem_types::network_address::NetworkAddress;
+
+    let home = diem_temppath::TempPath::new();
+    home.create_as_dir().unwrap();
+    let home = home.path();
+
+    // a config saved elsewhere is still updated in place
+    let other = diem_temppath::TempPath::new();
+    let mut cfg = AppCfg::default();
+    cfg.workspace.node_home = other.path().to_path_buf();
+    std::fs::write(
+        home.join(CONFIG_FILE_NAME),
+        serde_yaml::to_string(&cfg).unwrap(),
+    )
+    .unwrap();
+
+    // a seed added by hand, which the spec does not know about
+    let path = home.join(FN_FILENAME);
+    let mut config = NodeYamlBuilder::new(NodePreset::PublicFullnode, home)
+        .build()
+        .unwrap();
+    let pk = x25519::PrivateKey::from([3u8; 32]).public_key();
+    let addr: NetworkAddress = format!("/ip4/10.0.0.1/tcp/6182/noise-ik/0x{pk}/handshake/0")
+        .parse()
+        .unwrap();
+    config.full_node_networks[0].seeds.insert(
+        from_identity_public_key(pk),
+        Peer::new(vec![addr], HashSet::from([pk]), PeerRole::Upstream),
+    );
+    std::fs::write(&path, serde_yaml::to_string(&config).unwrap()).unwrap();
+
+    let spec: ConfigSpec = serde_yaml::from_str(
+        r#"
+chain_id: testing
+node:
+  preset: public-fullnode
+  api_enabled: false
+  public_port: 7182
+"#,
+    )
+    .unwrap();
+    let diffs = apply_spec(&spec, home, false).await.unwrap();
+    assert_eq!(diffs.len(), 2);
+    assert!(!other.path().join(CONFIG_FILE_NAME).exists());
+    let cfg = AppCfg::load(Some(home.join(CONFIG_FILE_NAME))).unwrap();
+    assert_eq!(cfg.workspace.default_chain_id, NamedChain::TESTING);
+
+    let config: NodeConfig =
+        serde_yaml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert!(!config.api.enabled);
+    let public = &config.full_node_networks[0];
+    assert_eq!(public.seeds.len(), 1);
+    assert_eq!(public.listen_address.to_string(), "/ip4/0.0.0.0/tcp/7182");
+
+    assert!(apply_spec(&spec, home, false).await.unwrap().is_empty());
+}
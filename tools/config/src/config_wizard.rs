@@ -1,6 +1,5 @@
 use crate::interactive::account_selection;
 use anyhow::Context;
-use diem_crypto::{ed25519::Ed25519PrivateKey, ValidCryptoMaterialStringExt};
 use diem_types::chain_id::NamedChain;
 use libra_types::{
     core_types::{app_cfg::AppCfg, network_playlist::NetworkPlaylist},
@@ -8,6 +7,7 @@ use libra_types::{
     ol_progress,
     type_extensions::client_ext::ClientExt,
 };
+use libra_wallet::secret_source::SecretSource;
 use std::path::PathBuf;
 use url::Url;
 
@@ -16,7 +16,7 @@ pub async fn wizard(
     force_address: Option<AccountAddress>,
     config_dir: Option<PathBuf>,
     chain_name: Option<NamedChain>,
-    test_private_key: Option<String>,
+    secret: Option<SecretSource>,
     playlist_url: Option<Url>,
     network_playlist: Option<NetworkPlaylist>,
 ) -> anyhow::Result<AppCfg> {
@@ -24,8 +24,8 @@ pub async fn wizard(
     // Determine authkey and address based on provided options or prompt for account details
     let (authkey, mut address) = if force_authkey.is_some() && force_address.is_some() {
         (force_authkey.unwrap(), force_address.unwrap())
-    } else if let Some(pk_string) = test_private_key {
-        let pk = Ed25519PrivateKey::from_encoded_string(&pk_string)?;
+    } else if let Some(source) = secret {
+        let pk = source.account_key()?;
         let account_keys = libra_wallet::account_keys::get_account_from_private(&pk);
         (account_keys.auth_key, account_keys.account)
     } else {
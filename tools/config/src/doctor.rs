@@ -0,0 +1,533 @@
+//! Preflight checks of a node's config files, before the node is started.
+//! Each finding comes with a suggestion of how to fix it.
+
+use anyhow::Context;
+use diem_config::config::{
+    Identity, IdentityBlob, InitialSafetyRulesConfig, NetworkConfig, NodeConfig, SecureBackend,
+    WaypointConfig,
+};
+use diem_crypto::PrivateKey;
+use diem_types::{
+    network_address::{NetworkAddress, Protocol},
+    transaction::Transaction,
+    waypoint::Waypoint,
+};
+use libra_types::exports::Client;
+use std::{
+    fmt,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+/// how long to wait for a seed peer to accept a tcp connection
+const PEER_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Ok,
+    Warn,
+    Error,
+}
+
+/// The result of a single check
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+    /// how to fix it, if there is a problem
+    pub fix: Option<String>,
+}
+
+impl Finding {
+    fn ok(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warn,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn error(message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.severity {
+            Severity::Ok => "OK   ",
+            Severity::Warn => "WARN ",
+            Severity::Error => "ERROR",
+        };
+        write!(f, "[{}] {}", label, self.message)?;
+        if let Some(fix) = &self.fix {
+            write!(f, "\n        fix: {}", fix)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DoctorReport {
+    pub findings: Vec<Finding>,
+}
+
+impl DoctorReport {
+    pub fn has_errors(&self) -> bool {
+        self.findings.iter().any(|f| f.severity == Severity::Error)
+    }
+
+    pub fn print(&self) {
+        self.findings.iter().for_each(|f| println!("{f}"));
+    }
+}
+
+/// Run all checks against a node yaml. If there is no client, the on-chain
+/// checks are skipped.
+pub async fn run_doctor(node_yaml: &Path, client: Option<&Client>) -> DoctorReport {
+    let mut report = DoctorReport::default();
+
+    // parse only the yaml, loading from the path would also read genesis.blob
+    // and fail before check_files can report it missing
+    let parsed = std::fs::read_to_string(node_yaml)
+        .context(format!("cannot read {}", node_yaml.display()))
+        .and_then(|s| serde_yaml::from_str::<NodeConfig>(&s).map_err(anyhow::Error::from));
+    let config = match parsed {
+        Ok(c) => {
+            report
+                .findings
+                .push(Finding::ok(format!("{} parses", node_yaml.display())));
+            c
+        }
+        Err(e) => {
+            report.findings.push(Finding::error(
+                format!("{} does not parse: {:#}", node_yaml.display(), e),
+                "regenerate it with `libra config validator-init` or `libra config fullnode-init`, and re-apply your changes",
+            ));
+            // nothing else can be checked
+            return report;
+        }
+    };
+
+    report.findings.extend(check_files(&config));
+    report.findings.extend(check_waypoints(&config));
+    report.findings.extend(check_ports(&config));
+    report.findings.extend(check_seed_peers(&config).await);
+
+    if let Some(c) = client {
+        report
+            .findings
+            .extend(check_onchain_identity(&config, c).await);
+    }
+
+    report
+}
+
+/// every file the config refers to, and whether it holds keys
+fn referenced_files(config: &NodeConfig) -> Vec<(PathBuf, bool)> {
+    let mut files = vec![(config.execution.genesis_file_location.clone(), false)];
+
+    if let WaypointConfig::FromFile(p) = &config.base.waypoint {
+        files.push((p.clone(), false));
+    }
+
+    if let InitialSafetyRulesConfig::FromFile {
+        identity_blob_path,
+        waypoint,
+        ..
+    } = &config.consensus.safety_rules.initial_safety_rules_config
+    {
+        files.push((identity_blob_path.clone(), true));
+        if let WaypointConfig::FromFile(p) = waypoint {
+            files.push((p.clone(), false));
+        }
+    }
+
+    all_networks(config).for_each(|n| {
+        if let Identity::FromFile(i) = &n.identity {
+            files.push((i.path.clone(), true));
+        }
+    });
+
+    files.sort();
+    files.dedup();
+    files
+}
+
+fn all_networks(config: &NodeConfig) -> impl Iterator<Item = &NetworkConfig> {
+    config
+        .validator_network
+        .iter()
+        .chain(config.full_node_networks.iter())
+}
+
+/// files exist, and the ones with keys are only readable by the user
+pub fn check_files(config: &NodeConfig) -> Vec<Finding> {
+    let mut findings = vec![];
+
+    for (path, has_keys) in referenced_files(config) {
+        if !path.exists() {
+            findings.push(Finding::error(
+                format!("{} does not exist", path.display()),
+                "check the path in the node yaml, or restore the file from your backup",
+            ));
+            continue;
+        }
+        if has_keys {
+            findings.push(check_private_permissions(&path));
+        } else {
+            findings.push(Finding::ok(format!("{} exists", path.display())));
+        }
+    }
+
+    if let SecureBackend::OnDiskStorage(s) = &config.consensus.safety_rules.backend {
+        let path = if s.path.is_relative() {
+            config.base.data_dir.join(&s.path)
+        } else {
+            s.path.clone()
+        };
+        if path.exists() {
+            findings.push(check_private_permissions(&path));
+        } else if config.base.role.is_validator() {
+            findings.push(Finding::warn(
+                format!("safety rules storage {} does not exist yet", path.display()),
+                "this is expected before the first start. If the node has run before, the safety data was lost and the node must sync before voting",
+            ));
+        }
+    }
+
+    findings
+}
+
+#[cfg(unix)]
+fn check_private_permissions(path: &Path) -> Finding {
+    use std::os::unix::fs::PermissionsExt;
+    match std::fs::metadata(path) {
+        Ok(m) if m.permissions().mode() & 0o077 != 0 => Finding::warn(
+            format!(
+                "{} holds keys but is readable by other users (mode {:o})",
+                path.display(),
+                m.permissions().mode() & 0o777
+            ),
+            format!("chmod 600 {}", path.display()),
+        ),
+        Ok(_) => Finding::ok(format!(
+            "{} exists, permissions are private",
+            path.display()
+        )),
+        Err(e) => Finding::error(
+            format!("cannot read {}: {}", path.display(), e),
+            "check the file is owned by the user running the node",
+        ),
+    }
+}
+
+#[cfg(not(unix))]
+fn check_private_permissions(path: &Path) -> Finding {
+    Finding::ok(format!("{} exists", path.display()))
+}
+
+fn read_waypoint(w: &WaypointConfig) -> anyhow::Result<Option<Waypoint>> {
+    match w {
+        WaypointConfig::FromConfig(w) => Ok(Some(*w)),
+        WaypointConfig::FromFile(p) => {
+            let s = std::fs::read_to_string(p)
+                .context(format!("cannot read waypoint file {}", p.display()))?;
+            Ok(Some(Waypoint::from_str(s.trim()).context(format!(
+                "cannot parse waypoint in {}",
+                p.display()
+            ))?))
+        }
+        // stored in secure storage, cannot be checked offline
+        _ => Ok(None),
+    }
+}
+
+/// The waypoint of the node, of the safety rules, and the one published with
+/// the genesis file should all agree.
+/// NOTE: after a rescue all three must be the rescue waypoint, and the genesis
+/// file must be the rescue blob.
+pub fn check_waypoints(config: &NodeConfig) -> Vec<Finding> {
+    let mut findings = vec![];
+
+    let base = match read_waypoint(&config.base.waypoint) {
+        Ok(Some(w)) => w,
+        Ok(None) => {
+            findings.push(Finding::warn(
+                "the base waypoint is not in a file or in the config, it cannot be checked",
+                "set `base.waypoint` to `from_file` or `from_config`",
+            ));
+            return findings;
+        }
+        Err(e) => {
+            findings.push(Finding::error(
+                format!("{:#}", e),
+                "download the waypoint for your network with `libra config fullnode-init`",
+            ));
+            return findings;
+        }
+    };
+
+    if let InitialSafetyRulesConfig::FromFile { waypoint, .. } =
+        &config.consensus.safety_rules.initial_safety_rules_config
+    {
+        match read_waypoint(waypoint) {
+            Ok(Some(w)) if w != base => findings.push(Finding::error(
+                format!("safety rules waypoint {} does not match base waypoint {}", w, base),
+                "set `consensus.safety_rules.initial_safety_rules_config.waypoint` to the same waypoint as `base.waypoint`",
+            )),
+            Ok(Some(_)) => findings.push(Finding::ok("safety rules waypoint matches")),
+            Ok(None) => {}
+            Err(e) => findings.push(Finding::error(
+                format!("{:#}", e),
+                "point the safety rules waypoint to the same file as `base.waypoint`",
+            )),
+        }
+    }
+
+    let genesis = &config.execution.genesis_file_location;
+    if genesis.exists() {
+        let parses = std::fs::read(genesis)
+            .ok()
+            .and_then(|b| bcs::from_bytes::<Transaction>(&b).ok())
+            .is_some();
+        if !parses {
+            findings.push(Finding::error(
+                format!("{} is not a genesis transaction", genesis.display()),
+                "download the genesis blob again with `libra config fullnode-init`",
+            ));
+        }
+
+        // the waypoint published with the genesis blob
+        let published = genesis.with_file_name("waypoint.txt");
+        if let Ok(Some(w)) = read_waypoint(&WaypointConfig::FromFile(published.clone())) {
+            if w == base {
+                findings.push(Finding::ok("genesis waypoint matches"));
+            } else {
+                findings.push(Finding::error(
+                    format!(
+                        "{} has waypoint {}, but the node is using {}",
+                        published.display(),
+                        w,
+                        base
+                    ),
+                    "the genesis blob and waypoint must come from the same genesis or rescue, update `base.waypoint` or `execution.genesis_file_location`",
+                ));
+            }
+        }
+    }
+
+    findings
+}
+
+fn tcp_port(addr: &NetworkAddress) -> Option<u16> {
+    addr.as_slice().iter().find_map(|p| match p {
+        Protocol::Tcp(port) => Some(*port),
+        _ => None,
+    })
+}
+
+/// host and port to dial, for addresses on ip or dns
+fn dial_target(addr: &NetworkAddress) -> Option<String> {
+    let host = addr.as_slice().iter().find_map(|p| match p {
+        Protocol::Ip4(ip) => Some(ip.to_string()),
+        Protocol::Ip6(ip) => Some(format!("[{ip}]")),
+        Protocol::Dns(n) | Protocol::Dns4(n) | Protocol::Dns6(n) => Some(n.to_string()),
+        _ => None,
+    })?;
+    Some(format!("{}:{}", host, tcp_port(addr)?))
+}
+
+/// the ports the node listens on are not taken
+pub fn check_ports(config: &NodeConfig) -> Vec<Finding> {
+    let mut ports: Vec<u16> = all_networks(config)
+        .filter_map(|n| tcp_port(&n.listen_address))
+        .collect();
+    if config.api.enabled {
+        ports.push(config.api.address.port());
+    }
+
+    ports
+        .into_iter()
+        .map(|port| match TcpListener::bind(("0.0.0.0", port)) {
+            Ok(_) => Finding::ok(format!("port {port} is free")),
+            Err(e) => Finding::warn(
+                format!("port {port} is not available: {e}"),
+                "if the node is already running this is expected, otherwise stop the process using the port or change the port in the node yaml",
+            ),
+        })
+        .collect()
+}
+
+/// the seed peers of each network accept connections
+pub async fn check_seed_peers(config: &NodeConfig) -> Vec<Finding> {
+    let mut targets = vec![];
+    all_networks(config).for_each(|n| {
+        n.seed_addrs
+            .values()
+            .flatten()
+            .chain(n.seeds.values().flat_map(|p| p.addresses.iter()))
+            .filter_map(dial_target)
+            .for_each(|t| targets.push((n.network_id, t)));
+    });
+
+    if targets.is_empty() && !config.base.role.is_validator() {
+        return vec![Finding::warn(
+            "no seed peers are configured",
+            "add seed peers with `libra config fullnode-init`, the node cannot sync without them",
+        )];
+    }
+
+    let mut findings = vec![];
+    for (network_id, target) in targets {
+        let res = tokio::time::timeout(PEER_TIMEOUT, tokio::net::TcpStream::connect(&target)).await;
+        match res {
+            Ok(Ok(_)) => findings.push(Finding::ok(format!(
+                "seed peer {target} on the {network_id} network is reachable"
+            ))),
+            _ => findings.push(Finding::warn(
+                format!("seed peer {target} on the {network_id} network is not reachable"),
+                "the peer may be down, or a firewall is blocking outbound connections. Remove it if it stays unreachable",
+            )),
+        }
+    }
+    findings
+}
+
+/// The keys in the validator identity file are the ones registered on chain.
+pub async fn check_onchain_identity(config: &NodeConfig, client: &Client) -> Vec<Finding> {
+    let identity_path = match &config.consensus.safety_rules.initial_safety_rules_config {
+        InitialSafetyRulesConfig::FromFile {
+            identity_blob_path, ..
+        } if config.base.role.is_validator() => identity_blob_path.clone(),
+        _ => return vec![],
+    };
+
+    match compare_identity(&identity_path, client).await {
+        Ok(findings) => findings,
+        Err(e) => vec![Finding::warn(
+            format!("could not compare identity with the chain: {:#}", e),
+            "check the identity file is readable, and that the node url is reachable",
+        )],
+    }
+}
+
+async fn compare_identity(identity_path: &Path, client: &Client) -> anyhow::Result<Vec<Finding>> {
+    let blob = IdentityBlob::from_file(identity_path)?;
+    let account = blob
+        .account_address
+        .context("no account address in the identity file")?;
+
+    let onchain = libra_query::account_queries::get_val_config(client, account)
+        .await
+        .context(format!("no ValidatorConfig found for {account}"))?;
+
+    let mut findings = vec![];
+    if let Some(k) = &blob.consensus_private_key {
+        if k.public_key() == onchain.consensus_public_key {
+            findings.push(Finding::ok("consensus key matches the chain"));
+        } else {
+            findings.push(Finding::error(
+                "the consensus key in the identity file is not the one registered on chain",
+                "restore the identity file this validator registered with",
+            ));
+        }
+    }
+
+    let network_key = blob.network_private_key.public_key();
+    let onchain_keys: Vec<_> = onchain
+        .validator_network_addresses()?
+        .iter()
+        .filter_map(|a| a.find_noise_proto())
+        .collect();
+    if onchain_keys.contains(&network_key) {
+        findings.push(Finding::ok("validator network key matches the chain"));
+    } else {
+        findings.push(Finding::error(
+            "the validator network key in the identity file is not in the on-chain network addresses",
+            "other validators will reject connections, restore the identity file or register the new address with `libra txs validator update`",
+        ));
+    }
+
+    Ok(findings)
+}
+
+#[test]
+fn doctor_finds_missing_files() {
+    use crate::node_yaml_builder::{NodePreset, NodeYamlBuilder};
+    let home = diem_temppath::TempPath::new();
+    home.create_as_dir().unwrap();
+
+    let config = NodeYamlBuilder::new(NodePreset::Validator, home.path())
+        .build()
+        .unwrap();
+
+    let findings = check_files(&config);
+    let missing: Vec<_> = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .collect();
+    // genesis, waypoint and the identity file
+    assert_eq!(missing.len(), 3);
+    assert!(missing.iter().all(|f| f.fix.is_some()));
+
+    // the waypoint cannot be read, so the waypoint check fails too
+    assert!(check_waypoints(&config)
+        .iter()
+        .any(|f| f.severity == Severity::Error));
+
+    // an identity file readable by others is flagged
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let id = home.path().join("validator-identity.yaml");
+        std::fs::write(&id, "").unwrap();
+        std::fs::set_permissions(&id, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(check_private_permissions(&id).severity, Severity::Warn);
+        std::fs::set_permissions(&id, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert_eq!(check_private_permissions(&id).severity, Severity::Ok);
+    }
+}
+
+#[tokio::test]
+async fn doctor_runs_without_genesis() {
+    use crate::node_yaml_builder::{save_node_config, NodePreset, NodeYamlBuilder};
+    let home = diem_temppath::TempPath::new();
+    home.create_as_dir().unwrap();
+
+    let config = NodeYamlBuilder::new(NodePreset::Validator, home.path())
+        .build()
+        .unwrap();
+    let node_yaml = home.path().join("validator.yaml");
+    save_node_config(&config, &node_yaml, false).unwrap();
+    assert!(!config.execution.genesis_file_location.exists());
+
+    let report = run_doctor(&node_yaml, None).await;
+    // the yaml parses, and the missing genesis is reported by the file check
+    assert_eq!(report.findings[0].severity, Severity::Ok);
+    let genesis = config.execution.genesis_file_location.display().to_string();
+    assert!(report
+        .findings
+        .iter()
+        .any(|f| f.severity == Severity::Error && f.message.contains(&genesis)));
+    // the other checks still ran
+    assert!(report
+        .findings
+        .iter()
+        .any(|f| f.message.starts_with("port ")));
+    assert!(report.has_errors());
+}
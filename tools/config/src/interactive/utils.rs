@@ -31,7 +31,7 @@ pub fn display_existing_profiles(cfg: &AppCfg) {
         println!("  No networks configured");
     } else {
         for (i, np) in cfg.network_playlist.iter().enumerate() {
-            let current_chain = cfg.workspace.default_chain_id;
+            let current_chain = cfg.active_chain_id();
             let is_default = current_chain == np.chain_name;
             let default_marker = if is_default { " (default)" } else { "" };
 
@@ -44,6 +44,21 @@ pub fn display_existing_profiles(cfg: &AppCfg) {
             );
         }
     }
+
+    if !cfg.networks.is_empty() {
+        println!("\nNamed networks:");
+        for n in cfg.networks.iter() {
+            let is_active = cfg.workspace.active_network.as_ref() == Some(&n.name);
+            let active_marker = if is_active { " (active)" } else { "" };
+            println!(
+                "  - {} {:?} ({} URLs){}",
+                n.name,
+                n.playlist.chain_name,
+                n.playlist.nodes.len(),
+                active_marker
+            );
+        }
+    }
 }
 
 /// Get available chains that are not yet configured
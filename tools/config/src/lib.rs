@@ -1,9 +1,16 @@
 pub mod config_cli;
+pub mod config_spec;
 pub mod config_wizard;
+pub mod doctor;
 pub mod get_genesis_artifacts;
 pub mod interactive;
 pub mod make_profile; // TODO: deprecated?
 pub mod make_yaml_public_fullnode;
 pub mod make_yaml_validator;
+pub mod migrate;
+pub mod node_yaml_builder;
+pub mod playlist_cli;
+pub mod seed_discovery;
 pub mod validator_config;
 pub mod validator_registration;
+pub mod vfn_pairing;
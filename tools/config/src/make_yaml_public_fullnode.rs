@@ -12,7 +12,10 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use crate::get_genesis_artifacts::get_genesis_waypoint;
+use crate::{
+    get_genesis_artifacts::get_genesis_waypoint,
+    node_yaml_builder::{save_node_config, NodePreset, NodeYamlBuilder, NodeYamlOpts},
+};
 
 pub const FN_FILENAME: &str = "fullnode.yaml";
 pub const VFN_FILENAME: &str = "vfn.yaml";
@@ -27,23 +30,28 @@ struct GithubContent {
     download_url: Option<String>,
 }
 
-/// fetch seed peers and make a yaml file from template
+/// fetch seed peers and make a fullnode yaml, optionally keeping all history
 pub async fn init_fullnode_yaml(
     home_dir: Option<PathBuf>,
     overwrite_peers: bool,
+    archive: bool,
+    opts: &NodeYamlOpts,
 ) -> anyhow::Result<PathBuf> {
     let waypoint = get_genesis_waypoint(home_dir.clone()).await?;
 
-    let yaml = make_fullnode_yaml(home_dir.clone(), waypoint)?;
-
     let home = home_dir.unwrap_or_else(global_config_dir);
+    let preset = if archive {
+        NodePreset::Archive
+    } else {
+        NodePreset::PublicFullnode
+    };
     let p = home.join(FN_FILENAME);
-    std::fs::write(&p, yaml)?;
-
+    let mut builder = NodeYamlBuilder::new(preset, &home).waypoint(waypoint);
     if overwrite_peers {
-        let peers = fetch_seed_addresses(None).await?;
-        add_peers_to_yaml(&p, peers)?;
+        builder = builder.seed_peers(fetch_seed_addresses(None).await?);
     }
+    opts.apply(&mut builder);
+    builder.save(&p)?;
 
     Ok(p)
 }
@@ -64,9 +72,9 @@ pub fn add_peers_to_yaml(
         }
     });
 
-    // Serialize the updated content and write it back to the file
-    let ser = serde_yaml::to_string(&parsed)?;
-    std::fs::write(path, ser)?;
+    // write it back the same way it was made, validator files hold key paths
+    let user_only = parsed.base.role.is_validator();
+    save_node_config(&parsed, path, user_only)?;
 
     Ok(())
 }
@@ -88,35 +96,10 @@ pub async fn fetch_seed_addresses(
 /// Create a fullnode yaml to bootstrap node
 pub fn make_fullnode_yaml(home_dir: Option<PathBuf>, waypoint: Waypoint) -> anyhow::Result<String> {
     let home_dir = home_dir.unwrap_or_else(global_config_dir);
-    let path = home_dir.display().to_string();
-
-    // Create the YAML template with necessary configurations
-    let template = format!(
-        "
-base:
-  role: 'full_node'
-  data_dir: '{path}/data'
-  waypoint:
-    from_config: '{waypoint}'
-
-execution:
-  genesis_file_location: '{path}/genesis/genesis.blob'
-
-state_sync:
-     state_sync_driver:
-        bootstrapping_mode: ExecuteOrApplyFromGenesis
-        continuous_syncing_mode: ApplyTransactionOutputs
-
-full_node_networks:
-- network_id: 'public'
-  listen_address: '/ip4/0.0.0.0/tcp/6182'
-
-api:
-  enabled: true
-  address: '0.0.0.0:8080'
-"
-    );
-    Ok(template)
+    let config = NodeYamlBuilder::new(NodePreset::PublicFullnode, &home_dir)
+        .waypoint(waypoint)
+        .build()?;
+    Ok(serde_yaml::to_string(&config)?)
 }
 
 /// Create a VFN file to for validators to seed the public network
@@ -125,59 +108,9 @@ pub fn make_private_vfn_yaml(
     val_net_pubkey: x25519::PublicKey,
     val_host_addr: DnsName,
 ) -> anyhow::Result<String> {
-    let path = home_dir.display().to_string();
-    let val_net_pubkey = val_net_pubkey.to_string();
-    let val_host_addr = val_host_addr.to_string();
-
-    // Create the YAML template with necessary configurations for VFN
-    let template = format!(
-        "
-base:
-  role: 'full_node'
-  data_dir: '{path}/data'
-  waypoint:
-    from_file: '{path}/genesis/waypoint.txt'
-
-execution:
-  genesis_file_location: '{path}/genesis/genesis.blob'
-
-state_sync:
-     state_sync_driver:
-        bootstrapping_mode: ApplyTransactionOutputsFromGenesis
-        continuous_syncing_mode: ApplyTransactionOutputs
-
-full_node_networks:
-- network_id: 'public'
-  listen_address: '/ip4/0.0.0.0/tcp/6182'
-  identity:
-    type: 'from_file'
-    path: {path}/validator-full-node-identity.yaml
-
-- network_id:
-    private: 'vfn'
-  listen_address: '/ip4/0.0.0.0/tcp/6181'
-  identity:
-    type: 'from_file'
-    path: {path}/validator-full-node-identity.yaml
-  seeds:
-    {val_net_pubkey}:
-      addresses:
-      - '/ip4/{val_host_addr}/tcp/6181/noise-ik/0x{val_net_pubkey}/handshake/0'
-      role: 'Validator'
-
-api:
-  enabled: true
-  address: '0.0.0.0:8080'
-
-mempool:
-  default_failovers: 3
-"
-    );
-
-    let p = home_dir.join(VFN_FILENAME);
-    std::fs::write(p, &template)?;
-
-    Ok(template)
+    NodeYamlBuilder::new(NodePreset::Vfn, home_dir)
+        .vfn_upstream(val_net_pubkey, val_host_addr)
+        .save(&home_dir.join(VFN_FILENAME))
 }
 
 // #[tokio::test]
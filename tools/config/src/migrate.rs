@@ -0,0 +1,520 @@
+//! Migrate a client config from an older version (the v5 0L.toml, or an
+//! early libra-cli-config.yaml) to the current `AppCfg`.
+//! Every field of the old file is either carried over or listed in the
+//! report, so nothing is lost silently.
+
+use anyhow::{bail, Context};
+use libra_backwards_compatibility::deprecated::config::{AppCfg as V5AppCfg, TxCost as V5TxCost};
+use libra_types::{
+    core_types::{
+        app_cfg::{AddressBookEntry, AppCfg, Profile, TxCost, CONFIG_FILE_NAME},
+        network_playlist::NetworkPlaylist,
+    },
+    exports::{AccountAddress, AuthenticationKey, NamedChain},
+};
+use serde_json::Value;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+use url::Url;
+
+/// the autopay batch file v5 tools looked for in the node home
+pub const AUTOPAY_FILE: &str = "autopay_batch.json";
+
+/// A field of the old config which has no place in the new one
+#[derive(Debug, Clone)]
+pub struct DroppedField {
+    /// dotted path of the field, e.g. `profile.ip`
+    pub field: String,
+    /// the value found in the old config
+    pub value: String,
+    pub reason: String,
+}
+
+impl fmt::Display for DroppedField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {} ({})", self.field, self.value, self.reason)
+    }
+}
+
+/// What the migration did
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    /// fields which were carried to the new config
+    pub carried: Vec<String>,
+    pub dropped: Vec<DroppedField>,
+    /// copy of the original file
+    pub backup: Option<PathBuf>,
+    /// the new libra-cli-config.yaml
+    pub output: Option<PathBuf>,
+}
+
+impl MigrationReport {
+    fn drop_field(&mut self, field: &str, value: impl fmt::Display) {
+        self.dropped.push(DroppedField {
+            field: field.to_owned(),
+            value: value.to_string(),
+            reason: drop_reason(field).to_owned(),
+        })
+    }
+
+    pub fn print(&self) {
+        println!("carried over:");
+        self.carried.iter().for_each(|c| println!("  {c}"));
+        if self.dropped.is_empty() {
+            println!("all fields were carried over");
+        } else {
+            println!("could not carry over:");
+            self.dropped.iter().for_each(|d| println!("  {d}"));
+        }
+        if let Some(b) = &self.backup {
+            println!("original backed up to {}", b.display());
+        }
+        if let Some(o) = &self.output {
+            println!("new config saved to {}", o.display());
+        }
+    }
+}
+
+/// why a known v5/v6 field is not carried over
+fn drop_reason(field: &str) -> &'static str {
+    if field.starts_with("autopay_instructions") {
+        return "autopay was removed, recurring payments must be made by hand";
+    }
+    match field.rsplit('.').next().unwrap_or(field) {
+        "ip" | "vfn_ip" => "node addresses are now in operator.yaml",
+        "tower_link" | "block_dir" => "proof-of-work towers were removed",
+        "db_path" | "base_waypoint" | "base_epoch" => "now set in the node yaml",
+        "source_path" | "stdlib_bin_path" => "framework builds do not use the cli config",
+        _ => "not used by this version",
+    }
+}
+
+/// An old config file, as read from disk
+pub enum LegacyCfg {
+    /// the v5 0L.toml, and the raw file for the fields the v5 types don't know
+    V5(Box<V5AppCfg>, Value),
+    /// an early libra-cli-config.yaml, already in the v7 shapes
+    Yaml(Value),
+}
+
+/// Parse any old config file. TOML for the v5 0L.toml, otherwise yaml.
+fn parse_legacy(path: &Path) -> anyhow::Result<LegacyCfg> {
+    let s = std::fs::read_to_string(path)
+        .context(format!("cannot read legacy config {}", path.display()))?;
+    if path.extension().map(|e| e == "toml").unwrap_or(false) {
+        let v5: V5AppCfg =
+            toml::from_str(&s).context(format!("{} is not a v5 0L.toml", path.display()))?;
+        let raw: Value = toml::from_str(&s)?;
+        return Ok(LegacyCfg::V5(Box::new(v5), raw));
+    }
+    let v: Value = serde_yaml::from_str(&s)?;
+    if !v.is_object() {
+        bail!("{} is not a config file", path.display());
+    }
+    Ok(LegacyCfg::Yaml(v))
+}
+
+/// remove a field from a section, so that whatever is left can be reported
+fn take(root: &mut Value, section: &str, key: &str) -> Option<Value> {
+    root.get_mut(section)?.as_object_mut()?.remove(key)
+}
+
+fn parse_as<T: FromStr>(v: &Value) -> Option<T> {
+    v.as_str().and_then(|s| T::from_str(s).ok())
+}
+
+/// v5 addresses were 16 bytes and written without a 0x prefix
+fn parse_account(v: &Value) -> Option<AccountAddress> {
+    let s = v.as_str()?.trim_start_matches("0x");
+    AccountAddress::from_hex_literal(&format!("0x{s}")).ok()
+}
+
+fn tx_cost(c: V5TxCost) -> TxCost {
+    TxCost {
+        max_gas_unit_for_tx: c.max_gas_unit_for_tx,
+        coin_price_per_unit: c.coin_price_per_unit,
+        user_tx_timeout: c.user_tx_timeout,
+    }
+}
+
+/// Map an old config onto a fresh `AppCfg`. Anything which could not be
+/// mapped goes in the report.
+pub fn map_legacy(
+    legacy: LegacyCfg,
+    home: &Path,
+    autopay_files: &[PathBuf],
+) -> anyhow::Result<(AppCfg, MigrationReport)> {
+    let mut report = MigrationReport::default();
+    let mut cfg = AppCfg::default();
+    cfg.workspace.node_home = home.to_path_buf();
+    cfg.user_profiles = vec![];
+    cfg.network_playlist = vec![];
+
+    match legacy {
+        LegacyCfg::V5(v5, raw) => map_v5(*v5, &raw, home, &mut cfg, &mut report)?,
+        LegacyCfg::Yaml(v) => map_yaml(v, home, &mut cfg, &mut report),
+    }
+
+    if cfg.network_playlist.is_empty() {
+        // the default nodes will be fetched the first time a tool runs
+        cfg.network_playlist.push(NetworkPlaylist::new(
+            None,
+            Some(cfg.workspace.default_chain_id),
+        ));
+    }
+
+    // Autopay no longer exists. Keep the payees in the address book so the
+    // user can find them, and report the instructions themselves.
+    for file in autopay_files {
+        let json: Value = serde_json::from_str(&std::fs::read_to_string(file)?)
+            .context(format!("cannot parse autopay file {}", file.display()))?;
+        let instructions = json
+            .get("autopay_instructions")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        for (i, inst) in instructions.iter().enumerate() {
+            let Some(account) = inst.get("destination").and_then(parse_account) else {
+                report.drop_field(&format!("autopay_instructions.{i}"), inst);
+                continue;
+            };
+            let mut entry = AddressBookEntry::new(format!("autopay-{}", i + 1), account, true);
+            entry.note = inst
+                .get("note")
+                .and_then(Value::as_str)
+                .map(|n| format!("legacy autopay payee: {n}"));
+            if cfg.add_address_book_entry(entry).is_ok() {
+                report
+                    .carried
+                    .push(format!("autopay payee: {account} (address book)"));
+            }
+            report.drop_field(&format!("autopay_instructions.{i}"), inst);
+        }
+    }
+
+    if cfg.user_profiles.is_empty() {
+        bail!("no account found in the legacy config, use `libra config init` instead");
+    }
+    Ok((cfg, report))
+}
+
+/// Map a v5 0L.toml. Each field of the v5 types is carried or reported,
+/// fields the v5 types don't know are found in the raw file.
+fn map_v5(
+    old: V5AppCfg,
+    raw: &Value,
+    home: &Path,
+    cfg: &mut AppCfg,
+    report: &mut MigrationReport,
+) -> anyhow::Result<()> {
+    let known = serde_json::to_value(&old)?;
+    for (section, fields) in raw.as_object().into_iter().flatten() {
+        match (fields.as_object(), known.get(section)) {
+            (Some(fields), Some(k)) => fields
+                .iter()
+                .filter(|(f, _)| k.get(f).is_none())
+                .for_each(|(f, v)| report.drop_field(&format!("{section}.{f}"), v)),
+            (_, None) => report.drop_field(section, fields),
+            (None, Some(_)) => {}
+        }
+    }
+
+    let chain_id = old.chain_info.chain_id;
+    cfg.workspace.default_chain_id = chain_id;
+    report.carried.push(format!("chain id: {chain_id}"));
+    if let Some(e) = old.chain_info.base_epoch {
+        report.drop_field("chain_info.base_epoch", e);
+    }
+    if let Some(w) = old.chain_info.base_waypoint {
+        report.drop_field("chain_info.base_waypoint", w);
+    }
+
+    let ws = old.workspace;
+    if ws.node_home != home {
+        report.drop_field("workspace.node_home", ws.node_home.display());
+    }
+    report.drop_field("workspace.block_dir", &ws.block_dir);
+    if !ws.db_path.as_os_str().is_empty() {
+        report.drop_field("workspace.db_path", ws.db_path.display());
+    }
+    if let Some(p) = ws.source_path {
+        report.drop_field("workspace.source_path", p.display());
+    }
+    if let Some(p) = ws.stdlib_bin_path {
+        report.drop_field("workspace.stdlib_bin_path", p.display());
+    }
+
+    // v5 had a single profile
+    let p = old.profile;
+    let account = AccountAddress::from_hex_literal(&p.account.to_hex_literal())?;
+    let mut profile = Profile::new(p.auth_key, account);
+    profile.statement = p.statement;
+    report.carried.push(format!("profile: {account}"));
+    cfg.workspace.default_profile = Some(profile.nickname.clone());
+    cfg.user_profiles.push(profile);
+    report.drop_field("profile.ip", p.ip);
+    if let Some(ip) = p.vfn_ip {
+        report.drop_field("profile.vfn_ip", ip);
+    }
+    if let Some(t) = p.tower_link {
+        report.drop_field("profile.tower_link", t);
+    }
+
+    // the upstream nodes become the playlist
+    let mut urls: Vec<Url> = p.default_node.into_iter().chain(p.upstream_nodes).collect();
+    urls.sort();
+    urls.dedup();
+    if !urls.is_empty() {
+        let mut np = NetworkPlaylist::new(None, Some(chain_id));
+        np.nodes = vec![];
+        urls.into_iter().for_each(|u| {
+            report.carried.push(format!("upstream node: {u}"));
+            np.add_url(u);
+        });
+        cfg.network_playlist.push(np);
+    }
+
+    // v5 filled in a default for every tx cost, only carry the ones in the file
+    let written = |key: &str| raw.get("tx_configs").and_then(|t| t.get(key)).is_some();
+    let c = old.tx_configs;
+    if written("baseline_cost") {
+        cfg.tx_configs.baseline_cost = tx_cost(c.baseline_cost);
+        report.carried.push("tx cost: baseline_cost".to_owned());
+    }
+    let costs = &mut cfg.tx_configs;
+    for (key, cost, slot) in [
+        (
+            "critical_txs_cost",
+            c.critical_txs_cost,
+            &mut costs.critical_txs_cost,
+        ),
+        (
+            "management_txs_cost",
+            c.management_txs_cost,
+            &mut costs.management_txs_cost,
+        ),
+        (
+            "miner_txs_cost",
+            c.miner_txs_cost,
+            &mut costs.miner_txs_cost,
+        ),
+        (
+            "cheap_txs_cost",
+            c.cheap_txs_cost,
+            &mut costs.cheap_txs_cost,
+        ),
+    ] {
+        if let Some(cost) = cost.filter(|_| written(key)) {
+            *slot = Some(tx_cost(cost));
+            report.carried.push(format!("tx cost: {key}"));
+        }
+    }
+    Ok(())
+}
+
+/// Map an early libra-cli-config.yaml, which already had the v7 shapes.
+/// Fields are removed from `legacy` as they are used, anything left over
+/// goes in the report.
+fn map_yaml(mut legacy: Value, home: &Path, cfg: &mut AppCfg, report: &mut MigrationReport) {
+    let chain = take(&mut legacy, "workspace", "default_chain_id");
+    match chain.as_ref().map(|c| (c, parse_as::<NamedChain>(c))) {
+        Some((_, Some(id))) => {
+            cfg.workspace.default_chain_id = id;
+            report.carried.push(format!("chain id: {id}"));
+        }
+        Some((c, None)) => report.drop_field("workspace.default_chain_id", c),
+        None => {}
+    }
+
+    if let Some(h) = take(&mut legacy, "workspace", "node_home") {
+        if h.as_str().map(Path::new) != Some(home) {
+            report.drop_field("workspace.node_home", &h);
+        }
+    }
+    if let Some(p) = take(&mut legacy, "workspace", "default_profile") {
+        cfg.workspace.default_profile = p.as_str().map(str::to_owned);
+    }
+
+    if let Some(l) = legacy.as_object_mut() {
+        if let Some(profiles) = l.remove("user_profiles") {
+            let profiles: Vec<Profile> = serde_json::from_value(profiles.clone())
+                .map_err(|_| report.drop_field("user_profiles", &profiles))
+                .unwrap_or_default();
+            for p in profiles {
+                report.carried.push(format!("profile: {}", p.account));
+                cfg.user_profiles.push(p);
+            }
+        }
+        if let Some(playlists) = l.remove("network_playlist") {
+            let playlists: Vec<NetworkPlaylist> = serde_json::from_value(playlists.clone())
+                .map_err(|_| report.drop_field("network_playlist", &playlists))
+                .unwrap_or_default();
+            for np in playlists {
+                report.carried.push(format!(
+                    "playlist for {} with {} nodes",
+                    np.chain_name,
+                    np.nodes.len()
+                ));
+                cfg.network_playlist.push(np);
+            }
+        }
+    }
+
+    for key in [
+        "baseline_cost",
+        "critical_txs_cost",
+        "management_txs_cost",
+        "miner_txs_cost",
+        "cheap_txs_cost",
+    ] {
+        let Some(v) = take(&mut legacy, "tx_configs", key) else {
+            continue;
+        };
+        let Ok(cost) = serde_json::from_value::<TxCost>(v.clone()) else {
+            report.drop_field(&format!("tx_configs.{key}"), &v);
+            continue;
+        };
+        report.carried.push(format!("tx cost: {key}"));
+        let costs = &mut cfg.tx_configs;
+        match key {
+            "baseline_cost" => costs.baseline_cost = cost,
+            "critical_txs_cost" => costs.critical_txs_cost = Some(cost),
+            "management_txs_cost" => costs.management_txs_cost = Some(cost),
+            "miner_txs_cost" => costs.miner_txs_cost = Some(cost),
+            _ => costs.cheap_txs_cost = Some(cost),
+        }
+    }
+
+    // whatever is left could not be mapped
+    if let Some(l) = legacy.as_object() {
+        for (section, v) in l {
+            match v.as_object() {
+                Some(m) => m
+                    .iter()
+                    .for_each(|(k, v)| report.drop_field(&format!("{section}.{k}"), v)),
+                None => report.drop_field(section, v),
+            }
+        }
+    }
+}
+
+/// the first free backup path for a file
+fn backup_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut backup = path.with_file_name(format!("{name}.bak"));
+    let mut i = 1;
+    while backup.exists() {
+        backup = path.with_file_name(format!("{name}.bak.{i}"));
+        i += 1;
+    }
+    backup
+}
+
+/// Migrate an old config file into a libra-cli-config.yaml in `home`.
+/// The original is backed up next to itself. Autopay files next to the old
+/// config are picked up, as well as any extra ones given.
+pub fn migrate(
+    legacy_file: &Path,
+    home: &Path,
+    extra_autopay_files: &[PathBuf],
+    force: bool,
+) -> anyhow::Result<MigrationReport> {
+    let output = home.join(CONFIG_FILE_NAME);
+    if output.exists() && !force && output != legacy_file {
+        bail!(
+            "{} already exists, use --force to overwrite it",
+            output.display()
+        );
+    }
+
+    let legacy = parse_legacy(legacy_file)?;
+
+    let mut autopay_files = extra_autopay_files.to_vec();
+    if let Some(dir) = legacy_file.parent() {
+        let found = dir.join(AUTOPAY_FILE);
+        if found.exists() && !autopay_files.contains(&found) {
+            autopay_files.push(found);
+        }
+    }
+
+    let (cfg, mut report) = map_legacy(legacy, home, &autopay_files)?;
+
+    let backup = backup_path(legacy_file);
+    std::fs::copy(legacy_file, &backup)?;
+    report.backup = Some(backup);
+
+    report.output = Some(cfg.save_file()?);
+    Ok(report)
+}
+
+#[test]
+fn migrate_v5_toml() {
+    let dir = diem_temppath::TempPath::new();
+    dir.create_as_dir().unwrap();
+    let legacy_path = dir.path().join("0L.toml");
+    std::fs::write(
+        &legacy_path,
+        r#"
+[workspace]
+node_home = "/root/.0L"
+block_dir = "vdf_proofs"
+db_path = "/root/.0L/db"
+
+[chain_info]
+chain_id = "TESTING"
+base_epoch = 0
+
+[profile]
+account = "4c613c2f4b1e67ca8d98a542ee3f59f5"
+auth_key = "87515d94a244235a1433d7117bc0cb154c613c2f4b1e67ca8d98a542ee3f59f5"
+statement = "hello"
+ip = "1.2.3.4"
+email = "alice@example.com"
+upstream_nodes = ["http://1.2.3.4:8080/", "http://5.6.7.8:8080/", "http://1.2.3.4:8080/"]
+
+[tx_configs.baseline_cost]
+max_gas_unit_for_tx = 10000
+coin_price_per_unit = 100
+user_tx_timeout = 5000
+"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.path().join(AUTOPAY_FILE),
+        r#"{"autopay_instructions": [{"note": "rent", "type_of": "FixedRecurring", "value": 10, "destination": "88e74dfed34420f2ad8032148280a84b", "end_epoch": 100}]}"#,
+    )
+    .unwrap();
+
+    let report = migrate(&legacy_path, dir.path(), &[], false).unwrap();
+
+    let cfg = AppCfg::load(report.output.clone()).unwrap();
+    assert_eq!(cfg.workspace.default_chain_id, NamedChain::TESTING);
+    assert_eq!(cfg.user_profiles[0].statement, "hello");
+    assert_eq!(cfg.network_playlist[0].nodes.len(), 2);
+    assert_eq!(cfg.tx_configs.baseline_cost.max_gas_unit_for_tx, 10000);
+    assert!(cfg.resolve_alias("autopay-1").is_ok());
+
+    // everything without a home in v7 is listed
+    let dropped: Vec<&str> = report.dropped.iter().map(|d| d.field.as_str()).collect();
+    for f in [
+        "workspace.node_home",
+        "workspace.block_dir",
+        "workspace.db_path",
+        "chain_info.base_epoch",
+        "profile.ip",
+        "profile.email",
+        "autopay_instructions.0",
+    ] {
+        assert!(dropped.contains(&f), "{f} not reported");
+    }
+
+    assert!(report.backup.unwrap().exists());
+    // will not overwrite a config by accident
+    assert!(migrate(&legacy_path, dir.path(), &[], false).is_err());
+}
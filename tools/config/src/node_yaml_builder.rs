@@ -0,0 +1,427 @@
+//! Typed builder for node yaml files, with presets for each node role.
+//! Every file is parsed back as a `NodeConfig` before it is put in place, so
+//! a node should never be handed a config it can't read.
+
+use anyhow::{bail, Context};
+use diem_config::{
+    config::{
+        BootstrappingMode, ContinuousSyncingMode, DiscoveryMethod, Identity,
+        InitialSafetyRulesConfig, NetworkConfig, NodeConfig, OnDiskStorageConfig, Peer, PeerRole,
+        RoleType, SecureBackend, WaypointConfig,
+    },
+    network_id::NetworkId,
+};
+use diem_crypto::x25519;
+use diem_types::{
+    account_address::from_identity_public_key,
+    network_address::{DnsName, NetworkAddress},
+    waypoint::Waypoint,
+    PeerId,
+};
+use libra_wallet::{utils::write_to_user_only_file, validator_files::VALIDATOR_IDENTITY_FILE};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+};
+
+pub const VALIDATOR_NETWORK_PORT: u16 = 6180;
+pub const VFN_NETWORK_PORT: u16 = 6181;
+pub const PUBLIC_NETWORK_PORT: u16 = 6182;
+pub const API_PORT: u16 = 8080;
+
+/// The presets a node yaml can be built from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodePreset {
+    /// consensus node, API only on localhost
+    Validator,
+    /// a validator's private fullnode, which relays to the public network
+    Vfn,
+    /// a public fullnode, pruned with the default windows
+    PublicFullnode,
+    /// a public fullnode which keeps all history
+    Archive,
+}
+
+/// The validator a VFN connects to on the private vfn network
+#[derive(Debug, Clone)]
+pub struct VfnUpstream {
+    /// the validator network public key, shared with the VFN identity
+    pub val_net_pubkey: x25519::PublicKey,
+    /// the validator host, an ip or a dns name
+    pub val_host: DnsName,
+}
+
+/// Options for a node yaml. Start from `NodeYamlBuilder::new` with a preset
+/// and change any field before calling `build` or `save`.
+#[derive(Debug, Clone)]
+pub struct NodeYamlBuilder {
+    pub preset: NodePreset,
+    /// where the genesis files and identity files are found
+    pub home_dir: PathBuf,
+    /// database and secure storage, defaults to `<home>/data`
+    pub data_dir: PathBuf,
+    pub waypoint: WaypointConfig,
+    pub validator_port: u16,
+    pub vfn_port: u16,
+    pub public_port: u16,
+    pub api_enabled: bool,
+    pub api_address: SocketAddr,
+    /// versions of ledger history to keep, `None` keeps the node default
+    pub ledger_prune_window: Option<u64>,
+    /// versions of state merkle history to keep, `None` keeps the node default
+    pub state_prune_window: Option<u64>,
+    /// disable all pruners, set by the archive preset
+    pub disable_pruning: bool,
+    /// required for the VFN preset
+    pub vfn_upstream: Option<VfnUpstream>,
+    /// seed peers of the public network
+    pub seed_peers: HashMap<PeerId, Vec<NetworkAddress>>,
+}
+
+/// Command line overrides for a preset, see `NodeYamlBuilder`
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct NodeYamlOpts {
+    /// database and secure storage, defaults to <home>/data
+    #[clap(long)]
+    pub data_dir: Option<PathBuf>,
+    /// validator network port
+    #[clap(long)]
+    pub validator_port: Option<u16>,
+    /// vfn network port
+    #[clap(long)]
+    pub vfn_port: Option<u16>,
+    /// public fullnode network port
+    #[clap(long)]
+    pub public_port: Option<u16>,
+    /// address the API listens on
+    #[clap(long)]
+    pub api_address: Option<SocketAddr>,
+    /// do not serve the API
+    #[clap(long)]
+    pub no_api: bool,
+    /// versions of ledger history to keep
+    #[clap(long)]
+    pub ledger_prune_window: Option<u64>,
+    /// versions of state merkle history to keep
+    #[clap(long)]
+    pub state_prune_window: Option<u64>,
+}
+
+impl NodeYamlOpts {
+    /// override the builder's fields with the options which are set
+    pub fn apply(&self, b: &mut NodeYamlBuilder) {
+        if let Some(d) = &self.data_dir {
+            b.data_dir.clone_from(d);
+        }
+        b.validator_port = self.validator_port.unwrap_or(b.validator_port);
+        b.vfn_port = self.vfn_port.unwrap_or(b.vfn_port);
+        b.public_port = self.public_port.unwrap_or(b.public_port);
+        b.api_address = self.api_address.unwrap_or(b.api_address);
+        b.api_enabled = b.api_enabled && !self.no_api;
+        b.ledger_prune_window = self.ledger_prune_window.or(b.ledger_prune_window);
+        b.state_prune_window = self.state_prune_window.or(b.state_prune_window);
+    }
+}
+
+impl NodeYamlBuilder {
+    pub fn new(preset: NodePreset, home_dir: &Path) -> Self {
+        // only validators keep the API private by default
+        let api_ip = match preset {
+            NodePreset::Validator => Ipv4Addr::LOCALHOST,
+            _ => Ipv4Addr::UNSPECIFIED,
+        };
+
+        Self {
+            preset,
+            home_dir: home_dir.to_path_buf(),
+            data_dir: home_dir.join("data"),
+            waypoint: WaypointConfig::FromFile(home_dir.join("genesis").join("waypoint.txt")),
+            validator_port: VALIDATOR_NETWORK_PORT,
+            vfn_port: VFN_NETWORK_PORT,
+            public_port: PUBLIC_NETWORK_PORT,
+            api_enabled: true,
+            api_address: SocketAddr::from((api_ip, API_PORT)),
+            ledger_prune_window: None,
+            state_prune_window: None,
+            disable_pruning: preset == NodePreset::Archive,
+            vfn_upstream: None,
+            seed_peers: HashMap::new(),
+        }
+    }
+
+    /// use a fixed waypoint instead of reading `genesis/waypoint.txt`
+    pub fn waypoint(mut self, waypoint: Waypoint) -> Self {
+        self.waypoint = WaypointConfig::FromConfig(waypoint);
+        self
+    }
+
+    pub fn vfn_upstream(mut self, val_net_pubkey: x25519::PublicKey, val_host: DnsName) -> Self {
+        self.vfn_upstream = Some(VfnUpstream {
+            val_net_pubkey,
+            val_host,
+        });
+        self
+    }
+
+    /// seed the public network with these peers
+    pub fn seed_peers(mut self, peers: HashMap<PeerId, Vec<NetworkAddress>>) -> Self {
+        self.seed_peers = peers;
+        self
+    }
+
+    pub fn build(&self) -> anyhow::Result<NodeConfig> {
+        let mut config = NodeConfig::default();
+
+        config.base.data_dir.clone_from(&self.data_dir);
+        config.base.waypoint = self.waypoint.clone();
+        config.execution.genesis_file_location = self.home_dir.join("genesis").join("genesis.blob");
+
+        config.api.enabled = self.api_enabled;
+        config.api.address = self.api_address;
+
+        match self.preset {
+            NodePreset::Validator => self.set_validator(&mut config)?,
+            NodePreset::Vfn => self.set_vfn(&mut config)?,
+            NodePreset::PublicFullnode | NodePreset::Archive => {
+                config.base.role = RoleType::FullNode;
+                config.state_sync.state_sync_driver.bootstrapping_mode =
+                    BootstrappingMode::ExecuteOrApplyFromGenesis;
+                config.state_sync.state_sync_driver.continuous_syncing_mode =
+                    ContinuousSyncingMode::ApplyTransactionOutputs;
+                config.full_node_networks = vec![self.network(NetworkId::Public)?];
+            }
+        }
+
+        if !self.seed_peers.is_empty() {
+            config
+                .full_node_networks
+                .iter_mut()
+                .filter(|n| n.network_id.is_public_network())
+                .for_each(|n| n.seed_addrs.clone_from(&self.seed_peers));
+        }
+
+        let pruner = &mut config.storage.storage_pruner_config;
+        if let Some(w) = self.ledger_prune_window {
+            pruner.ledger_pruner_config.prune_window = w;
+        }
+        if let Some(w) = self.state_prune_window {
+            pruner.state_merkle_pruner_config.prune_window = w;
+        }
+        if self.disable_pruning {
+            pruner.ledger_pruner_config.enable = false;
+            pruner.state_merkle_pruner_config.enable = false;
+            pruner.epoch_snapshot_pruner_config.enable = false;
+        }
+
+        Ok(config)
+    }
+
+    /// Build the config and write it to `path`, see `save_node_config`
+    pub fn save(&self, path: &Path) -> anyhow::Result<String> {
+        save_node_config(&self.build()?, path, self.preset == NodePreset::Validator)
+    }
+
+    fn set_validator(&self, config: &mut NodeConfig) -> anyhow::Result<()> {
+        let identity = self.home_dir.join(VALIDATOR_IDENTITY_FILE);
+        config.base.role = RoleType::Validator;
+
+        let safety_rules = &mut config.consensus.safety_rules;
+        let mut storage = OnDiskStorageConfig::default();
+        storage.path = PathBuf::from("secure-data.json");
+        storage.set_data_dir(self.data_dir.clone());
+        safety_rules.backend = SecureBackend::OnDiskStorage(storage);
+        safety_rules.initial_safety_rules_config =
+            InitialSafetyRulesConfig::from_file(identity.clone(), self.waypoint.clone());
+
+        let mut val_net = self.network(NetworkId::Validator)?;
+        val_net.discovery_method = DiscoveryMethod::Onchain;
+        val_net.mutual_authentication = true;
+        val_net.identity = Identity::from_file(identity.clone());
+        config.validator_network = Some(val_net);
+
+        let mut vfn_net = self.network(NetworkId::Vfn)?;
+        vfn_net.identity = Identity::from_file(identity);
+        config.full_node_networks = vec![vfn_net, self.network(NetworkId::Public)?];
+
+        Ok(())
+    }
+
+    fn set_vfn(&self, config: &mut NodeConfig) -> anyhow::Result<()> {
+        let upstream = self
+            .vfn_upstream
+            .as_ref()
+            .context("a VFN config needs the validator's network key and host")?;
+        let identity = self.home_dir.join("validator-full-node-identity.yaml");
+        config.base.role = RoleType::FullNode;
+
+        config.state_sync.state_sync_driver.bootstrapping_mode =
+            BootstrappingMode::ApplyTransactionOutputsFromGenesis;
+        config.state_sync.state_sync_driver.continuous_syncing_mode =
+            ContinuousSyncingMode::ApplyTransactionOutputs;
+        config.mempool.default_failovers = 3;
+
+        let mut public_net = self.network(NetworkId::Public)?;
+        public_net.identity = Identity::from_file(identity.clone());
+
+        let mut vfn_net = self.network(NetworkId::Vfn)?;
+        vfn_net.identity = Identity::from_file(identity);
+        let val_addr = validator_vfn_address(upstream, self.vfn_port)?;
+        vfn_net.seeds.insert(
+            from_identity_public_key(upstream.val_net_pubkey),
+            Peer::new(
+                vec![val_addr],
+                HashSet::from([upstream.val_net_pubkey]),
+                PeerRole::Validator,
+            ),
+        );
+
+        config.full_node_networks = vec![public_net, vfn_net];
+        Ok(())
+    }
+
+    fn network(&self, id: NetworkId) -> anyhow::Result<NetworkConfig> {
+        let port = match id {
+            NetworkId::Validator => self.validator_port,
+            NetworkId::Vfn => self.vfn_port,
+            NetworkId::Public => self.public_port,
+        };
+        let mut net = NetworkConfig::network_with_id(id);
+        net.listen_address = format!("/ip4/0.0.0.0/tcp/{port}").parse()?;
+        Ok(net)
+    }
+}
+
+/// Write a node config to `path`. The yaml is written next to the
+/// destination, read back and checked, then moved into place.
+/// Validator files reference the keys, pass `user_only` to keep them
+/// private. Returns the yaml as written.
+pub fn save_node_config(
+    config: &NodeConfig,
+    path: &Path,
+    user_only: bool,
+) -> anyhow::Result<String> {
+    let yaml = serde_yaml::to_string(config)?;
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    if user_only {
+        write_to_user_only_file(&tmp, "node yaml", yaml.as_bytes())?;
+    } else {
+        std::fs::write(&tmp, &yaml)?;
+    }
+    if let Err(e) = check_written_config(&tmp, &yaml) {
+        std::fs::remove_file(&tmp)?;
+        bail!(
+            "generated node config for {} is not valid: {:#}",
+            path.display(),
+            e
+        );
+    }
+    std::fs::rename(&tmp, path)?;
+
+    Ok(yaml)
+}
+
+/// The file on disk parses as a node config, and nothing in it was replaced
+/// by a default when parsing.
+/// NOTE: `NodeConfig::load_from_path` would also read genesis.blob, which may
+/// not be there yet.
+fn check_written_config(path: &Path, yaml: &str) -> anyhow::Result<()> {
+    let written = std::fs::read_to_string(path)?;
+    let parsed: NodeConfig = serde_yaml::from_str(&written)?;
+    if serde_yaml::to_string(&parsed)? != yaml {
+        bail!("the config does not read back the same, some fields were filled with defaults");
+    }
+    Ok(())
+}
+
+/// the noise address of the validator on the private vfn network
+pub(crate) fn validator_vfn_address(
+    upstream: &VfnUpstream,
+    port: u16,
+) -> anyhow::Result<NetworkAddress> {
+    let host = upstream.val_host.to_string();
+    let proto = if host.parse::<Ipv4Addr>().is_ok() {
+        "ip4"
+    } else {
+        "dns"
+    };
+    let addr = format!(
+        "/{proto}/{host}/tcp/{port}/noise-ik/0x{}/handshake/0",
+        upstream.val_net_pubkey
+    );
+    addr.parse()
+        .context(format!("cannot make a network address from {addr}"))
+}
+
+#[test]
+fn presets_load() {
+    use std::str::FromStr;
+    let home = diem_temppath::TempPath::new();
+    home.create_as_dir().unwrap();
+    let home = home.path();
+
+    let path = home.join("validator.yaml");
+    NodeYamlBuilder::new(NodePreset::Validator, home)
+        .save(&path)
+        .unwrap();
+    let c: NodeConfig = serde_yaml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert!(c.base.role.is_validator());
+    assert_eq!(c.base.data_dir, home.join("data"));
+    assert_eq!(c.full_node_networks.len(), 2);
+    assert!(c.validator_network.unwrap().mutual_authentication);
+
+    let pk = x25519::PrivateKey::from([7u8; 32]).public_key();
+    let path = home.join("vfn.yaml");
+    NodeYamlBuilder::new(NodePreset::Vfn, home)
+        .vfn_upstream(pk, DnsName::from_str("127.0.0.1").unwrap())
+        .save(&path)
+        .unwrap();
+    let c: NodeConfig = serde_yaml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let vfn_net = c
+        .full_node_networks
+        .iter()
+        .find(|n| n.network_id == NetworkId::Vfn)
+        .unwrap();
+    assert_eq!(vfn_net.seeds.len(), 1);
+
+    // a VFN without an upstream is not written
+    let path = home.join("bad_vfn.yaml");
+    assert!(NodeYamlBuilder::new(NodePreset::Vfn, home)
+        .save(&path)
+        .is_err());
+    assert!(!path.exists());
+
+    let path = home.join("archive.yaml");
+    let mut b = NodeYamlBuilder::new(NodePreset::Archive, home);
+    b.public_port = 7182;
+    b.save(&path).unwrap();
+    let c: NodeConfig = serde_yaml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert!(!c.storage.storage_pruner_config.ledger_pruner_config.enable);
+    assert_eq!(
+        c.full_node_networks[0].listen_address.to_string(),
+        "/ip4/0.0.0.0/tcp/7182"
+    );
+
+    let path = home.join("fullnode.yaml");
+    let mut b = NodeYamlBuilder::new(NodePreset::PublicFullnode, home);
+    b.ledger_prune_window = Some(1_000_000);
+    b.save(&path).unwrap();
+    let c: NodeConfig = serde_yaml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert!(c.storage.storage_pruner_config.ledger_pruner_config.enable);
+    assert_eq!(
+        c.storage
+            .storage_pruner_config
+            .ledger_pruner_config
+            .prune_window,
+        1_000_000
+    );
+}
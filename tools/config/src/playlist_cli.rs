@@ -0,0 +1,109 @@
+//! Sign, verify and trust network playlists.
+use anyhow::Context;
+use diem_crypto::{ed25519::Ed25519PublicKey, ValidCryptoMaterialStringExt};
+use libra_types::core_types::{
+    app_cfg::{default_file_path, AppCfg},
+    network_playlist::PlaylistSignature,
+};
+use libra_wallet::secret_source::SecretSource;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Subcommand)]
+pub enum PlaylistSub {
+    /// For playlist maintainers. Writes a detached signature of the playlist
+    /// file to `<file>.sig`, which should be published next to the playlist.
+    Sign {
+        /// the playlist json file, exactly as it will be published
+        #[clap(long)]
+        file: PathBuf,
+        /// use a private key to sign. Warning: intended for testing only.
+        #[clap(long)]
+        test_private_key: Option<String>,
+        /// where to read the signing mnemonic or key from: prompt, env:VAR,
+        /// fd:N, file:PATH, or keystore[:DIR]
+        #[clap(long, conflicts_with("test_private_key"))]
+        secret_source: Option<SecretSource>,
+    },
+    /// Check a playlist file against its `<file>.sig` and the trusted keys
+    Verify {
+        /// the playlist json file
+        #[clap(long)]
+        file: PathBuf,
+        /// optional, a key to check against instead of the trusted keys
+        #[clap(long)]
+        public_key: Option<String>,
+    },
+    /// Trust a playlist maintainer's public key. Once any key is trusted,
+    /// fetched playlists without a valid signature are rejected.
+    Trust {
+        /// the maintainer's ed25519 public key, as hex
+        public_key: String,
+    },
+}
+
+impl PlaylistSub {
+    pub fn run(&self, config_path: Option<PathBuf>) -> anyhow::Result<()> {
+        match self {
+            PlaylistSub::Sign {
+                file,
+                test_private_key,
+                secret_source,
+            } => {
+                let key = SecretSource::from_args(
+                    secret_source.as_ref(),
+                    None,
+                    test_private_key.as_ref(),
+                )
+                .unwrap_or(SecretSource::Prompt)
+                .account_key()?;
+                let bytes = std::fs::read(file)?;
+                let sig = PlaylistSignature::sign(&bytes, &key);
+                let sig_path = sig_path(file);
+                std::fs::write(&sig_path, serde_json::to_string_pretty(&sig)?)?;
+                println!(
+                    "signed by {}, signature written to {}",
+                    sig.public_key,
+                    sig_path.display()
+                );
+            }
+            PlaylistSub::Verify { file, public_key } => {
+                let trusted = match public_key {
+                    Some(k) => vec![parse_public_key(k)?],
+                    None => AppCfg::load(config_path)?.trusted_playlist_keys,
+                };
+                anyhow::ensure!(
+                    !trusted.is_empty(),
+                    "no trusted playlist keys to verify with"
+                );
+
+                let bytes = std::fs::read(file)?;
+                let sig: PlaylistSignature = serde_json::from_slice(
+                    &std::fs::read(sig_path(file)).context("cannot read signature file")?,
+                )?;
+                sig.verify(&bytes, &trusted)?;
+                println!("playlist signature is valid, signed by {}", sig.public_key);
+            }
+            PlaylistSub::Trust { public_key } => {
+                let path = config_path.unwrap_or_else(default_file_path);
+                let mut cfg = AppCfg::load(Some(path.clone()))?;
+                cfg.add_trusted_playlist_key(parse_public_key(public_key)?);
+                cfg.save_file_to(&path)?;
+                println!("trusted playlist keys:");
+                cfg.trusted_playlist_keys
+                    .iter()
+                    .for_each(|k| println!("{k}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn sig_path(file: &Path) -> PathBuf {
+    let mut p = file.as_os_str().to_owned();
+    p.push(".sig");
+    p.into()
+}
+
+fn parse_public_key(s: &str) -> anyhow::Result<Ed25519PublicKey> {
+    Ed25519PublicKey::from_encoded_string(s).context("cannot parse public key")
+}
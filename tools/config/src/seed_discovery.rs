@@ -0,0 +1,192 @@
+//! Find seed peers for a public fullnode from the chain itself, instead of the
+//! static list in the genesis repo.
+//! Each validator in the current set may publish fullnode addresses in its
+//! ValidatorConfig. Those are probed with a noise handshake, which also checks
+//! the peer is on our chain, and the reachable ones are ranked by latency.
+
+use crate::make_yaml_public_fullnode::add_peers_to_yaml;
+use anyhow::{bail, Context};
+use diem_network_checker::args::{CheckEndpointArgs, HandshakeArgs, NodeAddressArgs};
+use diem_types::{
+    account_address::from_identity_public_key, chain_id::ChainId, network_address::NetworkAddress,
+    PeerId,
+};
+use futures::future::join_all;
+use libra_types::exports::{AccountAddress, Client};
+use std::{collections::HashMap, path::Path, time::Instant};
+
+/// more seeds than this only slow down the node's startup
+pub const MAX_SEEDS: usize = 20;
+pub const HANDSHAKE_TIMEOUT_SECS: u64 = 5;
+
+#[derive(Debug, Clone)]
+pub struct SeedCandidate {
+    pub validator: AccountAddress,
+    pub peer_id: PeerId,
+    pub address: NetworkAddress,
+    /// time to complete the handshake, none if it failed
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl SeedCandidate {
+    pub fn is_reachable(&self) -> bool {
+        self.latency_ms.is_some()
+    }
+}
+
+/// the fullnode addresses published by the current validator set
+pub async fn onchain_candidates(client: &Client) -> anyhow::Result<Vec<SeedCandidate>> {
+    let validators = libra_query::chain_queries::get_current_validators(client).await?;
+
+    let mut candidates: Vec<SeedCandidate> = vec![];
+    for validator in validators {
+        let addresses = match libra_query::account_queries::get_val_config(client, validator)
+            .await
+            .and_then(|c| c.fullnode_network_addresses().map_err(anyhow::Error::from))
+        {
+            Ok(a) => a,
+            Err(e) => {
+                println!("skipping validator {validator}, cannot read its config: {e}");
+                continue;
+            }
+        };
+
+        for address in addresses {
+            // without a noise key there is no peer id to dial
+            let Some(key) = address.find_noise_proto() else {
+                continue;
+            };
+            if candidates.iter().any(|c| c.address == address) {
+                continue;
+            }
+            candidates.push(SeedCandidate {
+                validator,
+                peer_id: from_identity_public_key(key),
+                address,
+                latency_ms: None,
+                error: None,
+            });
+        }
+    }
+    Ok(candidates)
+}
+
+/// Handshake with all candidates at once. A peer on another chain fails the
+/// handshake, so a reachable candidate is also on `chain_id`.
+pub async fn probe_candidates(
+    candidates: Vec<SeedCandidate>,
+    chain_id: ChainId,
+    timeout_secs: u64,
+) -> Vec<SeedCandidate> {
+    let probes = candidates.into_iter().map(|mut c| async move {
+        let args = CheckEndpointArgs {
+            node_address_args: NodeAddressArgs {
+                address: c.address.clone(),
+                chain_id,
+            },
+            handshake_args: HandshakeArgs {
+                no_handshake: false,
+                handshake_timeout_seconds: timeout_secs,
+            },
+        };
+        let start = Instant::now();
+        match diem_network_checker::check_endpoint(&args, None).await {
+            Ok(_) => c.latency_ms = Some(start.elapsed().as_millis() as u64),
+            Err(e) => c.error = Some(e.to_string()),
+        }
+        c
+    });
+    join_all(probes).await
+}
+
+/// Keep the reachable candidates, fastest first, at most `max` peers.
+pub fn rank_seeds(candidates: &[SeedCandidate], max: usize) -> Vec<(PeerId, Vec<NetworkAddress>)> {
+    let mut reachable: Vec<&SeedCandidate> =
+        candidates.iter().filter(|c| c.is_reachable()).collect();
+    reachable.sort_by_key(|c| c.latency_ms);
+
+    let mut ranked: Vec<(PeerId, Vec<NetworkAddress>)> = vec![];
+    for c in reachable {
+        match ranked.iter().position(|(p, _)| *p == c.peer_id) {
+            Some(i) => {
+                if !ranked[i].1.contains(&c.address) {
+                    ranked[i].1.push(c.address.clone());
+                }
+            }
+            None if ranked.len() < max => ranked.push((c.peer_id, vec![c.address.clone()])),
+            None => {}
+        }
+    }
+    ranked
+}
+
+/// Discover and probe seeds, then write the ranked list into the fullnode
+/// yaml. Returns all the candidates with their probe results.
+pub async fn discover_seeds(
+    client: &Client,
+    fullnode_yaml: &Path,
+    max: usize,
+    dry_run: bool,
+) -> anyhow::Result<Vec<SeedCandidate>> {
+    let chain_id = ChainId::new(client.get_index().await?.inner().chain_id);
+
+    let candidates = onchain_candidates(client).await?;
+    if candidates.is_empty() {
+        bail!("no validator publishes a fullnode address on chain");
+    }
+    let probed = probe_candidates(candidates, chain_id, HANDSHAKE_TIMEOUT_SECS).await;
+
+    let ranked = rank_seeds(&probed, max);
+    if ranked.is_empty() {
+        bail!(
+            "none of the {} fullnode addresses found on chain are reachable",
+            probed.len()
+        );
+    }
+
+    if !dry_run {
+        let seeds: HashMap<PeerId, Vec<NetworkAddress>> = ranked.into_iter().collect();
+        add_peers_to_yaml(fullnode_yaml, seeds)
+            .context(format!("cannot update {}", fullnode_yaml.display()))?;
+    }
+    Ok(probed)
+}
+
+#[test]
+fn ranks_reachable_seeds() {
+    use diem_crypto::{x25519, Uniform};
+    use std::str::FromStr;
+
+    let mut rng = rand::rngs::OsRng;
+    let candidate = |host: u8, key: x25519::PublicKey, latency_ms: Option<u64>| SeedCandidate {
+        validator: AccountAddress::ONE,
+        peer_id: from_identity_public_key(key),
+        address: NetworkAddress::from_str(&format!("/ip4/10.0.0.{host}/tcp/6182"))
+            .unwrap()
+            .append_prod_protos(key, 0),
+        latency_ms,
+        error: None,
+    };
+    let a = x25519::PrivateKey::generate(&mut rng).public_key();
+    let b = x25519::PrivateKey::generate(&mut rng).public_key();
+    let c = x25519::PrivateKey::generate(&mut rng).public_key();
+
+    let candidates = vec![
+        candidate(1, a, Some(300)),
+        candidate(2, b, Some(40)),
+        candidate(3, c, None),
+        // same peer twice, one address is listed once
+        candidate(4, a, Some(90)),
+        candidate(4, a, Some(90)),
+    ];
+
+    let ranked = rank_seeds(&candidates, MAX_SEEDS);
+    // the unreachable peer is dropped, the fastest comes first
+    assert_eq!(ranked.len(), 2);
+    assert_eq!(ranked[0].0, from_identity_public_key(b));
+    assert_eq!(ranked[1].0, from_identity_public_key(a));
+    assert_eq!(ranked[1].1.len(), 2);
+
+    assert_eq!(rank_seeds(&candidates, 1).len(), 1);
+}
@@ -11,7 +11,10 @@ use libra_types::{
     core_types::{app_cfg::AppCfg, network_playlist::NetworkPlaylist},
     ol_progress::{self, OLProgress},
 };
-use libra_wallet::{utils::read_public_identity_file, validator_files::SetValidatorConfiguration};
+use libra_wallet::{
+    secret_source::SecretSource, utils::read_public_identity_file,
+    validator_files::SetValidatorConfiguration,
+};
 use std::{
     path::{Path, PathBuf},
     str::FromStr,
@@ -149,10 +152,12 @@ pub async fn what_host() -> Result<HostAndPort, anyhow::Error> {
 }
 
 // Function to handle the validator dialogue with the user
+// The mnemonic is read from the secret source if given, otherwise prompted.
 pub async fn validator_dialogue(
     data_path: &Path,
     github_username: Option<&str>,
     chain_name: Option<NamedChain>,
+    secret: Option<&SecretSource>,
 ) -> Result<(), anyhow::Error> {
     let to_init = Confirm::new()
         .with_prompt(format!(
@@ -169,11 +174,12 @@ pub async fn validator_dialogue(
             )
             .interact()?;
 
+        let mnem = secret.map(|s| s.mnemonic()).transpose()?;
         let (pub_id, _) = initialize_validator_files(
             Some(data_path.to_path_buf()),
             github_username,
             host.clone(),
-            None,
+            mnem,
             keep_legacy_address,
             chain_name,
         )
@@ -192,7 +198,7 @@ pub async fn validator_dialogue(
 }
 
 // Function to get the local validator full node ID
-fn get_local_vfn_id(home: &Path) -> anyhow::Result<x25519::PublicKey> {
+pub fn get_local_vfn_id(home: &Path) -> anyhow::Result<x25519::PublicKey> {
     let id = read_public_identity_file(&home.join("public-keys.yaml"))?;
 
     id.validator_network_public_key
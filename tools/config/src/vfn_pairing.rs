@@ -0,0 +1,215 @@
+//! Pair a validator with its fullnode (VFN) without copying keys and
+//! addresses between hosts by hand.
+//! The validator exports a bundle signed with its consensus key, and the VFN
+//! imports it to make a vfn.yaml with the validator as a trusted seed. The
+//! signature is checked with the validator's key from the chain, or one the
+//! VFN operator configured, never a key from the bundle itself.
+
+use crate::{
+    make_yaml_public_fullnode::VFN_FILENAME,
+    node_yaml_builder::{NodePreset, NodeYamlBuilder, VFN_NETWORK_PORT},
+    validator_config::get_local_vfn_id,
+};
+use anyhow::{bail, Context};
+use diem_config::config::IdentityBlob;
+use diem_crypto::{bls12381, x25519, Signature, SigningKey};
+use diem_crypto_derive::{BCSCryptoHash, CryptoHasher};
+use diem_genesis::config::HostAndPort;
+use diem_types::{network_address::DnsName, waypoint::Waypoint};
+use libra_types::exports::{AccountAddress, Client};
+use libra_wallet::{
+    utils::write_to_user_only_file,
+    validator_files::{OperatorFile, OPERATOR_FILE, VALIDATOR_IDENTITY_FILE},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+pub const PAIRING_FILE: &str = "vfn-pairing.json";
+
+/// What the VFN needs to know about its validator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, CryptoHasher, BCSCryptoHash)]
+pub struct VfnPairing {
+    pub validator_account: AccountAddress,
+    /// the validator network key, which the validator also uses on the vfn
+    /// network
+    pub val_net_pubkey: x25519::PublicKey,
+    pub val_host: DnsName,
+    pub vfn_port: u16,
+    pub waypoint: Waypoint,
+}
+
+/// A pairing bundle signed with the validator's consensus key, which the VFN
+/// checks against the chain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedVfnPairing {
+    pub pairing: VfnPairing,
+    pub signature: bls12381::Signature,
+}
+
+impl SignedVfnPairing {
+    pub fn sign(pairing: VfnPairing, key: &bls12381::PrivateKey) -> anyhow::Result<Self> {
+        let signature = key.sign(&pairing)?;
+        Ok(Self { pairing, signature })
+    }
+
+    /// check the bundle was signed with the validator's consensus key
+    pub fn verify(&self, consensus_key: &bls12381::PublicKey) -> anyhow::Result<()> {
+        self.signature
+            .verify(&self.pairing, consensus_key)
+            .context("the pairing bundle was not signed by the validator's consensus key")
+    }
+
+    /// Check the bundle against the validator's config on chain: signed by
+    /// its consensus key, with its registered network key. Returns the
+    /// consensus key.
+    pub async fn verify_onchain(&self, client: &Client) -> anyhow::Result<bls12381::PublicKey> {
+        let account = self.pairing.validator_account;
+        let onchain = libra_query::account_queries::get_val_config(client, account)
+            .await
+            .context(format!("no ValidatorConfig found for {account}"))?;
+
+        self.verify(&onchain.consensus_public_key)?;
+        let registered = onchain
+            .validator_network_addresses()?
+            .iter()
+            .any(|a| a.find_noise_proto() == Some(self.pairing.val_net_pubkey));
+        if !registered {
+            bail!("the bundle's network key is not registered on chain for validator {account}");
+        }
+        Ok(onchain.consensus_public_key)
+    }
+}
+
+/// On the validator host. Make a signed pairing bundle from the validator's
+/// key files.
+pub fn export_pairing(home: &Path, host: Option<DnsName>) -> anyhow::Result<SignedVfnPairing> {
+    let identity = IdentityBlob::from_file(&home.join(VALIDATOR_IDENTITY_FILE))?;
+    let validator_account = identity
+        .account_address
+        .context("no account address in the validator identity file")?;
+    let consensus_key = identity
+        .consensus_private_key
+        .context("no consensus key in the validator identity file")?;
+
+    let val_host = match host {
+        Some(h) => h,
+        None => {
+            OperatorFile::read(home)
+                .context(format!("no validator host given, and no {OPERATOR_FILE}"))?
+                .config
+                .validator_host
+                .host
+        }
+    };
+
+    let waypoint_path = home.join("genesis").join("waypoint.txt");
+    let waypoint = Waypoint::from_str(
+        std::fs::read_to_string(&waypoint_path)
+            .context(format!("cannot read {}", waypoint_path.display()))?
+            .trim(),
+    )?;
+
+    let pairing = VfnPairing {
+        validator_account,
+        val_net_pubkey: get_local_vfn_id(home)?,
+        val_host,
+        vfn_port: VFN_NETWORK_PORT,
+        waypoint,
+    };
+    SignedVfnPairing::sign(pairing, &consensus_key)
+}
+
+/// On the validator host. Save the VFN's public host in operator.yaml.
+/// Returns true if it changed, the addresses must then be published on
+/// chain with `libra txs validator update`.
+pub fn set_vfn_host(home: &Path, vfn_host: HostAndPort) -> anyhow::Result<bool> {
+    let mut file = OperatorFile::read(home).context(format!(
+        "the VFN host is saved in {OPERATOR_FILE}, which is missing"
+    ))?;
+    let current = file
+        .config
+        .full_node_host
+        .as_ref()
+        .map(|f| (f.host.to_string(), f.port));
+    if current == Some((vfn_host.host.to_string(), vfn_host.port)) {
+        return Ok(false);
+    }
+    file.config.full_node_host = Some(vfn_host);
+    write_to_user_only_file(
+        &home.join(OPERATOR_FILE),
+        OPERATOR_FILE,
+        serde_yaml::to_string(&file)?.as_bytes(),
+    )?;
+    Ok(true)
+}
+
+/// On the VFN host. Make vfn.yaml from a pairing bundle, after checking its
+/// signature with the validator's consensus key. Returns the path of the
+/// config.
+pub fn import_pairing(
+    home: &Path,
+    bundle: &SignedVfnPairing,
+    consensus_key: &bls12381::PublicKey,
+) -> anyhow::Result<PathBuf> {
+    bundle.verify(consensus_key)?;
+    let p = &bundle.pairing;
+
+    let mut builder = NodeYamlBuilder::new(NodePreset::Vfn, home)
+        .waypoint(p.waypoint)
+        .vfn_upstream(p.val_net_pubkey, p.val_host.clone());
+    builder.vfn_port = p.vfn_port;
+
+    let path = home.join(VFN_FILENAME);
+    builder.save(&path)?;
+    Ok(path)
+}
+
+#[test]
+fn pairing_roundtrip() {
+    use diem_crypto::{PrivateKey, Uniform};
+    let mut rng = rand::rngs::OsRng;
+    let consensus_key = bls12381::PrivateKey::generate(&mut rng);
+    let net_key = x25519::PrivateKey::generate(&mut rng);
+
+    let pairing = VfnPairing {
+        validator_account: AccountAddress::from_hex_literal("0x1234").unwrap(),
+        val_net_pubkey: net_key.public_key(),
+        val_host: DnsName::from_str("validator.example.com").unwrap(),
+        vfn_port: VFN_NETWORK_PORT,
+        waypoint: Waypoint::from_str(
+            "0:95023f4d6a7e24cac3e52cad29697184db260214210b57aef3f1031ad4d8c02c",
+        )
+        .unwrap(),
+    };
+    let signed = SignedVfnPairing::sign(pairing, &consensus_key).unwrap();
+
+    // survives the trip between hosts
+    let json = serde_json::to_string(&signed).unwrap();
+    let imported: SignedVfnPairing = serde_json::from_str(&json).unwrap();
+    let validator_key = consensus_key.public_key();
+    imported.verify(&validator_key).unwrap();
+
+    // a tampered address is rejected
+    let mut tampered = imported.clone();
+    tampered.pairing.val_host = DnsName::from_str("attacker.example.com").unwrap();
+    assert!(tampered.verify(&validator_key).is_err());
+
+    // so is a bundle signed by anyone but the validator
+    let other_key = bls12381::PrivateKey::generate(&mut rng);
+    let forged = SignedVfnPairing::sign(tampered.pairing.clone(), &other_key).unwrap();
+    assert!(forged.verify(&validator_key).is_err());
+
+    let home = diem_temppath::TempPath::new();
+    home.create_as_dir().unwrap();
+    let path = import_pairing(home.path(), &imported, &validator_key).unwrap();
+    assert!(path.exists());
+    assert!(import_pairing(home.path(), &tampered, &validator_key).is_err());
+    assert!(import_pairing(home.path(), &forged, &validator_key).is_err());
+
+    // the VFN host is kept in operator.yaml, which must exist
+    let host: HostAndPort = "vfn.example.com:6182".parse().unwrap();
+    assert!(set_vfn_host(home.path(), host).is_err());
+}
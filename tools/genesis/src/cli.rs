@@ -4,6 +4,7 @@ use crate::{
 };
 use clap::{Args, Parser, Subcommand};
 use libra_types::{exports::NamedChain, global_config_dir};
+use libra_wallet::secret_source::SecretSource;
 use std::{fs, path::PathBuf};
 
 #[derive(Parser)]
@@ -53,20 +54,25 @@ impl GenesisCli {
                     None,
                 )?;
             }
-            Some(Sub::Register { github }) => {
-                GenesisWizard::new(
+            Some(Sub::Register {
+                github,
+                secret_source,
+            }) => {
+                let mut wizard = GenesisWizard::new(
                     github.org_github.to_owned(),
                     github.name_github.to_owned(),
                     Some(data_path),
                     chain_name,
-                )
-                .start_wizard(
-                    github.local_framework.to_owned(),
-                    github.json_legacy.to_owned(),
-                    github.token_github_file.to_owned(),
-                    false,
-                )
-                .await?;
+                );
+                wizard.secret_source = SecretSource::from_args(secret_source.as_ref(), None, None);
+                wizard
+                    .start_wizard(
+                        github.local_framework.to_owned(),
+                        github.json_legacy.to_owned(),
+                        github.token_github_file.to_owned(),
+                        false,
+                    )
+                    .await?;
             }
             _ => {}
         }
@@ -112,5 +118,9 @@ enum Sub {
         /// github args
         #[clap(flatten)]
         github: GithubArgs,
+        /// where to read the validator mnemonic from: prompt, env:VAR, fd:N,
+        /// file:PATH, or keystore[:DIR]
+        #[clap(long)]
+        secret_source: Option<SecretSource>,
     },
 }
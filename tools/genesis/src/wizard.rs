@@ -18,7 +18,7 @@ use diem_github_client::Client;
 use indicatif::{ProgressBar, ProgressIterator};
 use libra_config::validator_config::validator_dialogue;
 use libra_types::{core_types::app_cfg::AppCfg, global_config_dir};
-use libra_wallet::keys::VALIDATOR_FILE;
+use libra_wallet::{keys::VALIDATOR_FILE, secret_source::SecretSource};
 use std::{
     env, fs,
     path::{Path, PathBuf},
@@ -49,6 +49,8 @@ pub struct GenesisWizard {
     pub epoch: Option<u64>,
     /// what epoch is the fork happening from
     pub chain: NamedChain,
+    /// where to read the validator mnemonic from, prompts if not set
+    pub secret_source: Option<SecretSource>,
 }
 
 impl GenesisWizard {
@@ -70,6 +72,7 @@ impl GenesisWizard {
             data_path,
             epoch: None,
             chain, // defaults to testing.
+            secret_source: None,
         }
     }
 
@@ -96,6 +99,7 @@ impl GenesisWizard {
             &self.data_path,
             Some(&self.github_username),
             Some(self.chain),
+            self.secret_source.as_ref(),
         )
         .await
         {
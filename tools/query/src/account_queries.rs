@@ -8,7 +8,10 @@ use diem_sdk::{
     types::{account_address::AccountAddress, validator_config::ValidatorConfig},
 };
 use libra_types::{
-    move_resource::{gas_coin::SlowWalletBalance, txschedule::TxSchedule},
+    move_resource::{
+        gas_coin::{LibraBalanceDisplay, SlowWalletBalance},
+        txschedule::TxSchedule,
+    },
     type_extensions::client_ext::{entry_function_id, ClientExt},
 };
 use serde::{Deserialize, Serialize};
@@ -27,6 +30,22 @@ pub struct AccountVouchReportData {
     pub errors: Vec<String>,
 }
 
+/// A watched account and its balance, for the portfolio view
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PortfolioEntry {
+    pub nickname: String,
+    pub account: AccountAddress,
+    pub balance: Option<LibraBalanceDisplay>,
+    pub error: Option<String>,
+}
+
+/// Balances of all watched accounts, and their sum
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PortfolioReport {
+    pub accounts: Vec<PortfolioEntry>,
+    pub total: LibraBalanceDisplay,
+}
+
 /// helper to get libra balance at a SlowWalletBalance type which shows
 /// total balance and the unlocked balance.
 pub async fn get_account_balance_libra(
@@ -45,6 +64,39 @@ pub async fn get_account_balance_libra(
     SlowWalletBalance::from_value(res)
 }
 
+/// Sums the SlowWalletBalance of a list of accounts. Accounts which cannot be
+/// queried (e.g. not yet created on chain) are reported but not summed.
+pub async fn get_portfolio(
+    client: &Client,
+    accounts: &[(String, AccountAddress)],
+) -> anyhow::Result<PortfolioReport> {
+    let mut total = SlowWalletBalance::default();
+    let mut entries = Vec::new();
+
+    for (nickname, account) in accounts {
+        let (balance, error) = match get_account_balance_libra(client, *account).await {
+            Ok(b) => {
+                total.unlocked += b.unlocked;
+                total.total += b.total;
+                (Some(b.scaled()), None)
+            }
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        entries.push(PortfolioEntry {
+            nickname: nickname.to_owned(),
+            account: *account,
+            balance,
+            error,
+        });
+    }
+
+    Ok(PortfolioReport {
+        accounts: entries,
+        total: total.scaled(),
+    })
+}
+
 /// Retrieves the validator configuration for a given account.
 pub async fn get_val_config(
     client: &Client,
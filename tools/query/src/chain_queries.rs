@@ -3,6 +3,7 @@
 use crate::query_view::{self, get_view};
 use anyhow::Context;
 use diem_sdk::rest_client::Client;
+use libra_types::exports::AccountAddress;
 
 /// Retrieves the current epoch from the blockchain.
 pub async fn get_epoch(client: &Client) -> anyhow::Result<u64> {
@@ -101,3 +102,13 @@ pub async fn epoch_over_can_trigger(client: &Client) -> anyhow::Result<bool> {
 
     Ok(value[0])
 }
+
+/// Retrieves the addresses of the validators in the current epoch.
+pub async fn get_current_validators(client: &Client) -> anyhow::Result<Vec<AccountAddress>> {
+    let res = get_view(client, "0x1::stake::get_current_validators", None, None).await?;
+
+    serde_json::from_value::<Vec<Vec<AccountAddress>>>(res)?
+        .into_iter()
+        .next()
+        .context("could not get a response from view function get_current_validators")
+}
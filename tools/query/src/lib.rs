@@ -3,3 +3,4 @@ pub mod chain_queries;
 pub mod query_cli;
 pub mod query_type;
 pub mod query_view;
+pub mod validator_status;
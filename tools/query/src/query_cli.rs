@@ -1,6 +1,6 @@
 use std::path::PathBuf;
 
-use crate::query_type::QueryType;
+use crate::{query_type::QueryType, validator_status::get_validator_status};
 
 use anyhow::Result;
 use clap::Parser;
@@ -37,18 +37,38 @@ impl QueryCli {
         // The user can set an alternative path the the config,
         // which is useful in testnets.
 
+        // The config resolves @nickname accounts and lists the portfolio. It
+        // is optional when a url is given.
+        let app_cfg = match &self.config_path {
+            Some(p) => Some(AppCfg::load(Some(p.to_owned()))?),
+            None => AppCfg::load(None).ok(),
+        };
+
         // Initialize client
         let client = if let Some(u) = &self.url {
             Client::new(u.clone())
-        } else if let Some(p) = &self.config_path {
-            let app_cfg = AppCfg::load(Some(p.to_owned()))?;
-            let (c, _) = Client::from_libra_config(&app_cfg, None).await?;
+        } else if let (Some(app_cfg), Some(_)) = (&app_cfg, &self.config_path) {
+            let (c, _) = Client::from_libra_config(app_cfg, None).await?;
             c
         } else {
             Client::default().await?
         };
 
-        let res = self.subcommand.query_to_json(&client).await?;
+        if let QueryType::ValidatorStatus {
+            account,
+            json: false,
+        } = &self.subcommand
+        {
+            let aliases = app_cfg.as_ref().map(AppCfg::aliases).unwrap_or_default();
+            let status = get_validator_status(&client, account.resolve(&aliases)?).await?;
+            println!("{status}");
+            return Ok(());
+        }
+
+        let res = self
+            .subcommand
+            .query_to_json(&client, app_cfg.as_ref())
+            .await?;
         let pretty_json = serde_json::to_string_pretty(&res)?;
         println!("{}", pretty_json);
 
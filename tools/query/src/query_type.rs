@@ -1,18 +1,23 @@
 use crate::{
     account_queries::{
         account_vouch_report, community_wallet_scheduled_transactions, community_wallet_signers,
-        get_account_balance_libra, get_events, get_transactions, get_val_config,
+        get_account_balance_libra, get_events, get_portfolio, get_transactions, get_val_config,
         is_community_wallet_migrated,
     },
     chain_queries::{get_epoch, get_height},
     query_view::get_view,
+    validator_status::get_validator_status,
 };
 use anyhow::{bail, Context, Result};
 use diem_api_types::Transaction;
 use diem_debugger::DiemDebugger;
-use diem_sdk::{rest_client::Client, types::account_address::AccountAddress};
+use diem_sdk::rest_client::Client;
 use indoc::indoc;
-use libra_types::{exports::AuthenticationKey, type_extensions::client_ext::ClientExt};
+use libra_types::{
+    core_types::app_cfg::{AddressOrAlias, AppCfg},
+    exports::AuthenticationKey,
+    type_extensions::client_ext::ClientExt,
+};
 use serde_json::json;
 
 #[derive(Debug, clap::Subcommand)]
@@ -20,19 +25,27 @@ pub enum QueryType {
     /// Account balance
     Balance {
         /// account to query txs of
-        account: AccountAddress,
+        account: AddressOrAlias,
     },
     /// A validator's on-chain configuration
     ValConfig {
         /// account to query txs of
-        account: AccountAddress,
+        account: AddressOrAlias,
+    },
+    /// Checklist of the conditions for a validator to be seated, and which
+    /// ones are missing
+    ValidatorStatus {
+        account: AddressOrAlias,
+        /// print json instead of the checklist
+        #[clap(long)]
+        json: bool,
     },
     /// Epoch and waypoint
     Epoch,
     /// Query any account resource by access path string
     Resource {
         /// account to query txs of
-        account: AccountAddress,
+        account: AddressOrAlias,
         #[clap(short, long)]
         /// the path of the resource, such as 0x1::slow_wallet::SlowWallet
         resource_path_string: String,
@@ -86,7 +99,7 @@ pub enum QueryType {
     /// Get events
     Events {
         /// account to query events
-        account: AccountAddress,
+        account: AddressOrAlias,
         #[clap(short, long)]
         /// switch for withdrawn or deposited events.
         withdrawn_or_deposited: bool,
@@ -97,7 +110,7 @@ pub enum QueryType {
     /// Get transaction history
     Txs {
         /// account to query txs of
-        account: AccountAddress,
+        account: AddressOrAlias,
         #[clap(long)]
         /// get transactions after this height
         txs_height: Option<u64>,
@@ -111,32 +124,42 @@ pub enum QueryType {
     /// Is the community wallet migrated
     ComWalletMigrated {
         /// account to query txs of
-        account: AccountAddress,
+        account: AddressOrAlias,
     },
     /// Signers of the community wallet
     ComWalletSigners {
         /// account to query txs of
-        account: AccountAddress,
+        account: AddressOrAlias,
     },
     /// Get the community wallet's pending transactions
     ComWalletPendTransactions {
         /// account to query txs of
-        account: AccountAddress,
+        account: AddressOrAlias,
     },
     /// Display all account structs
-    Annotate { account: AccountAddress },
+    Annotate { account: AddressOrAlias },
+    /// Sum the balances of your profiles and the watched accounts in the address book
+    Portfolio,
     /// Generate a comprehensive vouch report showing page rank scores and vouch limits
     VouchReport {
         /// account to generate vouch report for
-        account: AccountAddress,
+        account: AddressOrAlias,
     },
 }
 
 impl QueryType {
-    pub async fn query_to_json(&self, client: &Client) -> Result<serde_json::Value> {
+    /// Run the query. The config, if any, resolves `@nickname` accounts and
+    /// lists the accounts of the portfolio.
+    pub async fn query_to_json(
+        &self,
+        client: &Client,
+        app_cfg: Option<&AppCfg>,
+    ) -> Result<serde_json::Value> {
+        let aliases = app_cfg.map(AppCfg::aliases).unwrap_or_default();
+        let resolve = |a: &AddressOrAlias| a.resolve(&aliases);
         match self {
             QueryType::Balance { account } => {
-                let res = get_account_balance_libra(client, *account).await?;
+                let res = get_account_balance_libra(client, resolve(account)?).await?;
                 Ok(json!(res.scaled()))
             }
             QueryType::View {
@@ -168,7 +191,7 @@ impl QueryType {
                 resource_path_string,
             } => {
                 let res = client
-                    .get_account_resource(*account, resource_path_string)
+                    .get_account_resource(resolve(account)?, resource_path_string)
                     .await?;
 
                 if let Some(r) = res.inner() {
@@ -178,7 +201,7 @@ impl QueryType {
                 }
             }
             QueryType::ValConfig { account } => {
-                let res = get_val_config(client, *account).await?;
+                let res = get_val_config(client, resolve(account)?).await?;
 
                 // make this readable, turn the network address into a string
                 Ok(json!({
@@ -188,6 +211,10 @@ impl QueryType {
                   "validator_index": res.validator_index,
                 }))
             }
+            QueryType::ValidatorStatus { account, .. } => {
+                let status = get_validator_status(client, resolve(account)?).await?;
+                Ok(json!(status))
+            }
             QueryType::BlockHeight => {
                 let height = get_height(client).await?;
                 Ok(json!({ "BlockHeight": height }))
@@ -197,7 +224,13 @@ impl QueryType {
                 withdrawn_or_deposited,
                 seq_start,
             } => {
-                let res = get_events(client, *account, *withdrawn_or_deposited, *seq_start).await?;
+                let res = get_events(
+                    client,
+                    resolve(account)?,
+                    *withdrawn_or_deposited,
+                    *seq_start,
+                )
+                .await?;
                 Ok(json!({ "events": res }))
             }
             QueryType::Txs {
@@ -208,7 +241,7 @@ impl QueryType {
             } => {
                 let res: Vec<Transaction> = get_transactions(
                     client,
-                    *account,
+                    resolve(account)?,
                     *txs_height,
                     *txs_count,
                     txs_type.to_owned(),
@@ -227,24 +260,25 @@ impl QueryType {
                 Ok(json!({ "transactions": prune_res }))
             }
             QueryType::ComWalletMigrated { account } => {
-                let res = is_community_wallet_migrated(client, *account).await?;
+                let res = is_community_wallet_migrated(client, resolve(account)?).await?;
                 Ok(json!({ "migrated": res }))
             }
             QueryType::ComWalletSigners { account } => {
                 // Wont work at the moment as there is no community wallet that with governace structure
-                let _res = community_wallet_signers(client, *account).await?;
+                let _res = community_wallet_signers(client, resolve(account)?).await?;
                 Ok(json!({ "signers": "None"}))
             }
             QueryType::ComWalletPendTransactions { account } => {
                 // Wont work at the moment as there is no community wallet migrated
-                let _res = community_wallet_scheduled_transactions(client, *account).await?;
+                let _res =
+                    community_wallet_scheduled_transactions(client, resolve(account)?).await?;
                 Ok(json!({ "pending_transactions": "None" }))
             }
             QueryType::Annotate { account } => {
                 let dbgger = DiemDebugger::rest_client(client.clone())?;
                 let version = dbgger.get_latest_version().await?;
                 let blob = dbgger
-                    .annotate_account_state_at_version(account.to_owned(), version)
+                    .annotate_account_state_at_version(resolve(account)?, version)
                     .await?;
                 if blob.is_none() {
                     bail!("cannot find account state at {}", account)
@@ -254,10 +288,16 @@ impl QueryType {
             }
             QueryType::VouchReport { account } => {
                 // Get the structured vouch report data
-                let report_data = account_vouch_report(client, *account).await?;
+                let report_data = account_vouch_report(client, resolve(account)?).await?;
                 // Return the data as JSON
                 Ok(json!(report_data))
             }
+            QueryType::Portfolio => {
+                let app_cfg =
+                    app_cfg.context("the portfolio needs the accounts in libra-cli-config.yaml")?;
+                let report = get_portfolio(client, &app_cfg.watched_accounts()).await?;
+                Ok(json!(report))
+            }
             _ => {
                 bail!(
                     "Not implemented for type: {:?}\n Ground control to Major Tom.",
@@ -0,0 +1,367 @@
+//! Why is a validator (not) in the set? Evaluates the same conditions the
+//! proof-of-fee auction checks, and reports each one.
+
+use anyhow::{bail, Context};
+use diem_sdk::{
+    rest_client::{diem_api_types::ViewRequest, Client},
+    types::account_address::AccountAddress,
+};
+use libra_types::{
+    move_resource::gas_coin::cast_coin_to_decimal, type_extensions::client_ext::entry_function_id,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::fmt;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusCheck {
+    pub name: String,
+    /// none if the check does not apply, e.g. performance outside the set
+    pub passed: Option<bool>,
+    pub detail: String,
+}
+
+impl StatusCheck {
+    fn new(name: &str, passed: Option<bool>, detail: String) -> Self {
+        Self {
+            name: name.to_owned(),
+            passed,
+            detail,
+        }
+    }
+
+    /// a view failed, most likely because the account was never registered
+    fn failed_query(name: &str, e: anyhow::Error) -> Self {
+        Self::new(name, Some(false), format!("cannot query: {e}"))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidatorStatus {
+    pub account: AccountAddress,
+    pub epoch: u64,
+    pub in_current_set: bool,
+    /// the framework's own verdict for the next auction
+    pub qualified: bool,
+    pub checks: Vec<StatusCheck>,
+}
+
+impl ValidatorStatus {
+    pub fn missing(&self) -> impl Iterator<Item = &StatusCheck> {
+        self.checks.iter().filter(|c| c.passed == Some(false))
+    }
+}
+
+impl fmt::Display for ValidatorStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let set = if self.in_current_set { "in" } else { "not in" };
+        writeln!(
+            f,
+            "validator {} is {set} the validator set of epoch {}\n",
+            self.account, self.epoch
+        )?;
+        for c in &self.checks {
+            let mark = match c.passed {
+                Some(true) => "[x]",
+                Some(false) => "[ ]",
+                None => "[-]",
+            };
+            writeln!(f, "{mark} {}: {}", c.name, c.detail)?;
+        }
+        let verdict = if self.qualified {
+            "qualifies for the next auction"
+        } else {
+            "does NOT qualify for the next auction"
+        };
+        write!(f, "\n{verdict}")
+    }
+}
+
+async fn view(
+    client: &Client,
+    module: &str,
+    function: &str,
+    arguments: Vec<Value>,
+) -> anyhow::Result<Vec<Value>> {
+    let request = ViewRequest {
+        function: entry_function_id(module, function)?,
+        type_arguments: vec![],
+        arguments,
+    };
+    Ok(client.view(&request, None).await?.into_inner())
+}
+
+/// u64 come back from the api as strings
+fn as_u64(v: Option<&Value>) -> anyhow::Result<u64> {
+    match v {
+        Some(Value::String(s)) => Ok(s.parse()?),
+        Some(Value::Number(n)) => n.as_u64().context("not a u64"),
+        _ => bail!("expected a u64, got {v:?}"),
+    }
+}
+
+fn as_bool(v: Option<&Value>) -> anyhow::Result<bool> {
+    v.and_then(|b| b.as_bool())
+        .context(format!("expected a bool, got {v:?}"))
+}
+
+/// Bids are in tenths of a percent of the nominal reward
+fn bid_pct(bid: u64) -> String {
+    format!("{:.1}%", bid as f64 / 10.0)
+}
+
+/// Run every check, continuing past the failures.
+pub async fn get_validator_status(
+    client: &Client,
+    account: AccountAddress,
+) -> anyhow::Result<ValidatorStatus> {
+    let addr = vec![json!(account.to_string())];
+    let epoch = crate::chain_queries::get_epoch(client).await?;
+    let current = crate::chain_queries::get_current_validators(client).await?;
+    let in_current_set = current.contains(&account);
+
+    let mut checks = vec![];
+
+    let registered = view(client, "stake", "stake_pool_exists", addr.clone())
+        .await
+        .and_then(|r| as_bool(r.first()));
+    checks.push(match registered {
+        Ok(r) => StatusCheck::new(
+            "registered",
+            Some(r),
+            if r {
+                "validator config is published".to_owned()
+            } else {
+                "no validator config, run `libra txs validator register`".to_owned()
+            },
+        ),
+        Err(e) => StatusCheck::failed_query("registered", e),
+    });
+
+    let universe = view(client, "validator_universe", "is_in_universe", addr.clone())
+        .await
+        .and_then(|r| as_bool(r.first()));
+    checks.push(match universe {
+        Ok(u) => StatusCheck::new(
+            "validator universe",
+            Some(u),
+            if u {
+                "eligible to bid".to_owned()
+            } else {
+                "not in the list of validator candidates".to_owned()
+            },
+        ),
+        Err(e) => StatusCheck::failed_query("validator universe", e),
+    });
+
+    let slow = view(client, "slow_wallet", "is_slow", addr.clone())
+        .await
+        .and_then(|r| as_bool(r.first()));
+    checks.push(match slow {
+        Ok(s) => StatusCheck::new(
+            "slow wallet",
+            Some(s),
+            if s {
+                "account is a slow wallet".to_owned()
+            } else {
+                "validators must be slow wallets, run `libra txs user set-slow`".to_owned()
+            },
+        ),
+        Err(e) => StatusCheck::failed_query("slow wallet", e),
+    });
+
+    let jailed = view(client, "jail", "is_jailed", addr.clone())
+        .await
+        .and_then(|r| as_bool(r.first()));
+    checks.push(match jailed {
+        Ok(j) => StatusCheck::new(
+            "not jailed",
+            Some(!j),
+            if j {
+                "jailed, a voucher in the set must unjail you with `libra txs validator jail`"
+                    .to_owned()
+            } else {
+                "not jailed".to_owned()
+            },
+        ),
+        Err(e) => StatusCheck::failed_query("not jailed", e),
+    });
+
+    let vouches = async {
+        let r = view(
+            client,
+            "proof_of_fee",
+            "get_valid_vouchers_in_set",
+            addr.clone(),
+        )
+        .await?;
+        let required = view(
+            client,
+            "proof_of_fee",
+            "calculate_min_vouches_required",
+            vec![json!(current.len().to_string())],
+        )
+        .await?;
+        Ok::<_, anyhow::Error>((
+            as_bool(r.first())?,
+            as_u64(r.get(1))?,
+            as_u64(required.first())?,
+        ))
+    };
+    checks.push(match vouches.await {
+        Ok((ok, count, required)) => StatusCheck::new(
+            "vouches",
+            Some(ok),
+            format!("{count} vouches from current validators, {required} required"),
+        ),
+        Err(e) => StatusCheck::failed_query("vouches", e),
+    });
+
+    let reward = view(client, "proof_of_fee", "get_consensus_reward", vec![]).await;
+    let bid = view(client, "proof_of_fee", "current_bid", addr.clone()).await;
+    match bid.and_then(|b| Ok((as_u64(b.first())?, as_u64(b.get(1))?))) {
+        Ok((bid, expiry)) => {
+            checks.push(StatusCheck::new(
+                "bid",
+                Some(bid > 0),
+                if bid > 0 {
+                    format!("bidding {}", bid_pct(bid))
+                } else {
+                    "no bid, use `libra txs validator pof`".to_owned()
+                },
+            ));
+            // same as the auction's audit, which does not seat a zero expiry
+            let expired = epoch > expiry;
+            checks.push(StatusCheck::new(
+                "bid not expired",
+                Some(!expired),
+                if expired {
+                    format!("bid expired after epoch {expiry}, set a later --epoch-expiry")
+                } else {
+                    format!("bid valid through epoch {expiry}")
+                },
+            ));
+            if let Ok(r) = &reward {
+                if let Ok(clearing) = as_u64(r.get(2)) {
+                    checks.push(StatusCheck::new(
+                        "bid above cutoff",
+                        Some(bid >= clearing),
+                        format!(
+                            "bid {} against last epoch's clearing bid of {}",
+                            bid_pct(bid),
+                            bid_pct(clearing)
+                        ),
+                    ));
+                }
+            }
+        }
+        Err(e) => checks.push(StatusCheck::failed_query("bid", e)),
+    }
+
+    let balance = async {
+        let unlocked = view(client, "slow_wallet", "unlocked_amount", addr.clone()).await?;
+        let entry_fee = match &reward {
+            Ok(r) => as_u64(r.get(1))?,
+            Err(e) => bail!("cannot get the entry fee: {e}"),
+        };
+        Ok::<_, anyhow::Error>((as_u64(unlocked.first())?, entry_fee))
+    };
+    checks.push(match balance.await {
+        Ok((unlocked, fee)) => StatusCheck::new(
+            "unlocked balance",
+            Some(unlocked >= fee),
+            format!(
+                "{} unlocked, the entry fee is {}",
+                cast_coin_to_decimal(unlocked),
+                cast_coin_to_decimal(fee)
+            ),
+        ),
+        Err(e) => StatusCheck::failed_query("unlocked balance", e),
+    });
+
+    // the rank among qualified bidders, against the number of seats
+    let rank = async {
+        let bidders = view(client, "proof_of_fee", "get_bidders", vec![json!(true)]).await?;
+        let bidders: Vec<AccountAddress> =
+            serde_json::from_value(bidders.first().cloned().context("no bidders returned")?)?;
+        let seats = view(client, "musical_chairs", "get_current_seats", vec![]).await?;
+        Ok::<_, anyhow::Error>((
+            bidders.iter().position(|b| *b == account),
+            bidders.len(),
+            as_u64(seats.first())?,
+        ))
+    };
+    checks.push(match rank.await {
+        Ok((Some(i), n, seats)) => StatusCheck::new(
+            "auction rank",
+            Some((i as u64) < seats),
+            format!(
+                "ranked {} of {n} qualified bidders for {seats} seats",
+                i + 1
+            ),
+        ),
+        Ok((None, n, seats)) => StatusCheck::new(
+            "auction rank",
+            Some(false),
+            format!("not among the {n} qualified bidders for {seats} seats"),
+        ),
+        Err(e) => StatusCheck::failed_query("auction rank", e),
+    });
+
+    if in_current_set {
+        let grade = view(client, "grade", "get_validator_grade", addr.clone())
+            .await
+            .and_then(|g| Ok((as_bool(g.first())?, as_u64(g.get(1))?, as_u64(g.get(2))?)));
+        checks.push(match grade {
+            Ok((compliant, accepted, failed)) => StatusCheck::new(
+                "performance",
+                Some(compliant),
+                format!("{accepted} accepted and {failed} failed proposals this epoch"),
+            ),
+            Err(e) => StatusCheck::failed_query("performance", e),
+        });
+    } else {
+        checks.push(StatusCheck::new(
+            "performance",
+            None,
+            "graded only while in the set".to_owned(),
+        ));
+    }
+
+    let qualified = view(client, "proof_of_fee", "audit_qualification", addr)
+        .await
+        .and_then(|r| as_bool(r.get(1)))
+        .unwrap_or(false);
+
+    Ok(ValidatorStatus {
+        account,
+        epoch,
+        in_current_set,
+        qualified,
+        checks,
+    })
+}
+
+#[test]
+fn status_checklist() {
+    let status = ValidatorStatus {
+        account: AccountAddress::from_hex_literal("0x1234").unwrap(),
+        epoch: 7,
+        in_current_set: false,
+        qualified: false,
+        checks: vec![
+            StatusCheck::new("registered", Some(true), "ok".to_owned()),
+            StatusCheck::new("vouches", Some(false), "1 of 2".to_owned()),
+            StatusCheck::new("performance", None, "n/a".to_owned()),
+        ],
+    };
+    let text = status.to_string();
+    assert!(text.contains("[x] registered"));
+    assert!(text.contains("[ ] vouches"));
+    assert!(text.contains("[-] performance"));
+    assert!(text.contains("does NOT qualify"));
+    assert_eq!(status.missing().count(), 1);
+
+    assert_eq!(as_u64(Some(&Value::String("42".to_owned()))).unwrap(), 42);
+    assert_eq!(bid_pct(1100), "110.0%");
+}
@@ -12,8 +12,10 @@ async fn libra_query_test() {
 
     let c = s.client();
 
-    let q = QueryType::Balance { account: val_acct };
-    match q.query_to_json(&c).await {
+    let q = QueryType::Balance {
+        account: val_acct.into(),
+    };
+    match q.query_to_json(&c, None).await {
         Ok(v) => {
             println!("v: {:?}", v);
             let b: LibraBalanceDisplay = serde_json::from_value(v).unwrap();
@@ -37,8 +39,38 @@ async fn account_annotate_test() {
 
     let c = s.client();
 
-    let q = QueryType::Annotate { account: val_acct };
-    let res = q.query_to_json(&c).await.unwrap();
+    let q = QueryType::Annotate {
+        account: val_acct.into(),
+    };
+    let res = q.query_to_json(&c, None).await.unwrap();
     println!("{:#}", &res.as_str().unwrap());
     assert!(res.as_str().unwrap().contains("drop"));
 }
+
+/// a genesis validator is in the set and registered
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn validator_status_test() {
+    use libra_query::validator_status::ValidatorStatus;
+
+    let mut s = LibraSmoke::new(None, None)
+        .await
+        .expect("could not start swarm");
+    let val_acct = s.first_account.address();
+
+    let c = s.client();
+
+    let q = QueryType::ValidatorStatus {
+        account: val_acct.into(),
+        json: true,
+    };
+    let res = q.query_to_json(&c, None).await.unwrap();
+    let status: ValidatorStatus = serde_json::from_value(res).unwrap();
+    println!("{status}");
+    assert!(status.in_current_set);
+    let registered = status
+        .checks
+        .iter()
+        .find(|c| c.name == "registered")
+        .unwrap();
+    assert_eq!(registered.passed, Some(true));
+}
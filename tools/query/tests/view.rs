@@ -15,7 +15,7 @@ async fn libra_view_test() {
         type_args: None,
         args: None,
     };
-    match q.query_to_json(&c).await {
+    match q.query_to_json(&c, None).await {
         Ok(v) => {
             println!("v: {:?}", v);
         }
@@ -194,6 +194,7 @@ pub async fn upgrade_multiple_impl(
         })),
         mnemonic: None,
         test_private_key: Some(smoke.encoded_pri_key.clone()),
+        secret_source: None,
         chain_name: Some(NamedChain::TESTING),
         config_path: Some(d.path().to_owned().join("libra-cli-config.yaml")),
         url: Some(smoke.api_endpoint.clone()),
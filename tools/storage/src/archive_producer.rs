@@ -0,0 +1,357 @@
+//! Produce an epoch archive from a local node's backup service.
+//! At each epoch boundary the epoch ending, the state snapshot at the end of
+//! the epoch, and the transactions since the last backup are written in the
+//! folder layout which `RestoreBundle` expects, compressed, and listed in
+//! the archive's index.json. Operators can then serve the archive with any
+//! `ArchiveSource`.
+
+use crate::archive_source::ArchiveIndex;
+use anyhow::{Context, Result};
+use diem_backup_cli::{
+    backup_types::{
+        epoch_ending::{
+            backup::{EpochEndingBackupController, EpochEndingBackupOpt},
+            manifest::EpochEndingBackup,
+        },
+        state_snapshot::backup::{StateSnapshotBackupController, StateSnapshotBackupOpt},
+        transaction::backup::{TransactionBackupController, TransactionBackupOpt},
+    },
+    storage::{local_fs::LocalFs, BackupStorage},
+    utils::{
+        backup_service_client::{BackupServiceClient, BackupServiceClientOpt},
+        GlobalBackupOpt,
+    },
+};
+use diem_logger::{info, warn};
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+/// progress of the archive, kept next to the snapshots folder
+pub const ARCHIVE_STATE_FILE: &str = "archive_state.json";
+
+/// default address of a node's backup service
+pub const DEFAULT_BACKUP_SERVICE: &str = "http://localhost:6186";
+
+#[derive(Debug, Clone)]
+pub struct ArchiveOpts {
+    /// root of the archive, backups are written to `<archive_dir>/snapshots`
+    pub archive_dir: PathBuf,
+    pub backup_service_address: String,
+    /// first epoch to archive, if the archive is empty. Defaults to the
+    /// last completed epoch
+    pub start_epoch: Option<u64>,
+    pub max_chunk_size: usize,
+    pub concurrent_data_requests: usize,
+    /// leave the chunk files uncompressed
+    pub no_compress: bool,
+}
+
+/// What has been archived so far
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveState {
+    /// the next epoch ending to archive
+    pub next_epoch: u64,
+    /// the first version of the next transaction backup, None before the
+    /// first epoch is archived
+    pub next_version: Option<u64>,
+}
+
+impl ArchiveState {
+    pub fn load(archive_dir: &Path) -> Result<Option<Self>> {
+        let path = archive_dir.join(ARCHIVE_STATE_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let state = serde_json::from_slice(&fs::read(&path)?)
+            .with_context(|| format!("cannot parse {}", path.display()))?;
+        Ok(Some(state))
+    }
+
+    pub fn save(&self, archive_dir: &Path) -> Result<()> {
+        fs::write(
+            archive_dir.join(ARCHIVE_STATE_FILE),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// The completed epochs which are not archived yet. The epoch the node
+    /// is in has not ended.
+    pub fn pending_epochs(&self, current_epoch: u64) -> std::ops::Range<u64> {
+        self.next_epoch..current_epoch.max(self.next_epoch)
+    }
+
+    /// The transactions to back up so that the archive reaches the end of an
+    /// epoch. The first archived epoch only gets its last transaction, enough
+    /// to restore from its snapshot.
+    pub fn transaction_range(&self, epoch_end_version: u64) -> (u64, usize) {
+        let start = self.next_version.unwrap_or(epoch_end_version);
+        (start, (epoch_end_version + 1 - start) as usize)
+    }
+}
+
+pub struct ArchiveProducer {
+    opts: ArchiveOpts,
+    snapshots: PathBuf,
+    client: Arc<BackupServiceClient>,
+    storage: Arc<dyn BackupStorage>,
+}
+
+impl ArchiveProducer {
+    pub fn new(opts: ArchiveOpts) -> Result<Self> {
+        let snapshots = opts.archive_dir.join("snapshots");
+        fs::create_dir_all(&snapshots)?;
+        let client = Arc::new(BackupServiceClient::new_with_opt(BackupServiceClientOpt {
+            address: opts.backup_service_address.clone(),
+        }));
+        let storage: Arc<dyn BackupStorage> = Arc::new(LocalFs::new(snapshots.clone()));
+        Ok(Self {
+            opts,
+            snapshots,
+            client,
+            storage,
+        })
+    }
+
+    fn global_opt(&self) -> GlobalBackupOpt {
+        GlobalBackupOpt {
+            max_chunk_size: self.opts.max_chunk_size,
+            concurrent_data_requests: self.opts.concurrent_data_requests,
+        }
+    }
+
+    async fn current_epoch(&self) -> Result<u64> {
+        let db_state = self
+            .client
+            .get_db_state()
+            .await?
+            .context("the node's db is empty")?;
+        Ok(db_state.epoch)
+    }
+
+    /// Archive every epoch which ended since the last run. Returns the
+    /// number of epochs archived.
+    pub async fn run_once(&self) -> Result<u64> {
+        let current_epoch = self.current_epoch().await?;
+        let mut state = match ArchiveState::load(&self.opts.archive_dir)? {
+            Some(s) => s,
+            None => ArchiveState {
+                next_epoch: self
+                    .opts
+                    .start_epoch
+                    .unwrap_or_else(|| current_epoch.saturating_sub(1)),
+                next_version: None,
+            },
+        };
+
+        // the first run indexes what is there, later runs only add the new
+        // folders
+        let mut index = match ArchiveIndex::load(&self.snapshots)? {
+            Some(i) => i,
+            None => ArchiveIndex::from_dir(&self.snapshots)?,
+        };
+
+        let pending = state.pending_epochs(current_epoch);
+        let count = pending.end - pending.start;
+        for epoch in pending {
+            let version = self.archive_epoch(epoch, &state).await?;
+            state = ArchiveState {
+                next_epoch: epoch + 1,
+                next_version: Some(version + 1),
+            };
+            state.save(&self.opts.archive_dir)?;
+            index.add_new_folders(&self.snapshots)?;
+            index.save(&self.snapshots)?;
+            println!("archived epoch {epoch}, version {version}");
+        }
+        Ok(count)
+    }
+
+    /// Poll the node, archiving each epoch as it ends
+    pub async fn run(&self, poll_interval: Duration) -> Result<()> {
+        loop {
+            if let Err(e) = self.run_once().await {
+                warn!("archive failed, will retry: {e:#}");
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    /// Back up one epoch, returns the version of its last transaction
+    async fn archive_epoch(&self, epoch: u64, state: &ArchiveState) -> Result<u64> {
+        info!("archiving epoch {epoch}");
+        let epoch_manifest = EpochEndingBackupController::new(
+            EpochEndingBackupOpt {
+                start_epoch: epoch,
+                end_epoch: epoch + 1,
+            },
+            self.global_opt(),
+            self.client.clone(),
+            self.storage.clone(),
+        )
+        .run()
+        .await
+        .with_context(|| format!("cannot back up epoch ending {epoch}"))?;
+
+        let manifest: EpochEndingBackup =
+            serde_json::from_slice(&fs::read(self.snapshots.join(&epoch_manifest))?)?;
+        let version = manifest
+            .waypoints
+            .last()
+            .context("epoch ending backup has no waypoint")?
+            .version();
+
+        let state_manifest = StateSnapshotBackupController::new(
+            StateSnapshotBackupOpt { epoch },
+            self.global_opt(),
+            self.client.clone(),
+            self.storage.clone(),
+        )
+        .run()
+        .await
+        .with_context(|| format!("cannot back up state at epoch {epoch}"))?;
+
+        let (start_version, num_transactions) = state.transaction_range(version);
+        let transaction_manifest = TransactionBackupController::new(
+            TransactionBackupOpt {
+                start_version,
+                num_transactions,
+            },
+            self.global_opt(),
+            self.client.clone(),
+            self.storage.clone(),
+        )
+        .run()
+        .await
+        .with_context(|| format!("cannot back up transactions from {start_version}"))?;
+
+        if !self.opts.no_compress {
+            for m in [&epoch_manifest, &state_manifest, &transaction_manifest] {
+                compress_backup(&self.snapshots, m)?;
+            }
+        }
+        Ok(version)
+    }
+}
+
+/// Gzip the files a manifest refers to, and point the manifest at the .gz
+/// files. The manifest itself stays uncompressed.
+pub fn compress_backup(snapshots: &Path, manifest_handle: &str) -> Result<()> {
+    let manifest_path = snapshots.join(manifest_handle);
+    let mut manifest: Value = serde_json::from_slice(&fs::read(&manifest_path)?)?;
+
+    let fields = manifest
+        .as_object_mut()
+        .context("manifest is not a json object")?;
+    let mut handles = vec![];
+    for (key, value) in fields.iter_mut() {
+        match (key.as_str(), value) {
+            ("chunks", Value::Array(chunks)) => {
+                for chunk in chunks.iter_mut().filter_map(Value::as_object_mut) {
+                    handles.extend(chunk.values_mut().filter(|v| v.is_string()));
+                }
+            }
+            ("proof", proof @ Value::String(_)) => handles.push(proof),
+            _ => {}
+        }
+    }
+
+    for handle in handles {
+        let Some(name) = handle.as_str().filter(|h| !h.ends_with(".gz")) else {
+            continue;
+        };
+        let compressed = format!("{name}.gz");
+        gzip_file(&snapshots.join(name), &snapshots.join(&compressed))?;
+        *handle = Value::String(compressed);
+    }
+
+    fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)?;
+    Ok(())
+}
+
+fn gzip_file(from: &Path, to: &Path) -> Result<()> {
+    let mut input = File::open(from).with_context(|| format!("cannot open {}", from.display()))?;
+    let mut encoder = GzEncoder::new(File::create(to)?, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(from)?;
+    Ok(())
+}
+
+#[test]
+fn plan_epochs() {
+    let empty = ArchiveState {
+        next_epoch: 9,
+        next_version: None,
+    };
+    assert_eq!(empty.pending_epochs(10), 9..10);
+    assert_eq!(empty.pending_epochs(9), 9..9);
+    // the node is behind the archive
+    assert!(empty.pending_epochs(5).is_empty());
+    assert_eq!(empty.transaction_range(500), (500, 1));
+
+    let resumed = ArchiveState {
+        next_epoch: 10,
+        next_version: Some(501),
+    };
+    assert_eq!(resumed.pending_epochs(12), 10..12);
+    assert_eq!(resumed.transaction_range(700), (501, 200));
+}
+
+#[test]
+fn compress_manifest_files() {
+    let dir = diem_temppath::TempPath::new();
+    dir.create_as_dir().unwrap();
+    let snapshots = dir.path();
+    let folder = snapshots.join("state_epoch_9_ver_500.abcd");
+    fs::create_dir_all(&folder).unwrap();
+    fs::write(folder.join("0-.chunk"), b"blobs").unwrap();
+    fs::write(folder.join("0-.proof"), b"chunk proof").unwrap();
+    fs::write(folder.join("state.proof"), b"proof").unwrap();
+    let manifest = serde_json::json!({
+        "version": 500,
+        "epoch": 9,
+        "chunks": [{
+            "first_idx": 0,
+            "blobs": "state_epoch_9_ver_500.abcd/0-.chunk",
+            "proof": "state_epoch_9_ver_500.abcd/0-.proof"
+        }],
+        "proof": "state_epoch_9_ver_500.abcd/state.proof"
+    });
+    fs::write(folder.join("state.manifest"), manifest.to_string()).unwrap();
+
+    let handle = "state_epoch_9_ver_500.abcd/state.manifest";
+    compress_backup(snapshots, handle).unwrap();
+    // idempotent
+    compress_backup(snapshots, handle).unwrap();
+
+    let updated: Value =
+        serde_json::from_slice(&fs::read(folder.join("state.manifest")).unwrap()).unwrap();
+    assert_eq!(
+        updated["chunks"][0]["blobs"],
+        "state_epoch_9_ver_500.abcd/0-.chunk.gz"
+    );
+    assert_eq!(
+        updated["proof"],
+        "state_epoch_9_ver_500.abcd/state.proof.gz"
+    );
+    assert_eq!(updated["chunks"][0]["first_idx"], 0);
+    assert!(folder.join("0-.chunk.gz").exists());
+    assert!(!folder.join("0-.chunk").exists());
+
+    let mut out = String::new();
+    io::Read::read_to_string(
+        &mut flate2::read::GzDecoder::new(File::open(folder.join("0-.proof.gz")).unwrap()),
+        &mut out,
+    )
+    .unwrap();
+    assert_eq!(out, "chunk proof");
+}
@@ -0,0 +1,750 @@
+//! Where restore bundles are downloaded from.
+//! An epoch archive is a `snapshots` directory with one folder per backup:
+//! epoch_ending_*, state_epoch_* and transaction_*. The same layout can be
+//! served from GitHub, a plain HTTP server with an index, a local disk, or an
+//! S3-compatible bucket. Sources are chosen with a location string:
+//!   github:OWNER/REPO[@BRANCH]     the GitHub tree API (the default)
+//!   http(s)://HOST/PATH            a directory with an index.json
+//!   s3+http(s)://HOST/BUCKET/PATH  an S3-compatible bucket, public or presigned read
+//!   file:PATH, or any other PATH   a local copy of the archive
+
+use crate::{
+    download_bundle::{download_github_folder, list_github_folders},
+    downloader::{sha256_hex, Downloader, FileHash, RemoteFile},
+};
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::UNIX_EPOCH,
+};
+
+pub const DEFAULT_OWNER: &str = "0LNetworkCommunity";
+pub const DEFAULT_REPO: &str = "epoch-archive-mainnet";
+pub const DEFAULT_BRANCH: &str = "v7.0.0";
+/// the directory of backup folders in an archive
+pub const SNAPSHOTS_DIR: &str = "snapshots";
+/// lists the files of an archive served over plain HTTP
+pub const INDEX_FILE: &str = "index.json";
+
+#[async_trait]
+pub trait ArchiveSource: fmt::Display + Send + Sync {
+    /// the names of the backup folders in the archive
+    async fn list_folders(&self) -> Result<Vec<String>>;
+
+    /// download a backup folder with all its files to `destination/folder`
+    async fn fetch_folder(&self, folder: &str, destination: &Path) -> Result<PathBuf>;
+}
+
+/// Parse a location string into a source, see the module docs.
+pub fn archive_source(location: &str) -> Result<Box<dyn ArchiveSource>> {
+    Ok(match location.parse::<ArchiveLocation>()? {
+        ArchiveLocation::Github(s) => Box::new(s),
+        ArchiveLocation::Http(s) => Box::new(s),
+        ArchiveLocation::S3(s) => Box::new(s),
+        ArchiveLocation::Local(s) => Box::new(s),
+    })
+}
+
+enum ArchiveLocation {
+    Github(GithubSource),
+    Http(HttpIndexSource),
+    S3(S3Source),
+    Local(LocalSource),
+}
+
+impl FromStr for ArchiveLocation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(rest) = s.strip_prefix("github:") {
+            let (path, branch) = rest.split_once('@').unwrap_or((rest, DEFAULT_BRANCH));
+            let Some((owner, repo)) = path.split_once('/') else {
+                bail!("expected github:OWNER/REPO[@BRANCH], got {s}");
+            };
+            return Ok(ArchiveLocation::Github(GithubSource::new(
+                owner, repo, branch,
+            )));
+        }
+        if let Some(rest) = s.strip_prefix("s3+") {
+            return Ok(ArchiveLocation::S3(S3Source::from_url(rest)?));
+        }
+        if s.starts_with("http://") || s.starts_with("https://") {
+            return Ok(ArchiveLocation::Http(HttpIndexSource::new(s)));
+        }
+        let path = s.strip_prefix("file:").unwrap_or(s);
+        Ok(ArchiveLocation::Local(LocalSource::new(PathBuf::from(
+            path,
+        ))))
+    }
+}
+
+/// Only the top level backup folders are of interest
+fn is_backup_folder(name: &str) -> bool {
+    name.starts_with("epoch_ending_")
+        || name.starts_with("state_epoch_")
+        || name.starts_with("transaction_")
+}
+
+/// The epoch-archive repos on GitHub
+pub struct GithubSource {
+    pub owner: String,
+    pub repo: String,
+    pub branch: String,
+}
+
+impl GithubSource {
+    pub fn new(owner: &str, repo: &str, branch: &str) -> Self {
+        Self {
+            owner: owner.to_owned(),
+            repo: repo.to_owned(),
+            branch: branch.to_owned(),
+        }
+    }
+}
+
+impl Default for GithubSource {
+    fn default() -> Self {
+        Self::new(DEFAULT_OWNER, DEFAULT_REPO, DEFAULT_BRANCH)
+    }
+}
+
+impl fmt::Display for GithubSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "github:{}/{}@{}", self.owner, self.repo, self.branch)
+    }
+}
+
+#[async_trait]
+impl ArchiveSource for GithubSource {
+    async fn list_folders(&self) -> Result<Vec<String>> {
+        list_github_folders(
+            &reqwest::Client::new(),
+            &self.owner,
+            &self.repo,
+            &self.branch,
+            SNAPSHOTS_DIR,
+        )
+        .await
+    }
+
+    async fn fetch_folder(&self, folder: &str, destination: &Path) -> Result<PathBuf> {
+        download_github_folder(
+            &self.owner,
+            &self.repo,
+            &format!("{SNAPSHOTS_DIR}/{folder}"),
+            &self.branch,
+            destination
+                .to_str()
+                .context("destination is not a valid path")?,
+        )
+        .await?;
+        Ok(destination.join(folder))
+    }
+}
+
+/// The files of each backup folder, relative to the folder. Published as
+/// index.json next to the folders, so an archive can be mirrored on any
+/// static HTTP server.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveIndex {
+    pub folders: BTreeMap<String, Vec<String>>,
+    /// keyed by "folder/file", so downloads can be verified. Older indexes
+    /// don't have them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub checksums: BTreeMap<String, FileChecksum>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileChecksum {
+    pub size: u64,
+    pub sha256: String,
+    /// modification time in unix milliseconds, to tell whether the hash of
+    /// a local file is still current
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_ms: Option<u64>,
+}
+
+impl ArchiveIndex {
+    /// Index a local archive, e.g. to publish a mirror. Files listed in an
+    /// existing index.json with the same size and modification time keep
+    /// their hash, only the others are hashed.
+    pub fn from_dir(snapshots: &Path) -> Result<Self> {
+        // a broken index is rebuilt from scratch
+        let cached = Self::load(snapshots)
+            .ok()
+            .flatten()
+            .map(|i| i.checksums)
+            .unwrap_or_default();
+        let mut index = ArchiveIndex::default();
+        for name in local_backup_folders(snapshots)? {
+            index.add_folder(snapshots, name, &cached)?;
+        }
+        Ok(index)
+    }
+
+    /// the index.json of a local archive, if it has one
+    pub fn load(snapshots: &Path) -> Result<Option<Self>> {
+        let path = snapshots.join(INDEX_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let index = serde_json::from_slice(&fs::read(&path)?)
+            .with_context(|| format!("cannot parse {}", path.display()))?;
+        Ok(Some(index))
+    }
+
+    /// Index only the backup folders which are not in the index yet, so the
+    /// files already indexed are not hashed again. Returns the number of
+    /// folders added.
+    pub fn add_new_folders(&mut self, snapshots: &Path) -> Result<usize> {
+        let mut added = 0;
+        for name in local_backup_folders(snapshots)? {
+            if !self.folders.contains_key(&name) {
+                self.add_folder(snapshots, name, &BTreeMap::new())?;
+                added += 1;
+            }
+        }
+        Ok(added)
+    }
+
+    fn add_folder(
+        &mut self,
+        snapshots: &Path,
+        name: String,
+        cached: &BTreeMap<String, FileChecksum>,
+    ) -> Result<()> {
+        let dir = snapshots.join(&name);
+        let mut files = vec![];
+        collect_files(&dir, &dir, &mut files)?;
+        files.sort();
+        for file in &files {
+            let path = dir.join(file);
+            let key = format!("{name}/{file}");
+            let meta = fs::metadata(&path)?;
+            let modified_ms = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64);
+            let checksum = match cached.get(&key) {
+                Some(c)
+                    if c.size == meta.len()
+                        && modified_ms.is_some()
+                        && c.modified_ms == modified_ms =>
+                {
+                    c.clone()
+                }
+                _ => FileChecksum {
+                    size: meta.len(),
+                    sha256: sha256_hex(&path)?,
+                    modified_ms,
+                },
+            };
+            self.checksums.insert(key, checksum);
+        }
+        self.folders.insert(name, files);
+        Ok(())
+    }
+
+    pub fn save(&self, snapshots: &Path) -> Result<PathBuf> {
+        let path = snapshots.join(INDEX_FILE);
+        fs::write(&path, serde_json::to_string_pretty(self)?)?;
+        Ok(path)
+    }
+}
+
+/// the backup folders in a local snapshots directory
+fn local_backup_folders(snapshots: &Path) -> Result<Vec<String>> {
+    let mut folders = vec![];
+    for entry in fs::read_dir(snapshots)
+        .with_context(|| format!("cannot read archive at {}", snapshots.display()))?
+    {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.file_type()?.is_dir() && is_backup_folder(&name) {
+            folders.push(name);
+        }
+    }
+    Ok(folders)
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else {
+            let relative = path.strip_prefix(root)?;
+            // urls always use forward slashes
+            let parts: Vec<_> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect();
+            files.push(parts.join("/"));
+        }
+    }
+    Ok(())
+}
+
+/// A plain HTTP directory, with an index.json listing the files
+pub struct HttpIndexSource {
+    /// the url of the snapshots directory
+    pub base_url: String,
+}
+
+impl HttpIndexSource {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        }
+    }
+
+    async fn index(&self) -> Result<ArchiveIndex> {
+        let url = format!("{}/{}", self.base_url, INDEX_FILE);
+        reqwest::get(&url)
+            .await
+            .with_context(|| format!("cannot fetch {url}"))?
+            .error_for_status()?
+            .json()
+            .await
+            .with_context(|| format!("cannot parse {url}"))
+    }
+}
+
+impl fmt::Display for HttpIndexSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.base_url)
+    }
+}
+
+#[async_trait]
+impl ArchiveSource for HttpIndexSource {
+    async fn list_folders(&self) -> Result<Vec<String>> {
+        Ok(self.index().await?.folders.into_keys().collect())
+    }
+
+    async fn fetch_folder(&self, folder: &str, destination: &Path) -> Result<PathBuf> {
+        let index = self.index().await?;
+        let files = index
+            .folders
+            .get(folder)
+            .with_context(|| format!("{folder} is not in the index of {self}"))?;
+
+        let out = destination.join(folder);
+        let remote: Vec<_> = files
+            .iter()
+            .map(|file| {
+                let url = format!("{}/{}/{}", self.base_url, folder, file);
+                let mut remote = RemoteFile::new(url, out.join(file));
+                if let Some(c) = index.checksums.get(&format!("{folder}/{file}")) {
+                    remote.size = Some(c.size);
+                    remote.hash = Some(FileHash::Sha256(c.sha256.clone()));
+                }
+                remote
+            })
+            .collect();
+        Downloader::default()
+            .download_all(&remote, &format!("downloading {folder}"))
+            .await?;
+        Ok(out)
+    }
+}
+
+/// An archive on a local or mounted disk
+pub struct LocalSource {
+    pub root: PathBuf,
+}
+
+impl LocalSource {
+    /// `root` can be the archive or its snapshots directory
+    pub fn new(root: PathBuf) -> Self {
+        let snapshots = root.join(SNAPSHOTS_DIR);
+        Self {
+            root: if snapshots.is_dir() { snapshots } else { root },
+        }
+    }
+}
+
+impl fmt::Display for LocalSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "file:{}", self.root.display())
+    }
+}
+
+#[async_trait]
+impl ArchiveSource for LocalSource {
+    async fn list_folders(&self) -> Result<Vec<String>> {
+        local_backup_folders(&self.root)
+    }
+
+    async fn fetch_folder(&self, folder: &str, destination: &Path) -> Result<PathBuf> {
+        let src = self.root.join(folder);
+        if !src.is_dir() {
+            bail!("no folder {folder} in {self}");
+        }
+        fs::create_dir_all(destination)?;
+        let mut opts = fs_extra::dir::CopyOptions::new();
+        opts.overwrite = true;
+        fs_extra::dir::copy(&src, destination, &opts)
+            .with_context(|| format!("cannot copy {}", src.display()))?;
+        Ok(destination.join(folder))
+    }
+}
+
+/// An S3-compatible bucket, read with path-style urls and no signing, so the
+/// bucket must allow public reads.
+pub struct S3Source {
+    /// e.g. https://s3.us-east-1.amazonaws.com
+    pub endpoint: String,
+    pub bucket: String,
+    /// the key prefix of the snapshots directory, without slashes at the ends
+    pub prefix: String,
+}
+
+impl S3Source {
+    /// from a url like https://HOST/BUCKET/PATH
+    pub fn from_url(url: &str) -> Result<Self> {
+        let (scheme, rest) = url
+            .split_once("://")
+            .with_context(|| format!("expected s3+https://HOST/BUCKET/PATH, got {url}"))?;
+        let mut parts = rest.splitn(3, '/');
+        let host = parts.next().unwrap_or_default();
+        let bucket = parts.next().unwrap_or_default();
+        if host.is_empty() || bucket.is_empty() {
+            bail!("expected s3+https://HOST/BUCKET/PATH, got {url}");
+        }
+        Ok(Self {
+            endpoint: format!("{scheme}://{host}"),
+            bucket: bucket.to_owned(),
+            prefix: parts
+                .next()
+                .unwrap_or_default()
+                .trim_matches('/')
+                .to_owned(),
+        })
+    }
+
+    fn key_prefix(&self, folder: Option<&str>) -> String {
+        let mut p = self.prefix.clone();
+        if let Some(f) = folder {
+            if !p.is_empty() {
+                p.push('/');
+            }
+            p.push_str(f);
+        }
+        if !p.is_empty() {
+            p.push('/');
+        }
+        p
+    }
+
+    /// ListObjectsV2 through all pages. Returns the keys with their sizes,
+    /// and the common prefixes when a delimiter is used.
+    async fn list(
+        &self,
+        prefix: &str,
+        delimiter: bool,
+    ) -> Result<(Vec<(String, Option<u64>)>, Vec<String>)> {
+        let client = reqwest::Client::new();
+        let url = format!("{}/{}", self.endpoint, self.bucket);
+        let (mut keys, mut prefixes) = (vec![], vec![]);
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2".to_owned()), ("prefix", prefix.to_owned())];
+            if delimiter {
+                query.push(("delimiter", "/".to_owned()));
+            }
+            if let Some(t) = &token {
+                query.push(("continuation-token", t.to_owned()));
+            }
+            let body = client
+                .get(&url)
+                .query(&query)
+                .send()
+                .await
+                .with_context(|| format!("cannot list {self}"))?
+                .error_for_status()?
+                .text()
+                .await?;
+
+            keys.extend(keys_with_sizes(&body));
+            prefixes.extend(
+                xml_values(&body, "Prefix")
+                    .into_iter()
+                    .filter(|p| p != prefix),
+            );
+            token = xml_values(&body, "NextContinuationToken").pop();
+            if token.is_none() {
+                break;
+            }
+        }
+        Ok((keys, prefixes))
+    }
+}
+
+impl fmt::Display for S3Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "s3+{}/{}/{}", self.endpoint, self.bucket, self.prefix)
+    }
+}
+
+/// The text of every `<tag>` element. The list responses are simple enough
+/// not to need an xml parser.
+fn xml_values(body: &str, tag: &str) -> Vec<String> {
+    let (open, close) = (format!("<{tag}>"), format!("</{tag}>"));
+    let mut out = vec![];
+    let mut rest = body;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let Some(end) = rest.find(&close) else {
+            break;
+        };
+        out.push(
+            rest[..end]
+                .replace("&amp;", "&")
+                .replace("&lt;", "<")
+                .replace("&gt;", ">"),
+        );
+        rest = &rest[end + close.len()..];
+    }
+    out
+}
+
+/// Percent-encode a key for a url, as S3 expects: everything but the
+/// unreserved characters, keeping the slashes
+fn encode_key(key: &str) -> String {
+    let mut out = String::with_capacity(key.len());
+    for b in key.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{b:02X}")),
+        }
+    }
+    out
+}
+
+/// Each `<Contents>` has a `<Key>` and a `<Size>`. Sizes are left out if
+/// they don't line up with the keys.
+fn keys_with_sizes(body: &str) -> Vec<(String, Option<u64>)> {
+    let keys = xml_values(body, "Key");
+    let sizes: Vec<_> = xml_values(body, "Size")
+        .iter()
+        .map(|s| s.parse::<u64>().ok())
+        .collect();
+    if sizes.len() != keys.len() {
+        return keys.into_iter().map(|k| (k, None)).collect();
+    }
+    keys.into_iter().zip(sizes).collect()
+}
+
+#[async_trait]
+impl ArchiveSource for S3Source {
+    async fn list_folders(&self) -> Result<Vec<String>> {
+        let (_, prefixes) = self.list(&self.key_prefix(None), true).await?;
+        Ok(prefixes
+            .iter()
+            .filter_map(|p| p.trim_end_matches('/').rsplit('/').next())
+            .filter(|name| is_backup_folder(name))
+            .map(|s| s.to_owned())
+            .collect())
+    }
+
+    async fn fetch_folder(&self, folder: &str, destination: &Path) -> Result<PathBuf> {
+        let prefix = self.key_prefix(Some(folder));
+        let (keys, _) = self.list(&prefix, false).await?;
+        if keys.is_empty() {
+            bail!("no folder {folder} in {self}");
+        }
+        let out = destination.join(folder);
+        let remote: Vec<_> = keys
+            .iter()
+            .filter_map(|(key, size)| {
+                let relative = key.strip_prefix(&prefix)?;
+                let url = format!("{}/{}/{}", self.endpoint, self.bucket, encode_key(key));
+                let mut remote = RemoteFile::new(url, out.join(relative));
+                remote.size = *size;
+                Some(remote)
+            })
+            .collect();
+        Downloader::default()
+            .download_all(&remote, &format!("downloading {folder}"))
+            .await?;
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_locations() {
+        let gh = archive_source("github:0LNetworkCommunity/epoch-archive-testnet@main").unwrap();
+        assert_eq!(
+            gh.to_string(),
+            "github:0LNetworkCommunity/epoch-archive-testnet@main"
+        );
+
+        let s3 = archive_source("s3+https://s3.example.com/archive/mainnet/snapshots").unwrap();
+        assert_eq!(
+            s3.to_string(),
+            "s3+https://s3.example.com/archive/mainnet/snapshots"
+        );
+        assert!(archive_source("s3+https://s3.example.com").is_err());
+
+        let http = archive_source("https://mirror.example.com/snapshots/").unwrap();
+        assert_eq!(http.to_string(), "https://mirror.example.com/snapshots");
+
+        let local = archive_source("file:/mnt/archive").unwrap();
+        assert_eq!(local.to_string(), "file:/mnt/archive");
+    }
+
+    #[test]
+    fn s3_list_response() {
+        let body = "<ListBucketResult><CommonPrefixes><Prefix>snapshots/epoch_ending_1-.aa/</Prefix></CommonPrefixes><Contents><Key>snapshots/a&amp;b</Key></Contents><NextContinuationToken>abc</NextContinuationToken></ListBucketResult>";
+        assert_eq!(
+            xml_values(body, "Prefix"),
+            vec!["snapshots/epoch_ending_1-.aa/"]
+        );
+        assert_eq!(xml_values(body, "Key"), vec!["snapshots/a&b"]);
+        assert_eq!(xml_values(body, "NextContinuationToken"), vec!["abc"]);
+        assert!(xml_values(body, "Missing").is_empty());
+        assert_eq!(
+            keys_with_sizes(body),
+            vec![("snapshots/a&b".to_owned(), None)]
+        );
+        assert_eq!(
+            encode_key("snapshots/epoch_ending_1-.aa/a&b c+d"),
+            "snapshots/epoch_ending_1-.aa/a%26b%20c%2Bd"
+        );
+        let sized = "<Contents><Key>a</Key><Size>12</Size></Contents><Contents><Key>b</Key><Size>3</Size></Contents>";
+        assert_eq!(
+            keys_with_sizes(sized),
+            vec![("a".to_owned(), Some(12)), ("b".to_owned(), Some(3))]
+        );
+    }
+
+    #[tokio::test]
+    async fn local_lists_backup_folders() {
+        let dir = diem_temppath::TempPath::new();
+        dir.create_as_dir().unwrap();
+        fs::create_dir_all(dir.path().join("epoch_ending_29-.abcd")).unwrap();
+        fs::create_dir_all(dir.path().join("not_a_backup")).unwrap();
+        fs::write(dir.path().join("transaction_1-.abcd"), "not a folder").unwrap();
+
+        let folders = LocalSource::new(dir.path().to_path_buf())
+            .list_folders()
+            .await
+            .unwrap();
+        assert_eq!(folders, vec!["epoch_ending_29-.abcd"]);
+    }
+
+    #[test]
+    fn index_local_archive() {
+        let dir = diem_temppath::TempPath::new();
+        dir.create_as_dir().unwrap();
+        let folder = dir.path().join("epoch_ending_29-.abcd");
+        fs::create_dir_all(folder.join("chunks")).unwrap();
+        fs::write(folder.join("epoch_ending.manifest"), "{}").unwrap();
+        fs::write(folder.join("chunks").join("29-.chunk"), "data").unwrap();
+        fs::create_dir_all(dir.path().join("not_a_backup")).unwrap();
+
+        let index = ArchiveIndex::from_dir(dir.path()).unwrap();
+        assert_eq!(index.folders.len(), 1);
+        assert_eq!(
+            index.folders["epoch_ending_29-.abcd"],
+            vec!["chunks/29-.chunk", "epoch_ending.manifest"]
+        );
+        let chunk = &index.checksums["epoch_ending_29-.abcd/chunks/29-.chunk"];
+        assert_eq!(chunk.size, 4);
+        // sha256sum of "data"
+        assert_eq!(
+            chunk.sha256,
+            "3a6eb0790f39ac87c94f3856b2dd2c5d110e6811602261a9a923d3bb23adc8b7"
+        );
+        assert_eq!(index.checksums.len(), 2);
+
+        // only the new folder is indexed when the archive grows
+        index.save(dir.path()).unwrap();
+        let mut index = ArchiveIndex::load(dir.path()).unwrap().unwrap();
+        fs::write(folder.join("epoch_ending.manifest"), "changed").unwrap();
+        let next = dir.path().join("epoch_ending_30-.ef01");
+        fs::create_dir_all(&next).unwrap();
+        fs::write(next.join("epoch_ending.manifest"), "{}").unwrap();
+        assert_eq!(index.add_new_folders(dir.path()).unwrap(), 1);
+        assert_eq!(index.folders.len(), 2);
+        assert_eq!(
+            index.checksums["epoch_ending_29-.abcd/epoch_ending.manifest"].size,
+            2
+        );
+        assert_eq!(index.checksums.len(), 3);
+
+        // a rebuild keeps the cached hash of an unchanged file, and hashes a
+        // changed one again
+        let manifest = "epoch_ending_30-.ef01/epoch_ending.manifest";
+        index.checksums.get_mut(manifest).unwrap().sha256 = "cached".to_owned();
+        index.save(dir.path()).unwrap();
+        let rebuilt = ArchiveIndex::from_dir(dir.path()).unwrap();
+        assert_eq!(rebuilt.checksums[manifest].sha256, "cached");
+        // same size, only the modification time tells it changed
+        let path = next.join("epoch_ending.manifest");
+        fs::write(&path, "[]").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(60))
+            .unwrap();
+        let rebuilt = ArchiveIndex::from_dir(dir.path()).unwrap();
+        assert_ne!(rebuilt.checksums[manifest].sha256, "cached");
+    }
+
+    #[tokio::test]
+    async fn restore_bundle_from_local_archive() {
+        let archive = diem_temppath::TempPath::new();
+        archive.create_as_dir().unwrap();
+        let snapshots = archive.path().join(SNAPSHOTS_DIR);
+        for folder in [
+            "epoch_ending_15-.efgh",
+            "epoch_ending_29-.abcd",
+            "state_epoch_29_ver_12345678.abcd",
+            "transaction_12000000-.aaaa",
+            "transaction_13000000-.bbbb",
+        ] {
+            fs::create_dir_all(snapshots.join(folder)).unwrap();
+            fs::write(snapshots.join(folder).join("manifest"), folder).unwrap();
+        }
+
+        // the archive root resolves to its snapshots directory
+        let source = archive_source(archive.path().to_str().unwrap()).unwrap();
+        assert_eq!(source.to_string(), format!("file:{}", snapshots.display()));
+
+        let dest = diem_temppath::TempPath::new();
+        dest.create_as_dir().unwrap();
+        let bundle = crate::download_bundle::download_restore_bundle(&*source, &30, dest.path())
+            .await
+            .unwrap();
+
+        let mut fetched: Vec<_> = fs::read_dir(&bundle)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        fetched.sort();
+        assert_eq!(
+            fetched,
+            vec![
+                "epoch_ending_29-.abcd",
+                "state_epoch_29_ver_12345678.abcd",
+                "transaction_12000000-.aaaa"
+            ]
+        );
+        assert!(bundle.join("transaction_12000000-.aaaa/manifest").exists());
+    }
+}
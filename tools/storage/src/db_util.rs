@@ -0,0 +1,79 @@
+//! Shared helpers for reading a restored DB offline.
+
+use anyhow::{bail, Context, Result};
+use diem_config::config::{
+    RocksdbConfigs, BUFFERED_STATE_TARGET_ITEMS, DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    NO_OP_STORAGE_PRUNER_CONFIG,
+};
+use diem_db::DiemDB;
+use diem_storage_interface::{DbReader, DbReaderWriter};
+use diem_types::state_store::{state_key::StateKey, state_value::StateValue};
+use std::{path::Path, sync::Arc};
+
+/// state values read per request from the DB
+const DB_CHUNK_SIZE: usize = 10_000;
+
+/// Open a restored DB without write access
+pub fn open_read_only(db_path: &Path) -> Result<DbReaderWriter> {
+    if !db_path.exists() {
+        bail!("DB path does not exist: {}", db_path.display());
+    }
+
+    let db = DiemDB::open(
+        db_path,
+        true,                        /* readonly */
+        NO_OP_STORAGE_PRUNER_CONFIG, /* pruner_config */
+        RocksdbConfigs::default(),
+        false, /* enable_indexer */
+        BUFFERED_STATE_TARGET_ITEMS,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    )
+    .context("Failed to open database")?;
+    Ok(DbReaderWriter::new(db))
+}
+
+/// Reads the state values of a DB at a version a chunk at a time, in the
+/// order of the key hashes, like the chunks of a snapshot backup.
+pub struct DbStateChunks {
+    reader: Arc<dyn DbReader>,
+    pub version: u64,
+    count: usize,
+    index: usize,
+}
+
+impl DbStateChunks {
+    /// at a state checkpoint version, by default the latest
+    pub fn new(reader: Arc<dyn DbReader>, version: Option<u64>) -> Result<Self> {
+        let version = match version {
+            Some(v) => v,
+            None => reader
+                .get_latest_state_checkpoint_version()?
+                .context("the DB has no state")?,
+        };
+        let count = reader
+            .get_state_item_count(version)
+            .with_context(|| format!("no state at version {version}, is it a state checkpoint?"))?;
+        Ok(Self {
+            reader,
+            version,
+            count,
+            index: 0,
+        })
+    }
+
+    pub fn next_chunk(&mut self) -> Result<Option<Vec<(StateKey, StateValue)>>> {
+        if self.index >= self.count {
+            return Ok(None);
+        }
+        let chunk = self.reader.get_state_value_chunk_with_proof(
+            self.version,
+            self.index,
+            DB_CHUNK_SIZE.min(self.count - self.index),
+        )?;
+        if chunk.raw_values.is_empty() {
+            return Ok(None);
+        }
+        self.index += chunk.raw_values.len();
+        Ok(Some(chunk.raw_values))
+    }
+}
@@ -24,6 +24,7 @@ use diem_config::config::{
 };
 use diem_db::{state_restore::StateSnapshotRestoreMode, DiemDB, GetRestoreHandler};
 use diem_executor_types::VerifyExecutionMode;
+use diem_types::waypoint::Waypoint;
 
 /// types of db restore. Note: all of them are necessary for a successful restore.
 pub enum RestoreTypes {
@@ -39,6 +40,19 @@ pub fn get_backup_storage(local_fs_dir: PathBuf) -> Result<Arc<dyn BackupStorage
 pub fn get_global_db_opts(
     db_dir: &Path,
     bundle: &RestoreBundle,
+) -> anyhow::Result<GlobalRestoreOptions> {
+    global_restore_opts(
+        db_dir,
+        vec![bundle.waypoint.expect("no waypoint")],
+        bundle.version,
+    )
+}
+
+/// restore options for any target version, trusting the given waypoints
+pub fn global_restore_opts(
+    db_dir: &Path,
+    trust_waypoint: Vec<Waypoint>,
+    target_version: u64,
 ) -> anyhow::Result<GlobalRestoreOptions> {
     let restore_handler = Arc::new(DiemDB::open_kv_only(
         db_dir.to_owned(),
@@ -53,12 +67,10 @@ pub fn get_global_db_opts(
 
     let run_mode = RestoreRunMode::Restore { restore_handler };
 
-    let twp = TrustedWaypointOpt {
-        trust_waypoint: vec![bundle.waypoint.expect("no waypoint")],
-    };
+    let twp = TrustedWaypointOpt { trust_waypoint };
 
     Ok(GlobalRestoreOptions {
-        target_version: bundle.version,
+        target_version,
         trusted_waypoints: Arc::new(twp.verify().expect("cannot verify waypoint")),
         run_mode: Arc::new(run_mode),
         concurrent_downloads: num_cpus::get(),
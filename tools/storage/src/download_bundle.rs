@@ -1,5 +1,9 @@
-use crate::parse_folder_names::{
-    parse_epoch_ending_number, parse_state_epoch_info, parse_transaction_number,
+use crate::{
+    archive_source::ArchiveSource,
+    downloader::{Downloader, FileHash, RemoteFile},
+    parse_folder_names::{
+        parse_epoch_ending_number, parse_state_epoch_info, parse_transaction_number,
+    },
 };
 use anyhow::bail;
 use anyhow::{Context, Result};
@@ -29,6 +33,7 @@ struct TreeItem {
     #[serde(rename = "type")]
     item_type: String,
     sha: String,
+    size: Option<u64>,
     url: Option<String>,
 }
 
@@ -98,17 +103,18 @@ fn find_closest_transaction_folder(
         .context("No suitable transaction folder found below target version")
 }
 
-pub async fn find_closest_epoch_folder(
+/// List the backup folders in a directory of an archive repo on GitHub
+pub async fn list_github_folders(
     client: &Client,
     owner: &str,
     repo: &str,
     branch: &str,
-    target_epoch: u64,
-) -> Result<EpochFolders> {
+    path: &str,
+) -> Result<Vec<String>> {
     // Update to use the Git Tree API instead of Contents API
     let api_url = format!(
-        "https://api.github.com/repos/{}/{}/git/trees/{}:snapshots",
-        owner, repo, branch
+        "https://api.github.com/repos/{}/{}/git/trees/{}:{}",
+        owner, repo, branch, path
     );
 
     let response = client
@@ -127,29 +133,45 @@ pub async fn find_closest_epoch_folder(
         info!("Warning: GitHub Tree API response is truncated. Some folders might be missing.");
     }
 
+    Ok(tree_response
+        .tree
+        .into_iter()
+        // Only consider tree items (directories)
+        .filter(|item| item.item_type == "tree")
+        // Extract just the folder name from the path
+        .filter_map(|item| {
+            Path::new(&item.path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .collect())
+}
+
+pub async fn find_closest_epoch_folder(
+    source: &dyn ArchiveSource,
+    target_epoch: u64,
+) -> Result<EpochFolders> {
+    let folders = source
+        .list_folders()
+        .await
+        .with_context(|| format!("cannot list the folders of {source}"))?;
+    closest_epoch_folders(&folders, target_epoch)
+}
+
+/// Pick the folders to restore `target_epoch` from the names in an archive
+pub fn closest_epoch_folders(folder_names: &[String], target_epoch: u64) -> Result<EpochFolders> {
     // Separate folders by type
     let mut epoch_ending_folders: Vec<(u64, String)> = Vec::new();
     let mut state_epoch_folders: Vec<(u64, String)> = Vec::new();
     let mut transaction_folders: Vec<(u64, String)> = Vec::new();
 
-    for item in tree_response.tree {
-        // Only consider tree items (directories)
-        if item.item_type != "tree" {
-            continue;
-        }
-
-        // Extract just the folder name from the path
-        let folder_name = match Path::new(&item.path).file_name() {
-            Some(name) => name.to_string_lossy().to_string(),
-            None => continue,
-        };
-
-        if let Some(epoch) = parse_epoch_ending_number(&folder_name) {
-            epoch_ending_folders.push((epoch, folder_name));
-        } else if let Some((epoch, _version)) = parse_state_epoch_info(&folder_name) {
-            state_epoch_folders.push((epoch, folder_name));
-        } else if let Some(version) = parse_transaction_number(&folder_name) {
-            transaction_folders.push((version, folder_name));
+    for folder_name in folder_names {
+        if let Some(epoch) = parse_epoch_ending_number(folder_name) {
+            epoch_ending_folders.push((epoch, folder_name.clone()));
+        } else if let Some((epoch, _version)) = parse_state_epoch_info(folder_name) {
+            state_epoch_folders.push((epoch, folder_name.clone()));
+        } else if let Some(version) = parse_transaction_number(folder_name) {
+            transaction_folders.push((version, folder_name.clone()));
         }
     }
 
@@ -186,6 +208,85 @@ pub async fn find_closest_epoch_folder(
     })
 }
 
+/// The folders to restore to an arbitrary version
+#[derive(Debug)]
+pub struct VersionFolders {
+    pub epoch_endings: Vec<String>,
+    pub state_epoch: String,
+    pub transactions: Vec<String>,
+}
+
+/// Pick the closest snapshot at or below `target_version`, and the epoch
+/// endings and transactions from that snapshot up to the target
+pub fn closest_version_folders(
+    folder_names: &[String],
+    target_version: u64,
+) -> Result<VersionFolders> {
+    let mut epoch_ending_folders: Vec<(u64, String)> = Vec::new();
+    let mut state_epoch_folders: Vec<(u64, u64, String)> = Vec::new();
+    let mut transaction_folders: Vec<(u64, String)> = Vec::new();
+
+    for folder_name in folder_names {
+        if let Some(epoch) = parse_epoch_ending_number(folder_name) {
+            epoch_ending_folders.push((epoch, folder_name.clone()));
+        } else if let Some((epoch, version)) = parse_state_epoch_info(folder_name) {
+            state_epoch_folders.push((epoch, version, folder_name.clone()));
+        } else if let Some(version) = parse_transaction_number(folder_name) {
+            transaction_folders.push((version, folder_name.clone()));
+        }
+    }
+
+    let (snapshot_epoch, snapshot_version, state_epoch) = state_epoch_folders
+        .iter()
+        .filter(|(_, version, _)| version <= &target_version)
+        .max_by_key(|(_, version, _)| *version)
+        .cloned()
+        .with_context(|| format!("No state_epoch folder at or below version {target_version}"))?;
+
+    // the target is before the end of the first snapshot's epoch past it
+    let end_epoch = state_epoch_folders
+        .iter()
+        .filter(|(_, version, _)| version > &target_version)
+        .map(|(epoch, _, _)| *epoch)
+        .min()
+        .unwrap_or(u64::MAX);
+
+    let mut epoch_endings = vec![find_closest_epoch_ending(
+        &epoch_ending_folders,
+        snapshot_epoch,
+    )?];
+    epoch_ending_folders.sort();
+    epoch_endings.extend(
+        epoch_ending_folders
+            .into_iter()
+            .filter(|(epoch, _)| epoch > &snapshot_epoch && epoch < &end_epoch)
+            .map(|(_, name)| name),
+    );
+
+    let mut transactions = vec![find_closest_transaction_folder(
+        &transaction_folders,
+        snapshot_version,
+    )?];
+    transaction_folders.sort();
+    transactions.extend(
+        transaction_folders
+            .into_iter()
+            .filter(|(version, _)| version > &snapshot_version && version <= &target_version)
+            .map(|(_, name)| name),
+    );
+
+    info!("Found folders for version {}:", target_version);
+    info!("  epoch_ending: {:?}", epoch_endings);
+    info!("  state_epoch:  {}", state_epoch);
+    info!("  transaction:  {:?}", transactions);
+
+    Ok(VersionFolders {
+        epoch_endings,
+        state_epoch,
+        transactions,
+    })
+}
+
 fn find_closest_epoch_ending(folders: &[(u64, String)], target: u64) -> Result<String> {
     // For epoch_ending, find the highest epoch that's less than or equal to target
     folders
@@ -259,51 +360,33 @@ pub async fn download_github_folder(
     let base_dir = Path::new(output_dir).join(base_path);
     fs::create_dir_all(&base_dir)?;
 
-    // Process files from the tree
-    for item in tree_response.tree {
-        // Skip if not a blob (file)
-        if item.item_type != "blob" {
-            continue;
-        }
-
-        // The path in tree response is relative to the requested path
-        let relative_path = item.path;
-
-        // Compute where to save the file
-        let output_path = base_dir.join(&relative_path);
-
-        // Create parent directory if it doesn't exist
-        if let Some(parent) = output_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
-
-        // Download the file content using raw GitHub URL
-        let content_url = format!(
-            "https://raw.githubusercontent.com/{}/{}/{}/{}/{}",
-            owner, repo, branch, path, relative_path
-        );
-
-        let content = client
-            .get(&content_url)
-            .header("User-Agent", "libra-framework-downloader")
-            .send()
-            .await
-            .with_context(|| format!("Failed to download file: {}", relative_path))?
-            .bytes()
-            .await
-            .with_context(|| format!("Failed to read bytes from: {}", relative_path))?;
-
-        fs::write(&output_path, content)
-            .with_context(|| format!("Failed to write file: {}", relative_path))?;
-    }
+    // Files from the tree, git lists each blob's size and hash
+    let files: Vec<RemoteFile> = tree_response
+        .tree
+        .into_iter()
+        .filter(|item| item.item_type == "blob")
+        .map(|item| {
+            // The path in tree response is relative to the requested path
+            let url = format!(
+                "https://raw.githubusercontent.com/{}/{}/{}/{}/{}",
+                owner, repo, branch, path, item.path
+            );
+            let mut file = RemoteFile::new(url, base_dir.join(&item.path));
+            file.size = item.size;
+            file.hash = Some(FileHash::GitBlob(item.sha));
+            file
+        })
+        .collect();
+
+    Downloader::default()
+        .download_all(&files, &format!("downloading {path}"))
+        .await?;
 
     Ok(())
 }
 
 pub async fn download_restore_bundle(
-    owner: &str,
-    repo: &str,
-    branch: &str,
+    source: &dyn ArchiveSource,
     epoch_num: &u64,
     destination: &Path,
 ) -> Result<PathBuf> {
@@ -314,9 +397,11 @@ pub async fn download_restore_bundle(
         fs::create_dir_all(&bundle_dir)?;
     }
 
-    let client = reqwest::Client::new();
-
-    let folders = find_closest_epoch_folder(&client, owner, repo, branch, *epoch_num).await?;
+    println!(
+        "finding restore bundle for epoch {} in {}",
+        epoch_num, source
+    );
+    let folders = find_closest_epoch_folder(source, *epoch_num).await?;
 
     // Download all three folders
     for folder in [
@@ -324,15 +409,7 @@ pub async fn download_restore_bundle(
         &folders.state_epoch,
         &folders.transaction,
     ] {
-        let snapshot_path = format!("snapshots/{}", folder);
-        download_github_folder(
-            owner,
-            repo,
-            &snapshot_path,
-            branch,
-            bundle_dir.to_str().unwrap(),
-        )
-        .await?;
+        source.fetch_folder(folder, &bundle_dir).await?;
     }
 
     println!(
@@ -343,6 +420,42 @@ pub async fn download_restore_bundle(
     Ok(bundle_dir)
 }
 
+/// Download the folders to restore to any version, see `closest_version_folders`
+pub async fn download_version_bundle(
+    source: &dyn ArchiveSource,
+    version: u64,
+    destination: &Path,
+) -> Result<PathBuf> {
+    let bundle_dir = destination.join(format!("version_{}_restore_bundle", version));
+    fs::create_dir_all(&bundle_dir)?;
+
+    println!(
+        "finding restore bundle for version {} in {}",
+        version, source
+    );
+    let names = source
+        .list_folders()
+        .await
+        .with_context(|| format!("cannot list the folders of {source}"))?;
+    let folders = closest_version_folders(&names, version)?;
+
+    for folder in folders
+        .epoch_endings
+        .iter()
+        .chain([&folders.state_epoch])
+        .chain(&folders.transactions)
+    {
+        source.fetch_folder(folder, &bundle_dir).await?;
+    }
+
+    println!(
+        "Successfully downloaded restore bundle for version {} into {}",
+        version,
+        bundle_dir.display()
+    );
+    Ok(bundle_dir)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -415,4 +528,40 @@ mod tests {
             "transaction_33000000-.58b4"
         );
     }
+
+    #[test]
+    fn test_version_folder_selection() {
+        let names: Vec<String> = [
+            "epoch_ending_115-.aaaa",
+            "epoch_ending_116-.be9b",
+            "epoch_ending_117-.cccc",
+            "epoch_ending_118-.dddd",
+            "state_epoch_116_ver_38180075.05af",
+            "state_epoch_118_ver_38400000.eeee",
+            "transaction_38100001-.541f",
+            "transaction_38200001-.ffff",
+            "transaction_38300001-.1111",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let folders = closest_version_folders(&names, 38250000).unwrap();
+        assert_eq!(folders.state_epoch, "state_epoch_116_ver_38180075.05af");
+        assert_eq!(
+            folders.epoch_endings,
+            vec!["epoch_ending_116-.be9b", "epoch_ending_117-.cccc"]
+        );
+        assert_eq!(
+            folders.transactions,
+            vec!["transaction_38100001-.541f", "transaction_38200001-.ffff"]
+        );
+
+        // exactly at a snapshot
+        let folders = closest_version_folders(&names, 38180075).unwrap();
+        assert_eq!(folders.transactions, vec!["transaction_38100001-.541f"]);
+
+        // before the first snapshot
+        assert!(closest_version_folders(&names, 38000000).is_err());
+    }
 }
@@ -0,0 +1,356 @@
+//! Download the files of an archive: concurrently with a bounded pool,
+//! resuming partial files with HTTP range requests, retrying transient
+//! errors with backoff, and verifying sizes and hashes when the source
+//! publishes them.
+
+use anyhow::{anyhow, bail, Context, Result};
+use diem_logger::warn;
+use futures::{stream, StreamExt};
+use indicatif::ProgressBar;
+use libra_types::ol_progress::OLProgress;
+use reqwest::{header::RANGE, StatusCode};
+use sha1::Sha1;
+use sha2_0_10_6::{Digest, Sha256};
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+pub const DEFAULT_CONCURRENCY: usize = 8;
+pub const DEFAULT_RETRIES: u32 = 5;
+/// appended to a file while it downloads
+const PART_SUFFIX: &str = ".part";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileHash {
+    /// sha1 of "blob {len}\0{content}", as listed by the GitHub tree API
+    GitBlob(String),
+    Sha256(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteFile {
+    pub url: String,
+    pub destination: PathBuf,
+    pub size: Option<u64>,
+    pub hash: Option<FileHash>,
+}
+
+impl RemoteFile {
+    pub fn new(url: String, destination: PathBuf) -> Self {
+        Self {
+            url,
+            destination,
+            size: None,
+            hash: None,
+        }
+    }
+
+    fn part_path(&self) -> PathBuf {
+        let mut name = self.destination.clone().into_os_string();
+        name.push(PART_SUFFIX);
+        PathBuf::from(name)
+    }
+
+    /// check the size and the hash, if known
+    pub fn verify(&self, path: &Path) -> Result<()> {
+        let len = fs::metadata(path)?.len();
+        if let Some(size) = self.size {
+            if len != size {
+                bail!("{} has {len} bytes, expected {size}", self.url);
+            }
+        }
+        if let Some(expected) = &self.hash {
+            let (actual, wanted) = match expected {
+                FileHash::GitBlob(h) => (git_blob_sha1(path)?, h),
+                FileHash::Sha256(h) => (sha256_hex(path)?, h),
+            };
+            if !actual.eq_ignore_ascii_case(wanted) {
+                bail!("{} has hash {actual}, expected {wanted}", self.url);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// hex sha256 of a file
+pub fn sha256_hex(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hash_file(path, |b| hasher.update(b))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// the object id git gives a file, e.g. `git hash-object`
+pub fn git_blob_sha1(path: &Path) -> Result<String> {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", fs::metadata(path)?.len()));
+    hash_file(path, |b| hasher.update(b))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn hash_file(path: &Path, mut update: impl FnMut(&[u8])) -> Result<()> {
+    let mut file = File::open(path).with_context(|| format!("cannot open {}", path.display()))?;
+    let mut buf = vec![0; 1 << 16];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+        update(&buf[..n]);
+    }
+}
+
+/// Errors worth a retry, e.g. a dropped connection or a 503
+enum Failure {
+    Transient(anyhow::Error),
+    Permanent(anyhow::Error),
+}
+
+impl From<io::Error> for Failure {
+    fn from(e: io::Error) -> Self {
+        Failure::Permanent(e.into())
+    }
+}
+
+impl From<reqwest::Error> for Failure {
+    fn from(e: reqwest::Error) -> Self {
+        Failure::Transient(e.into())
+    }
+}
+
+pub struct Downloader {
+    client: reqwest::Client,
+    /// files downloaded at the same time
+    pub concurrency: usize,
+    pub retries: u32,
+    /// delay before the first retry, doubled for each retry
+    pub backoff: Duration,
+}
+
+impl Default for Downloader {
+    fn default() -> Self {
+        Self {
+            client: reqwest::Client::builder()
+                .user_agent("libra-framework-downloader")
+                .build()
+                .expect("cannot build http client"),
+            concurrency: DEFAULT_CONCURRENCY,
+            retries: DEFAULT_RETRIES,
+            backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+impl Downloader {
+    /// Download all files, showing the aggregate progress. Files which are
+    /// already downloaded and verified are skipped.
+    pub async fn download_all(&self, files: &[RemoteFile], message: &str) -> Result<()> {
+        let total = files.iter().filter_map(|f| f.size).sum();
+        let pb = ProgressBar::new(total)
+            .with_style(OLProgress::bytes())
+            .with_message(message.to_owned());
+
+        let failures: Vec<_> = stream::iter(files.iter().map(|f| self.download(f, &pb)))
+            .buffer_unordered(self.concurrency.max(1))
+            .filter_map(|r| async move { r.err() })
+            .collect()
+            .await;
+        pb.finish_and_clear();
+
+        if let Some(e) = failures.first() {
+            bail!(
+                "{} of {} files failed to download, e.g. {e:#}",
+                failures.len(),
+                files.len()
+            );
+        }
+        Ok(())
+    }
+
+    /// Download one file, resuming and retrying
+    pub async fn download(&self, file: &RemoteFile, pb: &ProgressBar) -> Result<()> {
+        if file.destination.exists() && file.verify(&file.destination).is_ok() {
+            pb.inc(file.size.unwrap_or(0));
+            return Ok(());
+        }
+        // bytes from an interrupted run
+        if let Ok(m) = fs::metadata(file.part_path()) {
+            pb.inc(m.len());
+        }
+
+        let mut attempt = 0;
+        loop {
+            match self.try_download(file, pb).await {
+                Ok(()) => return Ok(()),
+                Err(Failure::Permanent(e)) => return Err(e),
+                Err(Failure::Transient(e)) if attempt < self.retries => {
+                    let delay = self.backoff * 2u32.pow(attempt);
+                    warn!("retrying {} in {:?}: {:#}", file.url, delay, e);
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(Failure::Transient(e)) => {
+                    return Err(e.context(format!("gave up after {} retries", self.retries)))
+                }
+            }
+        }
+    }
+
+    async fn try_download(&self, file: &RemoteFile, pb: &ProgressBar) -> Result<(), Failure> {
+        let part = file.part_path();
+        if let Some(parent) = part.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut offset = fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+        if file.size.is_some_and(|size| offset > size) {
+            pb.set_position(pb.position().saturating_sub(offset));
+            fs::remove_file(&part)?;
+            offset = 0;
+        }
+
+        let mut request = self.client.get(&file.url);
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={offset}-"));
+        }
+        let mut response = request.send().await?;
+        let status = response.status();
+        if status == StatusCode::RANGE_NOT_SATISFIABLE {
+            // the partial file is stale, start over
+            pb.set_position(pb.position().saturating_sub(offset));
+            fs::remove_file(&part)?;
+            return Err(Failure::Transient(anyhow!(
+                "{} cannot be resumed",
+                file.url
+            )));
+        }
+        if status.is_server_error()
+            || status == StatusCode::TOO_MANY_REQUESTS
+            || status == StatusCode::REQUEST_TIMEOUT
+        {
+            return Err(Failure::Transient(anyhow!("{status} from {}", file.url)));
+        }
+        if !status.is_success() {
+            return Err(Failure::Permanent(anyhow!("{status} from {}", file.url)));
+        }
+
+        // a server which ignores the range sends the whole file
+        let append = offset > 0 && status == StatusCode::PARTIAL_CONTENT;
+        if !append {
+            pb.set_position(pb.position().saturating_sub(offset));
+        }
+        if file.size.is_none() {
+            if let Some(len) = response.content_length() {
+                pb.inc_length(len);
+            }
+        }
+
+        let mut out = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(&part)?;
+        while let Some(chunk) = response.chunk().await? {
+            out.write_all(&chunk)?;
+            pb.inc(chunk.len() as u64);
+        }
+        out.flush()?;
+        drop(out);
+
+        if let Err(e) = file.verify(&part) {
+            // corrupted, download it again from the start
+            pb.set_position(pb.position().saturating_sub(fs::metadata(&part)?.len()));
+            fs::remove_file(&part)?;
+            return Err(Failure::Transient(e));
+        }
+        fs::rename(&part, &file.destination)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn hash_files() {
+    let dir = diem_temppath::TempPath::new();
+    dir.create_as_dir().unwrap();
+    let path = dir.path().join("hello");
+    fs::write(&path, "hello\n").unwrap();
+    // git hash-object hello
+    assert_eq!(
+        git_blob_sha1(&path).unwrap(),
+        "ce013625030ba8dba906f756967f9e9ca394464a"
+    );
+    assert_eq!(
+        sha256_hex(&path).unwrap(),
+        "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03"
+    );
+
+    let mut file = RemoteFile::new("http://example.com/hello".to_owned(), path.clone());
+    file.size = Some(6);
+    file.hash = Some(FileHash::GitBlob(
+        "ce013625030ba8dba906f756967f9e9ca394464a".to_owned(),
+    ));
+    assert!(file.verify(&path).is_ok());
+    file.size = Some(7);
+    assert!(file.verify(&path).is_err());
+}
+
+#[tokio::test]
+async fn resume_partial_download() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let body: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let served = body.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let n = socket.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..n]).to_lowercase();
+            let start = request
+                .lines()
+                .find_map(|l| l.strip_prefix("range: bytes="))
+                .and_then(|r| r.trim_end_matches('-').parse::<usize>().ok());
+            let (status, content) = match start {
+                Some(s) => ("206 Partial Content", &served[s..]),
+                None => ("200 OK", &served[..]),
+            };
+            let head = format!(
+                "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                content.len()
+            );
+            socket.write_all(head.as_bytes()).await.unwrap();
+            socket.write_all(content).await.unwrap();
+        }
+    });
+
+    let dir = diem_temppath::TempPath::new();
+    dir.create_as_dir().unwrap();
+    let destination = dir.path().join("chunks/0-.chunk");
+    let mut file = RemoteFile::new(format!("http://{address}/0-.chunk"), destination.clone());
+    file.size = Some(body.len() as u64);
+    file.hash = Some(FileHash::Sha256(hex::encode(Sha256::digest(&body))));
+
+    // an interrupted download left the first half
+    fs::create_dir_all(destination.parent().unwrap()).unwrap();
+    fs::write(file.part_path(), &body[..50_000]).unwrap();
+
+    Downloader::default()
+        .download_all(&[file.clone()], "test")
+        .await
+        .unwrap();
+    assert_eq!(fs::read(&destination).unwrap(), body);
+    assert!(!file.part_path().exists());
+
+    // a corrupted partial file is downloaded again
+    fs::remove_file(&destination).unwrap();
+    fs::write(file.part_path(), vec![0u8; 50_000]).unwrap();
+    Downloader::default()
+        .download_all(&[file], "test")
+        .await
+        .unwrap();
+    assert_eq!(fs::read(&destination).unwrap(), body);
+}
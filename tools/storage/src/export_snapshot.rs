@@ -0,0 +1,320 @@
+//! Export a v7 state snapshot as typed JSON records, one per account.
+//! Unlike `read_snapshot::manifest_to_json`, which casts accounts to the v6
+//! recovery format, this decodes the resources of the current framework.
+
+use crate::read_snapshot::filtered_state_values_from_snapshot;
+use anyhow::{Context, Result};
+use diem_types::{
+    account_address::AccountAddress, account_config::AccountResource, account_state::AccountState,
+    account_view::AccountView, state_store::state_key::StateKeyInner,
+};
+use libra_types::move_resource::{
+    ancestry::AncestryResource,
+    cumulative_deposits::CumulativeDepositResource,
+    donor_voice::{FreezeResource, RegistryResource},
+    jail::JailResource,
+    libra_coin::LibraCoinStoreResource,
+    ol_account::BurnTrackerResource,
+    originating_address::OriginatingAddressResource,
+    pledge_account::MyPledgesResource,
+    receipts::ReceiptsResource,
+    vouch::{GivenVouchesResource, ReceivedVouchesResource},
+    wallet::SlowWalletResource,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The resources which can be selected for export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum ResourceKind {
+    Account,
+    Balance,
+    SlowWallet,
+    BurnTracker,
+    Vouch,
+    Receipts,
+    Pledges,
+    DonorVoice,
+    CumulativeDeposits,
+    Ancestry,
+    Jail,
+}
+
+/// Which accounts and resources to export, everything if empty
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotFilter {
+    pub resources: Vec<ResourceKind>,
+    pub addresses: Vec<AccountAddress>,
+}
+
+impl SnapshotFilter {
+    fn wants(&self, kind: ResourceKind) -> bool {
+        self.resources.is_empty() || self.resources.contains(&kind)
+    }
+
+    fn wants_address(&self, address: &AccountAddress) -> bool {
+        self.addresses.is_empty() || self.addresses.contains(address)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountRecord {
+    pub address: AccountAddress,
+    pub sequence_number: Option<u64>,
+    /// hex
+    pub authentication_key: Option<String>,
+    /// CoinStore<LibraCoin>
+    pub balance: Option<u64>,
+    pub slow_wallet: Option<SlowWalletResource>,
+    pub burn_tracker: Option<BurnTrackerResource>,
+    pub received_vouches: Option<ReceivedVouchesResource>,
+    pub given_vouches: Option<GivenVouchesResource>,
+    pub receipts: Option<ReceiptsResource>,
+    pub pledges: Option<MyPledgesResource>,
+    /// in the donor voice registry
+    pub donor_voice: Option<bool>,
+    pub donor_voice_freeze: Option<FreezeResource>,
+    pub cumulative_deposits: Option<CumulativeDepositResource>,
+    pub ancestry: Option<AncestryResource>,
+    pub jail: Option<JailResource>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SnapshotExport {
+    pub version: u64,
+    pub accounts: Vec<AccountRecord>,
+    /// authentication key (as address) to the original address, from
+    /// 0x1::account::OriginatingAddress
+    pub originating_address: BTreeMap<AccountAddress, AccountAddress>,
+}
+
+/// Decode the selected resources of one account
+pub fn account_record(
+    state: &AccountState,
+    address: AccountAddress,
+    filter: &SnapshotFilter,
+    donor_voice_accounts: &HashSet<AccountAddress>,
+) -> Result<AccountRecord> {
+    let mut record = AccountRecord {
+        address,
+        sequence_number: None,
+        authentication_key: None,
+        balance: None,
+        slow_wallet: None,
+        burn_tracker: None,
+        received_vouches: None,
+        given_vouches: None,
+        receipts: None,
+        pledges: None,
+        donor_voice: None,
+        donor_voice_freeze: None,
+        cumulative_deposits: None,
+        ancestry: None,
+        jail: None,
+    };
+
+    if filter.wants(ResourceKind::Account) {
+        if let Some(a) = state.get_move_resource::<AccountResource>()? {
+            record.sequence_number = Some(a.sequence_number());
+            record.authentication_key = Some(hex::encode(a.authentication_key()));
+        }
+    }
+    if filter.wants(ResourceKind::Balance) {
+        record.balance = state
+            .get_move_resource::<LibraCoinStoreResource>()?
+            .map(|c| c.coin());
+    }
+    if filter.wants(ResourceKind::SlowWallet) {
+        record.slow_wallet = state.get_move_resource::<SlowWalletResource>()?;
+    }
+    if filter.wants(ResourceKind::BurnTracker) {
+        record.burn_tracker = state.get_move_resource::<BurnTrackerResource>()?;
+    }
+    if filter.wants(ResourceKind::Vouch) {
+        record.received_vouches = state.get_move_resource::<ReceivedVouchesResource>()?;
+        record.given_vouches = state.get_move_resource::<GivenVouchesResource>()?;
+    }
+    if filter.wants(ResourceKind::Receipts) {
+        record.receipts = state.get_move_resource::<ReceiptsResource>()?;
+    }
+    if filter.wants(ResourceKind::Pledges) {
+        record.pledges = state.get_move_resource::<MyPledgesResource>()?;
+    }
+    if filter.wants(ResourceKind::DonorVoice) {
+        record.donor_voice = Some(donor_voice_accounts.contains(&address));
+        record.donor_voice_freeze = state.get_move_resource::<FreezeResource>()?;
+    }
+    if filter.wants(ResourceKind::CumulativeDeposits) {
+        record.cumulative_deposits = state.get_move_resource::<CumulativeDepositResource>()?;
+    }
+    if filter.wants(ResourceKind::Ancestry) {
+        record.ancestry = state.get_move_resource::<AncestryResource>()?;
+    }
+    if filter.wants(ResourceKind::Jail) {
+        record.jail = state.get_move_resource::<JailResource>()?;
+    }
+    Ok(record)
+}
+
+/// The accounts in the donor voice registry, from the 0x1 account
+pub fn donor_voice_registry(framework: &AccountState) -> Result<HashSet<AccountAddress>> {
+    Ok(framework
+        .get_move_resource::<RegistryResource>()?
+        .map(|r| r.list.into_iter().collect())
+        .unwrap_or_default())
+}
+
+/// Decode the entries of a Table<address, address>
+pub fn address_table(
+    items: &[(Vec<u8>, Vec<u8>)],
+) -> Result<BTreeMap<AccountAddress, AccountAddress>> {
+    items
+        .iter()
+        .map(|(k, v)| Ok((bcs::from_bytes(k)?, bcs::from_bytes(v)?)))
+        .collect()
+}
+
+/// Read the chunks of a snapshot, and decode the accounts which pass the
+/// filter. Only 0x1 is read in the first pass, for the registries and
+/// tables. The second pass keeps only the selected accounts.
+pub async fn export_snapshot_v7(
+    manifest_path: &Path,
+    filter: &SnapshotFilter,
+) -> Result<SnapshotExport> {
+    let framework_state = filtered_state_values_from_snapshot(manifest_path, |key| {
+        matches!(key.inner(), StateKeyInner::AccessPath(p) if p.address == AccountAddress::ONE)
+    })
+    .await?;
+
+    // system tables and registries live at 0x1
+    let mut donor_voice_accounts = HashSet::new();
+    let mut originating_table = None;
+    if let Some(framework) = framework_state.account_state(AccountAddress::ONE) {
+        donor_voice_accounts = donor_voice_registry(&framework)?;
+        originating_table = framework
+            .get_move_resource::<OriginatingAddressResource>()?
+            .map(|o| o.address_map);
+    }
+
+    let mut state = filtered_state_values_from_snapshot(manifest_path, |key| match key.inner() {
+        StateKeyInner::AccessPath(p) => filter.wants_address(&p.address),
+        StateKeyInner::TableItem { handle, .. } => originating_table.as_ref() == Some(handle),
+        _ => false,
+    })
+    .await?;
+
+    let mut export = SnapshotExport {
+        version: state.version,
+        ..Default::default()
+    };
+    if let Some(handle) = &originating_table {
+        export.originating_address = address_table(&state.table_items(handle))?;
+    }
+
+    let addresses: Vec<_> = state.accounts.keys().cloned().collect();
+    for address in addresses {
+        let account =
+            AccountState::new(address, state.accounts.remove(&address).unwrap_or_default());
+        export.accounts.push(
+            account_record(&account, address, filter, &donor_voice_accounts)
+                .with_context(|| format!("cannot decode account {address}"))?,
+        );
+    }
+    Ok(export)
+}
+
+/// Export to a json file, by default next to the manifest
+pub async fn snapshot_to_json(
+    manifest_path: &Path,
+    out_path: Option<PathBuf>,
+    filter: &SnapshotFilter,
+) -> Result<PathBuf> {
+    let export = export_snapshot_v7(manifest_path, filter).await?;
+    let out = out_path.unwrap_or_else(|| {
+        manifest_path
+            .parent()
+            .unwrap_or(Path::new("."))
+            .join("snapshot.json")
+    });
+    fs::write(&out, serde_json::to_string_pretty(&export)?)?;
+    println!(
+        "exported {} accounts at version {} to {}",
+        export.accounts.len(),
+        export.version,
+        out.display()
+    );
+    Ok(out)
+}
+
+#[test]
+fn decode_account_record() {
+    use diem_types::event::{EventHandle, EventKey};
+    use move_core_types::move_resource::MoveResource;
+
+    let address = AccountAddress::from_hex_literal("0x1234").unwrap();
+    let handle = || EventHandle::new(EventKey::new(0, address), 0);
+    let mut data = BTreeMap::new();
+    data.insert(
+        SlowWalletResource::resource_path(),
+        bcs::to_bytes(&SlowWalletResource {
+            unlocked: 10,
+            transferred: 5,
+        })
+        .unwrap(),
+    );
+    data.insert(
+        LibraCoinStoreResource::resource_path(),
+        bcs::to_bytes(&LibraCoinStoreResource::new(100, handle(), handle())).unwrap(),
+    );
+    data.insert(
+        ReceivedVouchesResource::resource_path(),
+        bcs::to_bytes(&ReceivedVouchesResource {
+            incoming_vouches: vec![AccountAddress::ONE],
+            epoch_vouched: vec![3],
+        })
+        .unwrap(),
+    );
+    let state = AccountState::new(address, data);
+    let dv = HashSet::from([address]);
+
+    let all = account_record(&state, address, &SnapshotFilter::default(), &dv).unwrap();
+    assert_eq!(all.balance, Some(100));
+    assert_eq!(all.slow_wallet.unwrap().unlocked, 10);
+    assert_eq!(all.received_vouches.unwrap().epoch_vouched, vec![3]);
+    assert_eq!(all.donor_voice, Some(true));
+    assert!(all.jail.is_none());
+
+    let filter = SnapshotFilter {
+        resources: vec![ResourceKind::Balance],
+        addresses: vec![],
+    };
+    let some = account_record(&state, address, &filter, &dv).unwrap();
+    assert_eq!(some.balance, Some(100));
+    assert!(some.slow_wallet.is_none());
+    assert!(some.donor_voice.is_none());
+
+    let table = address_table(&[(
+        bcs::to_bytes(&address).unwrap(),
+        bcs::to_bytes(&AccountAddress::ONE).unwrap(),
+    )])
+    .unwrap();
+    assert_eq!(table[&address], AccountAddress::ONE);
+}
+
+#[tokio::test]
+async fn export_one_account_from_fixture() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures/v7/state_epoch_116_ver_38180075.05af/state.manifest");
+    let filter = SnapshotFilter {
+        resources: vec![],
+        addresses: vec![AccountAddress::ONE],
+    };
+    let export = export_snapshot_v7(&manifest, &filter).await.unwrap();
+    assert_eq!(export.version, 38180075);
+    assert_eq!(export.accounts.len(), 1);
+    assert_eq!(export.accounts[0].address, AccountAddress::ONE);
+}
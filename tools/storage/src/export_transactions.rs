@@ -0,0 +1,430 @@
+//! Export the transactions of an archive as flat rows, for loading chain
+//! history into a data warehouse without running a node.
+//! NDJSON writes one transaction per line, with its events nested.
+//! Parquet writes a transactions table and an events table per chunk,
+//! joined on version.
+
+use crate::read_tx_chunk::{load_chunk, load_tx_chunk_manifest, TransactionArchiveChunk};
+use anyhow::{Context, Result};
+use diem_types::{
+    contract_event::ContractEvent,
+    transaction::{Transaction, TransactionInfo, TransactionPayload},
+};
+use parquet::{
+    basic::Compression,
+    data_type::{BoolType, ByteArray, ByteArrayType, Int32Type, Int64Type},
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::parser::parse_message_type,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    Ndjson,
+    Parquet,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TransactionRow {
+    pub version: u64,
+    /// user, block_metadata, genesis or state_checkpoint
+    pub tx_type: String,
+    /// the signer of a user transaction, or the proposer of a block
+    pub sender: Option<String>,
+    pub sequence_number: Option<u64>,
+    /// e.g. 0x1::ol_account::transfer
+    pub entry_function: Option<String>,
+    pub type_args: Vec<String>,
+    /// bcs encoded arguments, hex
+    pub args: Vec<String>,
+    pub max_gas_amount: Option<u64>,
+    pub gas_unit_price: Option<u64>,
+    pub expiration_timestamp_secs: Option<u64>,
+    /// from the last block metadata before this transaction, none if it
+    /// is in an earlier chunk
+    pub block_timestamp_usecs: Option<u64>,
+    pub gas_used: u64,
+    pub success: bool,
+    pub vm_status: String,
+    pub events: Vec<EventRow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventRow {
+    pub type_tag: String,
+    /// bcs encoded event, hex
+    pub data: String,
+}
+
+impl From<&ContractEvent> for EventRow {
+    fn from(e: &ContractEvent) -> Self {
+        Self {
+            type_tag: e.type_tag().to_string(),
+            data: hex::encode(e.event_data()),
+        }
+    }
+}
+
+/// Flatten one transaction of a chunk
+pub fn transaction_row(
+    version: u64,
+    txn: &Transaction,
+    info: &TransactionInfo,
+    events: &[ContractEvent],
+    block_timestamp_usecs: Option<u64>,
+) -> TransactionRow {
+    let mut row = TransactionRow {
+        version,
+        tx_type: String::new(),
+        sender: None,
+        sequence_number: None,
+        entry_function: None,
+        type_args: vec![],
+        args: vec![],
+        max_gas_amount: None,
+        gas_unit_price: None,
+        expiration_timestamp_secs: None,
+        block_timestamp_usecs,
+        gas_used: info.gas_used(),
+        success: info.status().is_success(),
+        vm_status: format!("{:?}", info.status()),
+        events: events.iter().map(EventRow::from).collect(),
+    };
+
+    match txn {
+        Transaction::UserTransaction(t) => {
+            row.tx_type = "user".to_owned();
+            row.sender = Some(t.sender().to_hex_literal());
+            row.sequence_number = Some(t.sequence_number());
+            row.max_gas_amount = Some(t.max_gas_amount());
+            row.gas_unit_price = Some(t.gas_unit_price());
+            row.expiration_timestamp_secs = Some(t.expiration_timestamp_secs());
+            if let TransactionPayload::EntryFunction(f) = t.payload() {
+                row.entry_function = Some(format!(
+                    "{}::{}::{}",
+                    f.module().address().to_hex_literal(),
+                    f.module().name(),
+                    f.function()
+                ));
+                row.type_args = f.ty_args().iter().map(|t| t.to_string()).collect();
+                row.args = f.args().iter().map(hex::encode).collect();
+            }
+        }
+        Transaction::BlockMetadata(b) => {
+            row.tx_type = "block_metadata".to_owned();
+            row.sender = Some(b.proposer().to_hex_literal());
+            row.block_timestamp_usecs = Some(b.timestamp_usecs());
+        }
+        Transaction::GenesisTransaction(_) => row.tx_type = "genesis".to_owned(),
+        Transaction::StateCheckpoint(_) => row.tx_type = "state_checkpoint".to_owned(),
+    }
+    row
+}
+
+/// The rows of a chunk within the version range, inclusive
+pub fn chunk_rows(chunk: &TransactionArchiveChunk, start: u64, end: u64) -> Vec<TransactionRow> {
+    let mut block_timestamp = None;
+    let mut rows = vec![];
+    for (i, txn) in chunk.txns.iter().enumerate() {
+        let version = chunk.manifest.first_version + i as u64;
+        let row = transaction_row(
+            version,
+            txn,
+            &chunk.txn_infos[i],
+            &chunk.event_vecs[i],
+            block_timestamp,
+        );
+        block_timestamp = row.block_timestamp_usecs;
+        if (start..=end).contains(&version) {
+            rows.push(row);
+        }
+    }
+    rows
+}
+
+/// Export every chunk of a transaction backup overlapping the range.
+/// Returns the files written.
+pub async fn export_transactions(
+    manifest_path: &Path,
+    out_dir: &Path,
+    format: ExportFormat,
+    start_version: Option<u64>,
+    end_version: Option<u64>,
+) -> Result<Vec<PathBuf>> {
+    let manifest = load_tx_chunk_manifest(manifest_path)?;
+    let archive_path = manifest_path
+        .parent()
+        .context("manifest is not in a backup folder")?;
+    let start = start_version.unwrap_or(manifest.first_version);
+    let end = end_version.unwrap_or(manifest.last_version);
+    fs::create_dir_all(out_dir)?;
+
+    let mut written = vec![];
+    for chunk_manifest in manifest.chunks {
+        if chunk_manifest.last_version < start || chunk_manifest.first_version > end {
+            continue;
+        }
+        let name = format!(
+            "{}-{}",
+            chunk_manifest.first_version, chunk_manifest.last_version
+        );
+        let chunk = load_chunk(archive_path, chunk_manifest).await?;
+        let rows = chunk_rows(&chunk, start, end);
+
+        match format {
+            ExportFormat::Ndjson => {
+                let path = out_dir.join(format!("{name}.ndjson"));
+                write_ndjson(&rows, &path)?;
+                written.push(path);
+            }
+            ExportFormat::Parquet => {
+                let txs = out_dir.join(format!("transactions_{name}.parquet"));
+                let events = out_dir.join(format!("events_{name}.parquet"));
+                write_parquet(&rows, &txs, &events)?;
+                written.push(txs);
+                written.push(events);
+            }
+        }
+        println!("exported {} transactions from chunk {name}", rows.len());
+    }
+    Ok(written)
+}
+
+pub fn write_ndjson(rows: &[TransactionRow], path: &Path) -> Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for row in rows {
+        serde_json::to_writer(&mut file, row)?;
+        file.write_all(b"\n")?;
+    }
+    file.flush()?;
+    Ok(())
+}
+
+const TRANSACTIONS_SCHEMA: &str = "
+message transaction {
+    required int64 version;
+    required binary tx_type (UTF8);
+    optional binary sender (UTF8);
+    optional int64 sequence_number;
+    optional binary entry_function (UTF8);
+    required binary type_args (UTF8);
+    required binary args (UTF8);
+    optional int64 max_gas_amount;
+    optional int64 gas_unit_price;
+    optional int64 expiration_timestamp_secs;
+    optional int64 block_timestamp_usecs;
+    required int64 gas_used;
+    required boolean success;
+    required binary vm_status (UTF8);
+    required int32 event_count;
+}";
+
+const EVENTS_SCHEMA: &str = "
+message event {
+    required int64 version;
+    required int32 event_index;
+    required binary type_tag (UTF8);
+    required binary data (UTF8);
+}";
+
+/// A column of a table, in schema order
+enum Column {
+    Int64(Vec<i64>),
+    OptInt64(Vec<Option<i64>>),
+    Int32(Vec<i32>),
+    Bool(Vec<bool>),
+    Str(Vec<String>),
+    OptStr(Vec<Option<String>>),
+}
+
+/// definition levels, and the values which are set
+fn optional<T: Clone>(values: &[Option<T>]) -> (Vec<T>, Vec<i16>) {
+    let levels = values.iter().map(|v| v.is_some() as i16).collect();
+    (values.iter().flatten().cloned().collect(), levels)
+}
+
+fn byte_arrays(values: Vec<String>) -> Vec<ByteArray> {
+    values
+        .into_iter()
+        .map(|s| ByteArray::from(s.into_bytes()))
+        .collect()
+}
+
+fn write_table(path: &Path, schema: &str, columns: Vec<Column>) -> Result<()> {
+    let schema = Arc::new(parse_message_type(schema)?);
+    let props = Arc::new(
+        WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build(),
+    );
+    let mut writer = SerializedFileWriter::new(File::create(path)?, schema, props)?;
+    let mut row_group = writer.next_row_group()?;
+    for column in columns {
+        let mut col = row_group
+            .next_column()?
+            .context("more columns than the schema")?;
+        match column {
+            Column::Int64(v) => {
+                col.typed::<Int64Type>().write_batch(&v, None, None)?;
+            }
+            Column::OptInt64(v) => {
+                let (values, levels) = optional(&v);
+                col.typed::<Int64Type>()
+                    .write_batch(&values, Some(&levels), None)?;
+            }
+            Column::Int32(v) => {
+                col.typed::<Int32Type>().write_batch(&v, None, None)?;
+            }
+            Column::Bool(v) => {
+                col.typed::<BoolType>().write_batch(&v, None, None)?;
+            }
+            Column::Str(v) => {
+                col.typed::<ByteArrayType>()
+                    .write_batch(&byte_arrays(v), None, None)?;
+            }
+            Column::OptStr(v) => {
+                let (values, levels) = optional(&v);
+                col.typed::<ByteArrayType>().write_batch(
+                    &byte_arrays(values),
+                    Some(&levels),
+                    None,
+                )?;
+            }
+        }
+        col.close()?;
+    }
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+pub fn write_parquet(rows: &[TransactionRow], transactions: &Path, events: &Path) -> Result<()> {
+    let opt_i64 = |f: fn(&TransactionRow) -> Option<u64>| {
+        Column::OptInt64(rows.iter().map(|r| f(r).map(|n| n as i64)).collect())
+    };
+    write_table(
+        transactions,
+        TRANSACTIONS_SCHEMA,
+        vec![
+            Column::Int64(rows.iter().map(|r| r.version as i64).collect()),
+            Column::Str(rows.iter().map(|r| r.tx_type.clone()).collect()),
+            Column::OptStr(rows.iter().map(|r| r.sender.clone()).collect()),
+            opt_i64(|r| r.sequence_number),
+            Column::OptStr(rows.iter().map(|r| r.entry_function.clone()).collect()),
+            Column::Str(
+                rows.iter()
+                    .map(|r| serde_json::to_string(&r.type_args))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Column::Str(
+                rows.iter()
+                    .map(|r| serde_json::to_string(&r.args))
+                    .collect::<Result<_, _>>()?,
+            ),
+            opt_i64(|r| r.max_gas_amount),
+            opt_i64(|r| r.gas_unit_price),
+            opt_i64(|r| r.expiration_timestamp_secs),
+            opt_i64(|r| r.block_timestamp_usecs),
+            Column::Int64(rows.iter().map(|r| r.gas_used as i64).collect()),
+            Column::Bool(rows.iter().map(|r| r.success).collect()),
+            Column::Str(rows.iter().map(|r| r.vm_status.clone()).collect()),
+            Column::Int32(rows.iter().map(|r| r.events.len() as i32).collect()),
+        ],
+    )?;
+
+    let events_of = || {
+        rows.iter()
+            .flat_map(|r| r.events.iter().enumerate().map(move |(i, e)| (r, i, e)))
+    };
+    write_table(
+        events,
+        EVENTS_SCHEMA,
+        vec![
+            Column::Int64(events_of().map(|(r, _, _)| r.version as i64).collect()),
+            Column::Int32(events_of().map(|(_, i, _)| i as i32).collect()),
+            Column::Str(events_of().map(|(_, _, e)| e.type_tag.clone()).collect()),
+            Column::Str(events_of().map(|(_, _, e)| e.data.clone()).collect()),
+        ],
+    )
+}
+
+#[test]
+fn write_rows() {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let user = TransactionRow {
+        version: 10,
+        tx_type: "user".to_owned(),
+        sender: Some("0x1234".to_owned()),
+        sequence_number: Some(3),
+        entry_function: Some("0x1::ol_account::transfer".to_owned()),
+        type_args: vec![],
+        args: vec!["01".to_owned(), "e803000000000000".to_owned()],
+        max_gas_amount: Some(1000),
+        gas_unit_price: Some(100),
+        expiration_timestamp_secs: Some(1_700_000_000),
+        block_timestamp_usecs: Some(1_699_999_999_000_000),
+        gas_used: 7,
+        success: true,
+        vm_status: "Success".to_owned(),
+        events: vec![
+            EventRow {
+                type_tag: "0x1::coin::WithdrawEvent".to_owned(),
+                data: "e803000000000000".to_owned(),
+            },
+            EventRow {
+                type_tag: "0x1::coin::DepositEvent".to_owned(),
+                data: "e803000000000000".to_owned(),
+            },
+        ],
+    };
+    let checkpoint = TransactionRow {
+        version: 11,
+        tx_type: "state_checkpoint".to_owned(),
+        sender: None,
+        sequence_number: None,
+        entry_function: None,
+        type_args: vec![],
+        args: vec![],
+        max_gas_amount: None,
+        gas_unit_price: None,
+        expiration_timestamp_secs: None,
+        block_timestamp_usecs: None,
+        gas_used: 0,
+        success: true,
+        vm_status: "Success".to_owned(),
+        events: vec![],
+    };
+    let rows = vec![user, checkpoint];
+
+    let dir = diem_temppath::TempPath::new();
+    dir.create_as_dir().unwrap();
+
+    let ndjson = dir.path().join("rows.ndjson");
+    write_ndjson(&rows, &ndjson).unwrap();
+    let read: Vec<TransactionRow> = fs::read_to_string(&ndjson)
+        .unwrap()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+    assert_eq!(read, rows);
+
+    let txs = dir.path().join("transactions.parquet");
+    let events = dir.path().join("events.parquet");
+    write_parquet(&rows, &txs, &events).unwrap();
+    let count = |p: &Path| {
+        SerializedFileReader::new(File::open(p).unwrap())
+            .unwrap()
+            .metadata()
+            .file_metadata()
+            .num_rows()
+    };
+    assert_eq!(count(&txs), 2);
+    assert_eq!(count(&events), 2);
+}
@@ -1,8 +1,20 @@
+pub mod archive_producer;
+pub mod archive_source;
 pub mod bootstrap;
+pub mod db_util;
 pub mod dbtool_init;
 pub mod download_bundle;
+pub mod downloader;
+pub mod export_snapshot;
+pub mod export_transactions;
+pub mod maintain;
 pub mod parse_folder_names;
+pub mod point_in_time;
 pub mod read_snapshot;
+pub mod read_tx_chunk;
 pub mod restore;
 pub mod restore_bundle;
+pub mod serve;
+pub mod state_diff;
 pub mod storage_cli;
+pub mod verify_bundle;
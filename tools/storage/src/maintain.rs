@@ -0,0 +1,372 @@
+//! Maintenance of an offline DB: report the size of each column family,
+//! prune old ledger history, compact, and check the DB still serves the
+//! latest state. The node must be stopped, RocksDB only allows one process
+//! to open a DB.
+
+use crate::db_util::open_read_only;
+use anyhow::{bail, Context, Result};
+use diem_config::config::{
+    RocksdbConfigs, BUFFERED_STATE_TARGET_ITEMS, DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    NO_OP_STORAGE_PRUNER_CONFIG,
+};
+use diem_db::DiemDB;
+use diem_storage_interface::DbReader;
+use indicatif::HumanBytes;
+use rocksdb::{Options, DB};
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// epoch ending ledger infos are read in pages of this size
+const EPOCH_PAGE: u64 = 100;
+
+#[derive(Debug, Clone)]
+pub struct MaintainOpts {
+    pub db_path: PathBuf,
+    /// prune the ledger history older than this many versions
+    pub prune_keep_versions: Option<u64>,
+    pub compact: bool,
+    /// how long to wait for the pruner to catch up
+    pub prune_timeout: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnFamilySize {
+    /// the RocksDB directory, e.g. ledger_db
+    pub db: String,
+    pub name: String,
+    pub sst_bytes: u64,
+    pub estimated_keys: u64,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SizeReport {
+    pub families: Vec<ColumnFamilySize>,
+}
+
+impl SizeReport {
+    pub fn total_bytes(&self) -> u64 {
+        self.families.iter().map(|f| f.sst_bytes).sum()
+    }
+}
+
+impl fmt::Display for SizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for cf in &self.families {
+            writeln!(
+                f,
+                "{:>12}  {:>14} keys  {}/{}",
+                HumanBytes(cf.sst_bytes).to_string(),
+                cf.estimated_keys,
+                cf.db,
+                cf.name
+            )?;
+        }
+        write!(
+            f,
+            "{:>12}  total",
+            HumanBytes(self.total_bytes()).to_string()
+        )
+    }
+}
+
+/// What a healthy DB serves after maintenance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DbHealth {
+    pub latest_version: u64,
+    pub epoch: u64,
+    /// the first transaction which was not pruned
+    pub first_version: u64,
+    pub state_version: u64,
+    pub state_items: usize,
+    /// readable epoch ending ledger infos, counted back from the latest
+    pub epoch_endings: u64,
+}
+
+impl fmt::Display for DbHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "versions {}..={}, epoch {}, {} epoch endings, {} state items at version {}",
+            self.first_version,
+            self.latest_version,
+            self.epoch,
+            self.epoch_endings,
+            self.state_items,
+            self.state_version
+        )
+    }
+}
+
+/// The RocksDB directories of a DB, e.g. ledger_db and state_merkle_db
+pub fn rocksdb_dirs(db_path: &Path) -> Result<Vec<PathBuf>> {
+    if !db_path.exists() {
+        bail!("DB path does not exist: {}", db_path.display());
+    }
+    // every RocksDB directory has a CURRENT file
+    let mut dirs = vec![];
+    if db_path.join("CURRENT").exists() {
+        dirs.push(db_path.to_path_buf());
+    }
+    for entry in std::fs::read_dir(db_path)? {
+        let path = entry?.path();
+        if path.is_dir() && path.join("CURRENT").exists() {
+            dirs.push(path);
+        }
+    }
+    dirs.sort();
+    if dirs.is_empty() {
+        bail!("no RocksDB found in {}", db_path.display());
+    }
+    Ok(dirs)
+}
+
+fn dir_name(dir: &Path) -> String {
+    dir.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn column_families(dir: &Path) -> Result<Vec<String>> {
+    DB::list_cf(&Options::default(), dir)
+        .with_context(|| format!("cannot list column families of {}", dir.display()))
+}
+
+/// The size on disk of each column family
+pub fn size_report(db_path: &Path) -> Result<SizeReport> {
+    let mut report = SizeReport::default();
+    for dir in rocksdb_dirs(db_path)? {
+        let names = column_families(&dir)?;
+        let db = DB::open_cf_for_read_only(&Options::default(), &dir, &names, false)
+            .with_context(|| format!("cannot open {}, is the node stopped?", dir.display()))?;
+        for name in names {
+            let cf = db
+                .cf_handle(&name)
+                .with_context(|| format!("no column family {name}"))?;
+            report.families.push(ColumnFamilySize {
+                db: dir_name(&dir),
+                sst_bytes: db
+                    .property_int_value_cf(cf, "rocksdb.total-sst-files-size")?
+                    .unwrap_or(0),
+                estimated_keys: db
+                    .property_int_value_cf(cf, "rocksdb.estimate-num-keys")?
+                    .unwrap_or(0),
+                name,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Flush and compact every column family, so pruned data leaves the disk
+pub fn compact(db_path: &Path) -> Result<()> {
+    for dir in rocksdb_dirs(db_path)? {
+        let names = column_families(&dir)?;
+        // the DB schemas use the default comparator, so plain options are enough
+        let db = DB::open_cf(&Options::default(), &dir, &names)
+            .with_context(|| format!("cannot open {}, is the node stopped?", dir.display()))?;
+        for name in &names {
+            let cf = db
+                .cf_handle(name)
+                .with_context(|| format!("no column family {name}"))?;
+            println!("compacting {}/{name}", dir_name(&dir));
+            db.flush_cf(cf)?;
+            db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+        }
+    }
+    Ok(())
+}
+
+/// Prune the ledger history older than `keep_versions`. The pruner starts
+/// when the DB is opened with pruning enabled, this waits until it reaches
+/// the target. Epoch ending ledger infos are not pruned. Returns the first
+/// version left.
+pub fn prune_ledger(db_path: &Path, keep_versions: u64, timeout: Duration) -> Result<u64> {
+    let mut pruner_config = NO_OP_STORAGE_PRUNER_CONFIG;
+    pruner_config.ledger_pruner_config.enable = true;
+    pruner_config.ledger_pruner_config.prune_window = keep_versions;
+
+    let db = DiemDB::open(
+        db_path,
+        false, /* readonly */
+        pruner_config,
+        RocksdbConfigs::default(),
+        false, /* enable_indexer */
+        BUFFERED_STATE_TARGET_ITEMS,
+        DEFAULT_MAX_NUM_NODES_PER_LRU_CACHE_SHARD,
+    )
+    .context("Failed to open database, is the node stopped?")?;
+
+    let target = db.get_latest_version()?.saturating_sub(keep_versions);
+    let deadline = Instant::now() + timeout;
+    loop {
+        let first = db.get_first_txn_version()?.unwrap_or(0);
+        if first >= target {
+            println!("pruned the ledger history before version {first}");
+            // dropping the DB waits for the pruner threads
+            drop(db);
+            return Ok(first);
+        }
+        if Instant::now() > deadline {
+            bail!("the pruner reached version {first} of {target} before the timeout");
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+/// The epochs with an ending ledger info, counted back from the latest
+fn count_epoch_endings(reader: &dyn DbReader, latest_epoch: u64) -> u64 {
+    let mut count = 0;
+    let mut end = latest_epoch;
+    while end > 0 {
+        let start = end.saturating_sub(EPOCH_PAGE);
+        match reader.get_epoch_ending_ledger_infos(start, end) {
+            Ok(proof) if proof.ledger_info_with_sigs.len() as u64 == end - start => {
+                count += end - start;
+                end = start;
+            }
+            _ => {
+                // a restored DB starts at a later epoch, find where
+                while end > start && reader.get_epoch_ending_ledger_infos(end - 1, end).is_ok() {
+                    count += 1;
+                    end -= 1;
+                }
+                break;
+            }
+        }
+    }
+    count
+}
+
+/// Open the DB read-only and read the latest ledger info, epoch endings
+/// and state
+pub fn check_db(db_path: &Path) -> Result<DbHealth> {
+    let reader = open_read_only(db_path)?.reader;
+    let latest_version = reader
+        .get_latest_version()
+        .context("the DB has no transactions")?;
+    let epoch = reader.get_latest_ledger_info()?.ledger_info().epoch();
+    let first_version = reader.get_first_txn_version()?.unwrap_or(0);
+    let state_version = reader
+        .get_latest_state_checkpoint_version()?
+        .context("the DB has no state")?;
+    let state_items = reader.get_state_item_count(state_version)?;
+    if state_items > 0 {
+        let chunk = reader.get_state_value_chunk_with_proof(state_version, 0, 1)?;
+        if chunk.raw_values.is_empty() {
+            bail!("cannot read the state at version {state_version}");
+        }
+    }
+    Ok(DbHealth {
+        latest_version,
+        epoch,
+        first_version,
+        state_version,
+        state_items,
+        epoch_endings: count_epoch_endings(reader.as_ref(), epoch),
+    })
+}
+
+/// Report, prune, compact, then check the DB against its state before
+pub fn maintain(opts: &MaintainOpts) -> Result<()> {
+    let before = size_report(&opts.db_path)?;
+    println!("{before}\n");
+    let health = check_db(&opts.db_path)?;
+    println!("before: {health}");
+
+    if let Some(keep) = opts.prune_keep_versions {
+        prune_ledger(&opts.db_path, keep, opts.prune_timeout)?;
+    }
+    if opts.compact {
+        compact(&opts.db_path)?;
+    }
+    if opts.prune_keep_versions.is_none() && !opts.compact {
+        return Ok(());
+    }
+
+    let after = check_db(&opts.db_path)?;
+    println!("after: {after}");
+    if after.latest_version != health.latest_version || after.state_items != health.state_items {
+        bail!("the latest state changed during maintenance");
+    }
+    if after.epoch_endings < health.epoch_endings {
+        bail!(
+            "epoch endings went from {} to {}",
+            health.epoch_endings,
+            after.epoch_endings
+        );
+    }
+
+    let report = size_report(&opts.db_path)?;
+    println!("\n{report}");
+    println!(
+        "reclaimed {}",
+        HumanBytes(before.total_bytes().saturating_sub(report.total_bytes()))
+    );
+    Ok(())
+}
+
+#[test]
+fn report_and_compact_rocksdb() {
+    let dir = diem_temppath::TempPath::new();
+    dir.create_as_dir().unwrap();
+    let ledger = dir.path().join("ledger_db");
+    std::fs::create_dir_all(&ledger).unwrap();
+    std::fs::create_dir_all(dir.path().join("not_a_db")).unwrap();
+    {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let db = DB::open_cf(&opts, &ledger, ["transaction"]).unwrap();
+        let cf = db.cf_handle("transaction").unwrap();
+        for i in 0..1000u32 {
+            db.put_cf(cf, i.to_be_bytes(), [7u8; 100]).unwrap();
+        }
+    }
+
+    assert_eq!(rocksdb_dirs(dir.path()).unwrap(), vec![ledger.clone()]);
+    compact(dir.path()).unwrap();
+    let report = size_report(dir.path()).unwrap();
+    let names: Vec<_> = report.families.iter().map(|f| f.name.as_str()).collect();
+    assert_eq!(names, vec!["default", "transaction"]);
+    let transaction = &report.families[1];
+    assert_eq!(transaction.db, "ledger_db");
+    assert!(transaction.sst_bytes > 0);
+    assert!(transaction.estimated_keys > 0);
+    assert_eq!(report.total_bytes(), transaction.sst_bytes);
+
+    assert!(rocksdb_dirs(&dir.path().join("not_a_db")).is_err());
+}
+
+#[tokio::test]
+async fn prune_fixture_db() {
+    // transactions from 38100001 are restored, up to 38180100
+    let (_temp, db) = crate::point_in_time::restore_fixture_db(38180100).await;
+    let before = check_db(&db).unwrap();
+    assert_eq!(before.latest_version, 38180100);
+    assert!(before.first_version <= 38100001);
+
+    maintain(&MaintainOpts {
+        db_path: db.clone(),
+        prune_keep_versions: Some(50),
+        compact: true,
+        prune_timeout: Duration::from_secs(120),
+    })
+    .unwrap();
+
+    let after = check_db(&db).unwrap();
+    assert_eq!(after.latest_version, before.latest_version);
+    assert_eq!(after.state_items, before.state_items);
+    assert!(after.first_version >= 38180050);
+    // the versions below the window are gone
+    let reader = open_read_only(&db).unwrap().reader;
+    assert!(reader
+        .get_transaction_by_version(38100001, after.latest_version, false)
+        .is_err());
+    assert!(reader
+        .get_transaction_by_version(after.latest_version, after.latest_version, false)
+        .is_ok());
+}
@@ -0,0 +1,294 @@
+//! Restore to any version, not only to an epoch boundary: restore the
+//! closest snapshot at or below the target, then replay the transactions
+//! after the snapshot up to exactly the target.
+
+use crate::{
+    dbtool_init::{get_backup_storage, global_restore_opts},
+    read_snapshot::load_snapshot_manifest,
+    restore::maybe_decompress_gz_files,
+    restore_bundle::RestoreBundle,
+};
+use anyhow::{bail, Context, Result};
+use diem_backup_cli::backup_types::{
+    epoch_ending::{
+        manifest::EpochEndingBackup,
+        restore::{EpochEndingRestoreController, EpochEndingRestoreOpt},
+    },
+    state_snapshot::restore::{StateSnapshotRestoreController, StateSnapshotRestoreOpt},
+    transaction::{
+        manifest::TransactionBackup,
+        restore::{TransactionRestoreController, TransactionRestoreOpt},
+    },
+};
+use diem_db::state_restore::StateSnapshotRestoreMode;
+use diem_executor_types::VerifyExecutionMode;
+use diem_logger::info;
+use diem_types::waypoint::Waypoint;
+use glob::glob;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// The manifests of a bundle needed to restore to `target_version`
+#[derive(Debug, Clone)]
+pub struct VersionBundle {
+    pub restore_bundle_dir: PathBuf,
+    pub target_version: u64,
+    /// the closest snapshot at or below the target
+    pub snapshot_version: u64,
+    pub snapshot_manifest: PathBuf,
+    /// from the epoch ending at the snapshot, ordered by epoch
+    pub epoch_manifests: Vec<PathBuf>,
+    /// the epoch ending waypoints from the snapshot to the target
+    pub waypoints: Vec<Waypoint>,
+    /// contiguous from the snapshot version to the target
+    pub transaction_manifests: Vec<PathBuf>,
+}
+
+impl VersionBundle {
+    /// Find the manifests in a bundle, e.g. from `download_version_bundle`
+    pub fn load(restore_bundle_dir: PathBuf, target_version: u64) -> Result<Self> {
+        let manifests = |pattern: &str| -> Result<Vec<PathBuf>> {
+            let mut paths = vec![];
+            for p in glob(&format!("{}/{}", restore_bundle_dir.display(), pattern))?.flatten() {
+                let content = fs::read_to_string(&p)?;
+                fs::write(&p, RestoreBundle::update_manifest_paths(&content))?;
+                paths.push(p);
+            }
+            Ok(paths)
+        };
+
+        let mut snapshot = None;
+        for p in manifests("state_epoch*/state.manifest")? {
+            let version = load_snapshot_manifest(&p)?.version;
+            if version <= target_version && !matches!(&snapshot, Some((v, _)) if *v >= version) {
+                snapshot = Some((version, p));
+            }
+        }
+        let (snapshot_version, snapshot_manifest) = snapshot
+            .with_context(|| format!("no state snapshot at or below version {target_version}"))?;
+
+        let mut epochs = vec![];
+        let mut waypoints: Vec<Waypoint> = vec![];
+        for p in manifests("epoch_ending*/epoch_ending.manifest")? {
+            let manifest: EpochEndingBackup = serde_json::from_str(&fs::read_to_string(&p)?)?;
+            let in_range: Vec<_> = manifest
+                .waypoints
+                .iter()
+                .filter(|w| w.version() >= snapshot_version && w.version() <= target_version)
+                .collect();
+            if in_range.is_empty() {
+                continue;
+            }
+            for w in in_range {
+                if !waypoints.iter().any(|t| t.version() == w.version()) {
+                    waypoints.push(*w);
+                }
+            }
+            epochs.push((manifest.first_epoch, p));
+        }
+        if !waypoints.iter().any(|w| w.version() == snapshot_version) {
+            bail!("no epoch ending at the snapshot version {snapshot_version}");
+        }
+        epochs.sort();
+
+        let mut transactions = vec![];
+        for p in manifests("**/transaction.manifest")? {
+            let manifest: TransactionBackup = serde_json::from_str(&fs::read_to_string(&p)?)?;
+            transactions.push((p, manifest.first_version, manifest.last_version));
+        }
+        let transaction_manifests =
+            transaction_chain(&transactions, snapshot_version, target_version)?;
+
+        Ok(Self {
+            restore_bundle_dir,
+            target_version,
+            snapshot_version,
+            snapshot_manifest,
+            epoch_manifests: epochs.into_iter().map(|(_, p)| p).collect(),
+            waypoints,
+            transaction_manifests,
+        })
+    }
+}
+
+/// Pick transaction backups which together cover `from..=to`, given each
+/// manifest's first and last version
+pub fn transaction_chain(
+    manifests: &[(PathBuf, u64, u64)],
+    from: u64,
+    to: u64,
+) -> Result<Vec<PathBuf>> {
+    let mut chain = vec![];
+    let mut next = from;
+    while next <= to {
+        let (path, _, last) = manifests
+            .iter()
+            .filter(|(_, first, last)| *first <= next && next <= *last)
+            .max_by_key(|(_, _, last)| *last)
+            .with_context(|| format!("no transaction backup has version {next}"))?;
+        chain.push(path.clone());
+        next = last + 1;
+    }
+    Ok(chain)
+}
+
+fn handle(path: &Path) -> String {
+    path.to_str().unwrap().to_string()
+}
+
+/// Restore epoch endings, the snapshot, then save and replay transactions
+/// up to the target version
+pub async fn restore_to_version(db_destination: &Path, bundle: &VersionBundle) -> Result<()> {
+    let storage = get_backup_storage(bundle.restore_bundle_dir.clone())?;
+    let global = || {
+        global_restore_opts(
+            db_destination,
+            bundle.waypoints.clone(),
+            bundle.target_version,
+        )
+    };
+
+    for manifest in &bundle.epoch_manifests {
+        EpochEndingRestoreController::new(
+            EpochEndingRestoreOpt {
+                manifest_handle: handle(manifest),
+            },
+            global()?,
+            storage.clone(),
+        )
+        .run(None)
+        .await?;
+    }
+
+    StateSnapshotRestoreController::new(
+        StateSnapshotRestoreOpt {
+            manifest_handle: handle(&bundle.snapshot_manifest),
+            version: bundle.snapshot_version,
+            validate_modules: false,
+            restore_mode: StateSnapshotRestoreMode::Default,
+        },
+        global()?,
+        storage.clone(),
+        None, /* epoch_history */
+    )
+    .run()
+    .await?;
+
+    // transactions up to the snapshot are saved, the rest are executed
+    for manifest in &bundle.transaction_manifests {
+        TransactionRestoreController::new(
+            TransactionRestoreOpt {
+                manifest_handle: handle(manifest),
+                replay_from_version: Some(bundle.snapshot_version + 1),
+                kv_only_replay: None,
+            },
+            global()?,
+            storage.clone(),
+            None, /* epoch_history */
+            VerifyExecutionMode::NoVerify,
+        )
+        .run()
+        .await?;
+    }
+    Ok(())
+}
+
+/// Perform a point-in-time restore from a bundle to a destination DB
+pub async fn version_restore(
+    bundle_path: PathBuf,
+    destination_db: PathBuf,
+    version: u64,
+) -> Result<PathBuf> {
+    if !bundle_path.exists() {
+        bail!("Bundle directory not found: {}", &bundle_path.display());
+    }
+
+    if destination_db.exists() {
+        bail!(
+            "Destination directory already exists and may contain conflicting state: {}",
+            &destination_db.display()
+        );
+    }
+
+    fs::create_dir_all(&destination_db)?;
+
+    let bundle_path =
+        fs::canonicalize(bundle_path).context("Failed to canonicalize bundle path")?;
+    let destination_db =
+        fs::canonicalize(destination_db).context("Failed to canonicalize destination path")?;
+
+    maybe_decompress_gz_files(&bundle_path)
+        .await
+        .context("Failed to decompress gz files")?;
+
+    let bundle = VersionBundle::load(bundle_path, version)?;
+    restore_to_version(&destination_db, &bundle).await?;
+
+    info!(
+        "SUCCESS: restored to version: {}, replayed {} transactions after the snapshot at {}",
+        bundle.target_version,
+        bundle.target_version - bundle.snapshot_version,
+        bundle.snapshot_version
+    );
+
+    Ok(destination_db)
+}
+
+#[test]
+fn chain_transaction_backups() {
+    let manifests = vec![
+        (PathBuf::from("b"), 200, 299),
+        (PathBuf::from("a"), 100, 199),
+        (PathBuf::from("d"), 400, 499),
+    ];
+    assert_eq!(
+        transaction_chain(&manifests, 150, 250).unwrap(),
+        vec![PathBuf::from("a"), PathBuf::from("b")]
+    );
+    assert_eq!(
+        transaction_chain(&manifests, 150, 150).unwrap(),
+        vec![PathBuf::from("a")]
+    );
+    // 300 to 399 is missing
+    assert!(transaction_chain(&manifests, 150, 450).is_err());
+}
+
+/// Restore the v7 fixtures to `target`, for the tests of tools which read a
+/// DB. Keep the temp dir for as long as the DB is used.
+#[cfg(test)]
+pub(crate) async fn restore_fixture_db(target: u64) -> (diem_temppath::TempPath, PathBuf) {
+    let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/v7");
+    let temp = diem_temppath::TempPath::new();
+    temp.create_as_dir().unwrap();
+    fs_extra::dir::copy(&fixtures, temp.path(), &fs_extra::dir::CopyOptions::new()).unwrap();
+    let db = version_restore(temp.path().join("v7"), temp.path().join("db"), target)
+        .await
+        .unwrap();
+    (temp, db)
+}
+
+#[tokio::test]
+async fn restore_fixture_to_version() {
+    // restore from a copy, the manifests are rewritten in place
+    let fixtures = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/v7");
+    let temp = diem_temppath::TempPath::new();
+    temp.create_as_dir().unwrap();
+    fs_extra::dir::copy(&fixtures, temp.path(), &fs_extra::dir::CopyOptions::new()).unwrap();
+    let bundle_dir = temp.path().join("v7");
+    maybe_decompress_gz_files(&bundle_dir).await.unwrap();
+
+    // snapshot at 38180075, transactions 38100001 to 38200000
+    let target = 38180100;
+    let bundle = VersionBundle::load(bundle_dir, target).unwrap();
+    assert_eq!(bundle.snapshot_version, 38180075);
+    assert_eq!(bundle.transaction_manifests.len(), 1);
+    assert!(bundle.waypoints.iter().any(|w| w.version() == 38180075));
+    assert!(VersionBundle::load(bundle.restore_bundle_dir.clone(), 38180074).is_err());
+
+    let db = temp.path().join("db");
+    restore_to_version(&db, &bundle).await.unwrap();
+    let reader = crate::db_util::open_read_only(&db).unwrap().reader;
+    assert_eq!(reader.get_latest_version().unwrap(), target);
+}
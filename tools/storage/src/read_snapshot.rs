@@ -2,7 +2,8 @@
 use anyhow::{anyhow, Context, Error, Result};
 use diem_backup_cli::{
     backup_types::{
-        epoch_ending::manifest::EpochEndingBackup, state_snapshot::manifest::StateSnapshotBackup,
+        epoch_ending::manifest::EpochEndingBackup,
+        state_snapshot::manifest::{StateSnapshotBackup, StateSnapshotChunk},
     },
     storage::{FileHandle, FileHandleRef},
     utils::read_record_bytes::ReadRecordBytes,
@@ -13,12 +14,13 @@ use diem_types::{
     state_store::{
         state_key::{StateKey, StateKeyInner},
         state_value::StateValue,
+        table::TableHandle,
     },
 };
 use libra_backwards_compatibility::legacy_recovery_v6;
 use serde_json::json;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs,
     path::{Path, PathBuf},
 };
@@ -71,6 +73,42 @@ async fn open_for_read(file_handle: &FileHandleRef) -> Result<Box<dyn AsyncRead
     Ok(Box::new(file))
 }
 
+/// Reads the chunks of a snapshot backup one at a time. The records are in
+/// the order of the key hashes.
+pub struct SnapshotChunks {
+    chunks: std::vec::IntoIter<StateSnapshotChunk>,
+    archive_path: PathBuf,
+    pub version: u64,
+}
+
+impl SnapshotChunks {
+    pub fn new(manifest: StateSnapshotBackup, archive_path: &Path) -> Self {
+        Self {
+            version: manifest.version,
+            chunks: manifest.chunks.into_iter(),
+            archive_path: archive_path.to_owned(),
+        }
+    }
+
+    /// from a state.manifest in its backup folder
+    pub fn from_manifest(manifest_path: &Path) -> Result<Self> {
+        let manifest = load_snapshot_manifest(manifest_path)?;
+        let archive_path = manifest_path
+            .parent()
+            .context("manifest is not in a backup folder")?;
+        Ok(Self::new(manifest, archive_path))
+    }
+
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<(StateKey, StateValue)>>> {
+        match self.chunks.next() {
+            Some(chunk) => Ok(Some(
+                read_account_state_chunk(chunk.blobs, &self.archive_path).await?,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
 /// Tokio async parsing of state snapshot into blob
 pub async fn accounts_from_snapshot_backup(
     manifest: StateSnapshotBackup,
@@ -79,10 +117,8 @@ pub async fn accounts_from_snapshot_backup(
     // parse AccountStateBlob from chunks of the archive
     let mut account_states_map: HashMap<AccountAddress, Vec<(StateKey, StateValue)>> =
         HashMap::new();
-    for chunk in manifest.chunks {
-        let blobs: Vec<(StateKey, StateValue)> =
-            read_account_state_chunk(chunk.blobs, archive_path).await?;
-
+    let mut chunks = SnapshotChunks::new(manifest, archive_path);
+    while let Some(blobs) = chunks.next_chunk().await? {
         // Filter out the AccountState blobs
         // and group by address
         let account_states_map_chunk: HashMap<AccountAddress, Vec<(StateKey, StateValue)>> = blobs
@@ -119,6 +155,73 @@ pub async fn accounts_from_snapshot_backup(
     Ok(account_states)
 }
 
+/// The raw resources of each account, and the table items, of a state.
+/// Unlike `AccountState` this keeps the table items, e.g. for aggregators.
+#[derive(Debug, Clone, Default)]
+pub struct StateValues {
+    pub version: u64,
+    /// access path to bytes, per account
+    pub accounts: BTreeMap<AccountAddress, BTreeMap<Vec<u8>, Vec<u8>>>,
+    pub tables: HashMap<(TableHandle, Vec<u8>), Vec<u8>>,
+}
+
+impl StateValues {
+    pub fn insert(&mut self, key: &StateKey, value: &StateValue) {
+        match key.inner() {
+            StateKeyInner::AccessPath(access_path) => {
+                self.accounts
+                    .entry(access_path.address)
+                    .or_default()
+                    .insert(access_path.path.clone(), value.bytes().to_vec());
+            }
+            StateKeyInner::TableItem { handle, key } => {
+                self.tables
+                    .insert((*handle, key.clone()), value.bytes().to_vec());
+            }
+            _ => {}
+        }
+    }
+
+    pub fn account_state(&self, address: AccountAddress) -> Option<AccountState> {
+        self.accounts
+            .get(&address)
+            .map(|data| AccountState::new(address, data.clone()))
+    }
+
+    /// the items of one table
+    pub fn table_items(&self, handle: &TableHandle) -> Vec<(Vec<u8>, Vec<u8>)> {
+        self.tables
+            .iter()
+            .filter(|((h, _), _)| h == handle)
+            .map(|((_, k), v)| (k.clone(), v.clone()))
+            .collect()
+    }
+}
+
+/// Read all chunks of a state snapshot
+pub async fn state_values_from_snapshot(manifest_path: &Path) -> Result<StateValues> {
+    filtered_state_values_from_snapshot(manifest_path, |_| true).await
+}
+
+/// Read the chunks of a state snapshot one at a time, keeping only the
+/// values whose key passes `keep`
+pub async fn filtered_state_values_from_snapshot(
+    manifest_path: &Path,
+    keep: impl Fn(&StateKey) -> bool,
+) -> Result<StateValues> {
+    let mut chunks = SnapshotChunks::from_manifest(manifest_path)?;
+    let mut values = StateValues {
+        version: chunks.version,
+        ..Default::default()
+    };
+    while let Some(chunk) = chunks.next_chunk().await? {
+        for (key, value) in chunk.iter().filter(|(k, _)| keep(k)) {
+            values.insert(key, value);
+        }
+    }
+    Ok(values)
+}
+
 #[test]
 // TODO: adapt for V7.
 
@@ -1,6 +1,6 @@
 use std::path::Path;
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 
 use diem_backup_cli::backup_types::transaction::manifest::TransactionBackup;
 use diem_backup_cli::backup_types::transaction::manifest::TransactionChunk;
@@ -39,10 +39,15 @@ pub async fn load_chunk(
 ) -> Result<TransactionArchiveChunk> {
     let full_handle = archive_path
         .parent()
-        .expect("could not read archive path")
+        .context("could not read archive path")?
         .join(&manifest.transactions);
+    if !full_handle.exists() {
+        bail!(
+            "transaction chunk does not exist: {}",
+            full_handle.display()
+        );
+    }
     let handle_str = full_handle.to_str().unwrap();
-    assert!(full_handle.exists(), "file does not exist");
 
     let mut file = open_for_read(handle_str)
         .await
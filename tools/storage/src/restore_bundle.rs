@@ -176,7 +176,8 @@ impl RestoreBundle {
         );
     }
 
-    fn update_manifest_paths(manifest_content: &str) -> String {
+    /// point the chunk and proof handles of a manifest at the decompressed files
+    pub fn update_manifest_paths(manifest_content: &str) -> String {
         let mut manifest: Value = serde_json::from_str(manifest_content).unwrap();
 
         if let Some(obj) = manifest.as_object_mut() {
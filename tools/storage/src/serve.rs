@@ -0,0 +1,131 @@
+//! Serve the REST API over a restored DB, without starting a node.
+//! Reads (resources, views, transactions, events) are answered from the DB,
+//! so `libra query --url` works against historical archives. There is no
+//! mempool: transaction submissions are refused.
+
+use crate::db_util::open_read_only;
+use anyhow::{anyhow, Context, Result};
+use diem_config::config::NodeConfig;
+use diem_mempool::MempoolClientRequest;
+use diem_types::chain_id::ChainId;
+use futures::{channel::mpsc, StreamExt};
+use std::{net::SocketAddr, path::Path};
+use tokio::runtime::Runtime;
+
+/// Open the DB read-only, and serve the REST API until ctrl-c
+pub async fn serve_db(db_path: &Path, address: SocketAddr, chain_id: ChainId) -> Result<()> {
+    let (runtime, version) = start_api(db_path, address, chain_id)?;
+
+    println!("serving {} at version {version}", db_path.display());
+    println!("query with: libra query --url http://{address} ...");
+    tokio::signal::ctrl_c().await?;
+
+    // the api has its own runtime, which can't be dropped from async code
+    runtime.shutdown_background();
+    Ok(())
+}
+
+/// Start the REST API over the DB, returns the api runtime and the latest
+/// version. Must be called from within a tokio runtime.
+pub fn start_api(db_path: &Path, address: SocketAddr, chain_id: ChainId) -> Result<(Runtime, u64)> {
+    let db_rw = open_read_only(db_path)?;
+    let version = db_rw
+        .reader
+        .get_latest_version()
+        .context("the DB has no transactions, was it restored?")?;
+
+    let (mp_sender, mut mp_requests) = mpsc::channel(1);
+    tokio::spawn(async move {
+        while let Some(request) = mp_requests.next().await {
+            match request {
+                MempoolClientRequest::SubmitTransaction(_, callback) => {
+                    let _ = callback.send(Err(anyhow!("this is a read-only archive server")));
+                }
+                MempoolClientRequest::GetTransactionByHash(_, callback) => {
+                    let _ = callback.send(None);
+                }
+            }
+        }
+    });
+
+    let mut config = NodeConfig::default();
+    config.api.enabled = true;
+    config.api.address = address;
+    let runtime = diem_api::bootstrap(&config, chain_id, db_rw.reader, mp_sender)?;
+    Ok((runtime, version))
+}
+
+#[tokio::test]
+async fn serve_fixture_db() {
+    use diem_crypto::{ed25519::Ed25519PrivateKey, PrivateKey};
+    use diem_types::{
+        account_address::AccountAddress,
+        transaction::{authenticator::AuthenticationKey, EntryFunction, RawTransaction},
+    };
+    use move_core_types::{identifier::Identifier, language_storage::ModuleId};
+
+    let (_temp, db) = crate::point_in_time::restore_fixture_db(38180100).await;
+
+    // grab a free port
+    let address = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let (runtime, version) = start_api(&db, address, ChainId::test()).unwrap();
+    assert_eq!(version, 38180100);
+
+    let client = reqwest::Client::new();
+    let url = format!("http://{address}/v1");
+
+    // the api starts in the background, wait for it
+    let mut index = None;
+    for _ in 0..50 {
+        if let Ok(res) = client.get(&url).send().await {
+            index = Some(res.json::<serde_json::Value>().await.unwrap());
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+    let index = index.expect("api did not start");
+    assert_eq!(index["ledger_version"], "38180100");
+
+    // a well formed signed transaction, which only the mempool can refuse
+    let key = Ed25519PrivateKey::try_from([7u8; 32].as_slice()).unwrap();
+    let sender = AuthenticationKey::ed25519(&key.public_key()).account_address();
+    let payload = EntryFunction::new(
+        ModuleId::new(AccountAddress::ONE, Identifier::new("ol_account").unwrap()),
+        Identifier::new("transfer").unwrap(),
+        vec![],
+        vec![
+            bcs::to_bytes(&AccountAddress::ONE).unwrap(),
+            bcs::to_bytes(&1u64).unwrap(),
+        ],
+    );
+    let txn = RawTransaction::new_entry_function(
+        sender,
+        0,
+        payload,
+        1_000,
+        100,
+        u64::MAX,
+        ChainId::test(),
+    )
+    .sign(&key, key.public_key())
+    .unwrap()
+    .into_inner();
+
+    let res = client
+        .post(format!("{url}/transactions"))
+        .header(
+            reqwest::header::CONTENT_TYPE,
+            "application/x.diem.signed_transaction+bcs",
+        )
+        .body(bcs::to_bytes(&txn).unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert!(!res.status().is_success());
+    assert!(res.text().await.unwrap().contains("read-only archive"));
+
+    runtime.shutdown_background();
+}
@@ -0,0 +1,684 @@
+//! Diff the state at two DB versions or snapshots, per account and per
+//! resource, e.g. to audit an upgrade or a rescue.
+//! Both states are streamed in the order of the key hashes, so only the
+//! changed accounts are held in memory.
+
+use crate::{
+    db_util::{open_read_only, DbStateChunks},
+    export_snapshot::{account_record, donor_voice_registry, AccountRecord, SnapshotFilter},
+    read_snapshot::{SnapshotChunks, StateValues},
+};
+use anyhow::{bail, Context, Result};
+use diem_crypto::{hash::CryptoHash, HashValue};
+use diem_types::{
+    access_path::{self, AccessPath},
+    account_address::AccountAddress,
+    account_view::AccountView,
+    state_store::{
+        state_key::{StateKey, StateKeyInner},
+        state_value::StateValue,
+    },
+};
+use libra_types::move_resource::{
+    coin_info::GasCoinInfoResource, libra_coin::LibraCoinStoreResource,
+};
+use move_core_types::move_resource::MoveResource;
+use serde::Serialize;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+/// Where to read a state from
+#[derive(Debug, Clone)]
+pub enum StateSource {
+    /// a state.manifest of a snapshot backup
+    Snapshot(PathBuf),
+    /// a DB, at a state checkpoint version, by default the latest
+    Db { path: PathBuf, version: Option<u64> },
+    /// a state already in memory
+    Values(StateValues),
+}
+
+impl StateSource {
+    /// from the cli args, the manifest or else the db
+    pub fn new(
+        manifest: Option<PathBuf>,
+        db: Option<PathBuf>,
+        version: Option<u64>,
+    ) -> Result<Self> {
+        match (manifest, db) {
+            (Some(m), _) => Ok(StateSource::Snapshot(m)),
+            (None, Some(path)) => Ok(StateSource::Db { path, version }),
+            (None, None) => bail!("either a snapshot manifest or a db is required"),
+        }
+    }
+
+    /// Start reading the state values, a chunk at a time
+    pub fn open(&self) -> Result<StateStream> {
+        Ok(match self {
+            StateSource::Snapshot(manifest) => {
+                let chunks = SnapshotChunks::from_manifest(manifest)?;
+                StateStream::new(chunks.version, Chunks::Snapshot(chunks))
+            }
+            StateSource::Db { path, version } => {
+                let chunks = DbStateChunks::new(open_read_only(path)?.reader, *version)?;
+                StateStream::new(chunks.version, Chunks::Db(chunks))
+            }
+            StateSource::Values(values) => {
+                let mut items: Vec<_> = values
+                    .accounts
+                    .iter()
+                    .flat_map(|(address, resources)| {
+                        resources.iter().map(move |(path, v)| {
+                            (
+                                StateKey::access_path(AccessPath::new(*address, path.clone())),
+                                StateValue::from(v.clone()),
+                            )
+                        })
+                    })
+                    .chain(values.tables.iter().map(|((handle, key), v)| {
+                        (
+                            StateKey::table_item(*handle, key.clone()),
+                            StateValue::from(v.clone()),
+                        )
+                    }))
+                    .collect();
+                items.sort_by_key(|(k, _)| k.hash());
+                StateStream::new(values.version, Chunks::Memory(Some(items)))
+            }
+        })
+    }
+}
+
+impl fmt::Display for StateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateSource::Snapshot(manifest) => write!(f, "snapshot {}", manifest.display()),
+            StateSource::Db { path, version } => match version {
+                Some(v) => write!(f, "db {} at version {v}", path.display()),
+                None => write!(f, "db {}", path.display()),
+            },
+            StateSource::Values(values) => write!(f, "state at version {}", values.version),
+        }
+    }
+}
+
+enum Chunks {
+    Snapshot(SnapshotChunks),
+    Db(DbStateChunks),
+    Memory(Option<Vec<(StateKey, StateValue)>>),
+}
+
+/// The state values of a source, one at a time in the order of the key
+/// hashes
+pub struct StateStream {
+    pub version: u64,
+    chunks: Chunks,
+    buf: std::vec::IntoIter<(StateKey, StateValue)>,
+    last: Option<HashValue>,
+}
+
+impl StateStream {
+    fn new(version: u64, chunks: Chunks) -> Self {
+        Self {
+            version,
+            chunks,
+            buf: vec![].into_iter(),
+            last: None,
+        }
+    }
+
+    pub async fn next(&mut self) -> Result<Option<(HashValue, StateKey, StateValue)>> {
+        loop {
+            if let Some((key, value)) = self.buf.next() {
+                let hash = key.hash();
+                // the merge of two states depends on the order
+                if self.last.is_some_and(|last| hash <= last) {
+                    bail!("state values are not in key hash order at {:?}", key);
+                }
+                self.last = Some(hash);
+                return Ok(Some((hash, key, value)));
+            }
+            let chunk = match &mut self.chunks {
+                Chunks::Snapshot(c) => c.next_chunk().await?,
+                Chunks::Db(c) => c.next_chunk()?,
+                Chunks::Memory(c) => c.take(),
+            };
+            match chunk {
+                Some(c) => self.buf = c.into_iter(),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Walk two states together, calling `f` with each key and its value
+/// before and after
+async fn merge_states<F>(before: &mut StateStream, after: &mut StateStream, mut f: F) -> Result<()>
+where
+    F: FnMut(StateKey, Option<StateValue>, Option<StateValue>) -> Result<()>,
+{
+    let mut b = before.next().await?;
+    let mut a = after.next().await?;
+    loop {
+        let (key, bv, av) = match (b.take(), a.take()) {
+            (None, None) => return Ok(()),
+            (Some((_, key, v)), None) => {
+                b = before.next().await?;
+                (key, Some(v), None)
+            }
+            (None, Some((_, key, v))) => {
+                a = after.next().await?;
+                (key, None, Some(v))
+            }
+            (Some(x), Some(y)) => match x.0.cmp(&y.0) {
+                Ordering::Less => {
+                    a = Some(y);
+                    b = before.next().await?;
+                    (x.1, Some(x.2), None)
+                }
+                Ordering::Greater => {
+                    b = Some(x);
+                    a = after.next().await?;
+                    (y.1, None, Some(y.2))
+                }
+                Ordering::Equal => {
+                    b = before.next().await?;
+                    a = after.next().await?;
+                    (x.1, Some(x.2), Some(y.2))
+                }
+            },
+        };
+        f(key, bv, av)?;
+    }
+}
+
+/// Read only the resources of some accounts, and some table items
+async fn collect(
+    source: &StateSource,
+    accounts: &BTreeSet<AccountAddress>,
+    table_items: &[StateKey],
+) -> Result<StateValues> {
+    let mut stream = source.open()?;
+    let mut values = StateValues {
+        version: stream.version,
+        ..Default::default()
+    };
+    while let Some((_, key, value)) = stream.next().await? {
+        let wanted = match key.inner() {
+            StateKeyInner::AccessPath(p) => accounts.contains(&p.address),
+            StateKeyInner::TableItem { .. } => table_items.contains(&key),
+            _ => false,
+        };
+        if wanted {
+            values.insert(&key, &value);
+        }
+    }
+    Ok(values)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    Added,
+    Removed,
+    Modified,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ResourceDiff {
+    /// the struct tag, or the module id of code
+    pub resource: String,
+    pub change: Change,
+    /// hex of the bcs bytes, not included for code
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountDiff {
+    pub address: AccountAddress,
+    pub change: Change,
+    pub resources: Vec<ResourceDiff>,
+    /// the resources which libra-types can decode. `None` if any of them
+    /// failed to decode, the raw bytes are still in `resources`
+    pub decoded_before: Option<AccountRecord>,
+    pub decoded_after: Option<AccountRecord>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decode_error: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DiffSummary {
+    pub accounts_added: usize,
+    pub accounts_removed: usize,
+    pub accounts_modified: usize,
+    pub resources_added: usize,
+    pub resources_removed: usize,
+    pub resources_modified: usize,
+    /// supply of CoinInfo<LibraCoin>
+    pub supply_before: Option<u128>,
+    pub supply_after: Option<u128>,
+    pub supply_delta: Option<i128>,
+    /// sum of the CoinStore<LibraCoin> of the diffed accounts
+    pub balances_before: u128,
+    pub balances_after: u128,
+    pub balances_delta: i128,
+}
+
+impl fmt::Display for DiffSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "accounts:  +{} -{} ~{}",
+            self.accounts_added, self.accounts_removed, self.accounts_modified
+        )?;
+        writeln!(
+            f,
+            "resources: +{} -{} ~{}",
+            self.resources_added, self.resources_removed, self.resources_modified
+        )?;
+        match self.supply_delta {
+            Some(delta) => writeln!(
+                f,
+                "supply:    {:?} -> {:?} ({delta:+})",
+                self.supply_before, self.supply_after
+            )?,
+            None => writeln!(
+                f,
+                "supply:    {:?} -> {:?}",
+                self.supply_before, self.supply_after
+            )?,
+        }
+        write!(
+            f,
+            "balances:  {} -> {} ({:+})",
+            self.balances_before, self.balances_after, self.balances_delta
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct StateDiff {
+    pub before_version: u64,
+    pub after_version: u64,
+    pub summary: DiffSummary,
+    pub accounts: Vec<AccountDiff>,
+}
+
+/// A readable name for an access path
+pub fn resource_name(path: &[u8]) -> String {
+    let Ok(p) = bcs::from_bytes::<access_path::Path>(path) else {
+        return hex::encode(path);
+    };
+    if let access_path::Path::Resource(tag) = &p {
+        return tag.to_string();
+    }
+    if let access_path::Path::Code(module) = &p {
+        return format!("code {}", module.short_str_lossless());
+    }
+    format!("{:?}", p)
+}
+
+fn is_code(path: &[u8]) -> bool {
+    matches!(
+        bcs::from_bytes::<access_path::Path>(path),
+        Ok(access_path::Path::Code(_))
+    )
+}
+
+fn value_hex(path: &[u8], value: Option<&[u8]>) -> Option<String> {
+    value.filter(|_| !is_code(path)).map(hex::encode)
+}
+
+/// The change of one resource, if any
+fn resource_diff(path: &[u8], before: Option<&[u8]>, after: Option<&[u8]>) -> Option<ResourceDiff> {
+    let change = match (before, after) {
+        (None, Some(_)) => Change::Added,
+        (Some(_), None) => Change::Removed,
+        (Some(x), Some(y)) if x != y => Change::Modified,
+        _ => return None,
+    };
+    Some(ResourceDiff {
+        resource: resource_name(path),
+        change,
+        before: value_hex(path, before),
+        after: value_hex(path, after),
+    })
+}
+
+/// The resources added, removed or modified in one account
+pub fn diff_resources(
+    before: &BTreeMap<Vec<u8>, Vec<u8>>,
+    after: &BTreeMap<Vec<u8>, Vec<u8>>,
+) -> Vec<ResourceDiff> {
+    let paths: BTreeSet<_> = before.keys().chain(after.keys()).collect();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            resource_diff(
+                path,
+                before.get(path).map(Vec::as_slice),
+                after.get(path).map(Vec::as_slice),
+            )
+        })
+        .collect()
+}
+
+/// The table item of the supply aggregator of CoinInfo<LibraCoin>, if it
+/// has one
+fn supply_aggregator(state: &StateValues) -> Result<Option<StateKey>> {
+    let Some(framework) = state.account_state(AccountAddress::ONE) else {
+        return Ok(None);
+    };
+    Ok(framework
+        .get_move_resource::<GasCoinInfoResource>()?
+        .and_then(|c| c.supply().as_ref().and_then(|s| s.aggregator.as_ref()))
+        .map(|a| a.state_key()))
+}
+
+/// The supply in CoinInfo<LibraCoin>, which may be kept in an aggregator
+pub fn total_supply(state: &StateValues) -> Result<Option<u128>> {
+    let Some(framework) = state.account_state(AccountAddress::ONE) else {
+        return Ok(None);
+    };
+    let Some(coin_info) = framework.get_move_resource::<GasCoinInfoResource>()? else {
+        return Ok(None);
+    };
+    let Some(supply) = coin_info.supply() else {
+        return Ok(None);
+    };
+    if let Some(aggregator) = &supply.aggregator {
+        let state_key = aggregator.state_key();
+        let StateKeyInner::TableItem { handle, key } = state_key.inner() else {
+            return Ok(None);
+        };
+        return state
+            .tables
+            .get(&(*handle, key.clone()))
+            .map(|v| bcs::from_bytes(v).context("cannot decode the supply aggregator"))
+            .transpose();
+    }
+    Ok(supply.integer.as_ref().map(|i| i.value))
+}
+
+fn balance(coin_store: Option<&[u8]>) -> Result<u128> {
+    Ok(coin_store
+        .map(bcs::from_bytes::<LibraCoinStoreResource>)
+        .transpose()?
+        .map_or(0, |c| c.coin() as u128))
+}
+
+fn decode(
+    state: &StateValues,
+    address: AccountAddress,
+    donor_voice_accounts: &HashSet<AccountAddress>,
+) -> Result<Option<AccountRecord>> {
+    state
+        .account_state(address)
+        .map(|s| {
+            account_record(
+                &s,
+                address,
+                &SnapshotFilter::default(),
+                donor_voice_accounts,
+            )
+        })
+        .transpose()
+}
+
+fn donor_voice_accounts(state: &StateValues) -> Result<HashSet<AccountAddress>> {
+    match state.account_state(AccountAddress::ONE) {
+        Some(framework) => donor_voice_registry(&framework),
+        None => Ok(HashSet::new()),
+    }
+}
+
+/// Compare two states, optionally only some accounts.
+/// The first pass finds the changed resources, the second reads the whole
+/// of the changed accounts to decode them.
+pub async fn diff_states(
+    before: &StateSource,
+    after: &StateSource,
+    addresses: &[AccountAddress],
+) -> Result<StateDiff> {
+    let wanted = |a: &AccountAddress| addresses.is_empty() || addresses.contains(a);
+    let coin_path = LibraCoinStoreResource::resource_path();
+
+    let mut before_stream = before.open()?;
+    let mut after_stream = after.open()?;
+    let before_version = before_stream.version;
+    let after_version = after_stream.version;
+
+    let mut summary = DiffSummary::default();
+    let mut changed: BTreeMap<AccountAddress, Vec<ResourceDiff>> = BTreeMap::new();
+    // 0x1 has the supply and the registries
+    let mut framework_before = StateValues::default();
+    let mut framework_after = StateValues::default();
+    merge_states(&mut before_stream, &mut after_stream, |key, b, a| {
+        let StateKeyInner::AccessPath(p) = key.inner() else {
+            return Ok(());
+        };
+        if p.address == AccountAddress::ONE {
+            if let Some(v) = &b {
+                framework_before.insert(&key, v);
+            }
+            if let Some(v) = &a {
+                framework_after.insert(&key, v);
+            }
+        }
+        if !wanted(&p.address) {
+            return Ok(());
+        }
+        let (b, a) = (b.as_ref().map(|v| v.bytes()), a.as_ref().map(|v| v.bytes()));
+        if p.path == coin_path {
+            summary.balances_before += balance(b.map(|v| &v[..]))?;
+            summary.balances_after += balance(a.map(|v| &v[..]))?;
+        }
+        if let Some(r) = resource_diff(&p.path, b.map(|v| &v[..]), a.map(|v| &v[..])) {
+            changed.entry(p.address).or_default().push(r);
+        }
+        Ok(())
+    })
+    .await?;
+    summary.balances_delta = summary.balances_after as i128 - summary.balances_before as i128;
+
+    let mut accounts_to_read: BTreeSet<_> = changed.keys().cloned().collect();
+    accounts_to_read.insert(AccountAddress::ONE);
+    let before_state = collect(
+        before,
+        &accounts_to_read,
+        &Vec::from_iter(supply_aggregator(&framework_before)?),
+    )
+    .await?;
+    let after_state = collect(
+        after,
+        &accounts_to_read,
+        &Vec::from_iter(supply_aggregator(&framework_after)?),
+    )
+    .await?;
+
+    summary.supply_before = total_supply(&before_state)?;
+    summary.supply_after = total_supply(&after_state)?;
+    if let (Some(b), Some(a)) = (summary.supply_before, summary.supply_after) {
+        summary.supply_delta = Some(a as i128 - b as i128);
+    }
+    let before_dv = donor_voice_accounts(&before_state)?;
+    let after_dv = donor_voice_accounts(&after_state)?;
+
+    let mut accounts = vec![];
+    for (address, mut resources) in changed {
+        resources.sort_by(|x, y| x.resource.cmp(&y.resource));
+        let change = match (
+            before_state.accounts.contains_key(&address),
+            after_state.accounts.contains_key(&address),
+        ) {
+            (false, _) => Change::Added,
+            (_, false) => Change::Removed,
+            _ => Change::Modified,
+        };
+        match change {
+            Change::Added => summary.accounts_added += 1,
+            Change::Removed => summary.accounts_removed += 1,
+            Change::Modified => summary.accounts_modified += 1,
+        }
+        for r in &resources {
+            match r.change {
+                Change::Added => summary.resources_added += 1,
+                Change::Removed => summary.resources_removed += 1,
+                Change::Modified => summary.resources_modified += 1,
+            }
+        }
+
+        // a resource which does not decode, e.g. after a struct changed in
+        // an upgrade, leaves only the raw bytes
+        let mut decode_error = None;
+        let mut decoded = |state: &StateValues, dv: &HashSet<AccountAddress>| {
+            decode(state, address, dv).unwrap_or_else(|e| {
+                decode_error = Some(format!("cannot decode account {address}: {e:#}"));
+                None
+            })
+        };
+        let decoded_before = decoded(&before_state, &before_dv);
+        let decoded_after = decoded(&after_state, &after_dv);
+
+        accounts.push(AccountDiff {
+            address,
+            change,
+            resources,
+            decoded_before,
+            decoded_after,
+            decode_error,
+        });
+    }
+
+    Ok(StateDiff {
+        before_version,
+        after_version,
+        summary,
+        accounts,
+    })
+}
+
+/// Diff two states, print the summary and write the diff as json
+pub async fn state_diff_to_json(
+    before: &StateSource,
+    after: &StateSource,
+    addresses: &[AccountAddress],
+    out_path: &Path,
+) -> Result<StateDiff> {
+    println!("diffing {before} and {after}");
+    let diff = diff_states(before, after, addresses).await?;
+    fs::write(out_path, serde_json::to_string_pretty(&diff)?)?;
+    println!(
+        "diff of version {} to {}:\n{}",
+        diff.before_version, diff.after_version, diff.summary
+    );
+    println!("written to {}", out_path.display());
+    Ok(diff)
+}
+
+#[tokio::test]
+async fn diff_two_states() {
+    use diem_types::event::{EventHandle, EventKey};
+    use libra_types::move_resource::wallet::SlowWalletResource;
+
+    let alice = AccountAddress::from_hex_literal("0xa11ce").unwrap();
+    let bob = AccountAddress::from_hex_literal("0xb0b").unwrap();
+    let coin = |address, value| {
+        let handle = || EventHandle::new(EventKey::new(0, address), 0);
+        bcs::to_bytes(&LibraCoinStoreResource::new(value, handle(), handle())).unwrap()
+    };
+    let slow = bcs::to_bytes(&SlowWalletResource {
+        unlocked: 1,
+        transferred: 0,
+    })
+    .unwrap();
+
+    let mut before = StateValues {
+        version: 10,
+        ..Default::default()
+    };
+    before.accounts.insert(
+        alice,
+        BTreeMap::from([
+            (LibraCoinStoreResource::resource_path(), coin(alice, 100)),
+            (SlowWalletResource::resource_path(), slow),
+        ]),
+    );
+
+    let mut after = StateValues {
+        version: 20,
+        ..Default::default()
+    };
+    after.accounts.insert(
+        alice,
+        BTreeMap::from([(LibraCoinStoreResource::resource_path(), coin(alice, 70))]),
+    );
+    after.accounts.insert(
+        bob,
+        BTreeMap::from([(LibraCoinStoreResource::resource_path(), coin(bob, 40))]),
+    );
+
+    let before = StateSource::Values(before);
+    let after = StateSource::Values(after);
+    let diff = diff_states(&before, &after, &[]).await.unwrap();
+    assert_eq!(diff.summary.accounts_added, 1);
+    assert_eq!(diff.summary.accounts_modified, 1);
+    assert_eq!(diff.summary.resources_added, 1);
+    assert_eq!(diff.summary.resources_removed, 1);
+    assert_eq!(diff.summary.resources_modified, 1);
+    assert_eq!(diff.summary.balances_delta, 10);
+    assert!(diff.summary.supply_delta.is_none());
+
+    let a = diff.accounts.iter().find(|d| d.address == alice).unwrap();
+    assert_eq!(a.change, Change::Modified);
+    assert_eq!(a.decoded_before.as_ref().unwrap().balance, Some(100));
+    assert_eq!(a.decoded_after.as_ref().unwrap().balance, Some(70));
+    assert!(a
+        .resources
+        .iter()
+        .any(|r| r.change == Change::Removed && r.resource.contains("SlowWallet")));
+
+    // only one account
+    let diff = diff_states(&before, &after, &[bob]).await.unwrap();
+    assert_eq!(diff.accounts.len(), 1);
+    assert_eq!(diff.accounts[0].change, Change::Added);
+    assert!(diff.accounts[0].decoded_before.is_none());
+}
+
+#[tokio::test]
+async fn diff_undecodable_resource() {
+    use libra_types::move_resource::wallet::SlowWalletResource;
+
+    let alice = AccountAddress::from_hex_literal("0xa11ce").unwrap();
+    let before = StateValues {
+        version: 10,
+        ..Default::default()
+    };
+    let mut after = StateValues {
+        version: 20,
+        ..Default::default()
+    };
+    // not a SlowWallet, e.g. the struct changed in an upgrade
+    after.accounts.insert(
+        alice,
+        BTreeMap::from([(SlowWalletResource::resource_path(), vec![1, 2, 3])]),
+    );
+
+    let diff = diff_states(
+        &StateSource::Values(before),
+        &StateSource::Values(after),
+        &[],
+    )
+    .await
+    .unwrap();
+    let a = &diff.accounts[0];
+    assert_eq!(a.change, Change::Added);
+    assert!(a.decoded_after.is_none());
+    assert!(a.decode_error.is_some());
+    assert_eq!(a.resources[0].after.as_deref(), Some("010203"));
+}
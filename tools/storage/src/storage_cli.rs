@@ -1,13 +1,24 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::{Parser, Subcommand};
 use diem_db_tool::DBTool;
 use diem_logger::{Level, Logger};
 use diem_push_metrics::MetricsPusher;
-use std::path::PathBuf;
+use diem_types::{account_address::AccountAddress, chain_id::ChainId, waypoint::Waypoint};
+use std::{net::SocketAddr, path::PathBuf, time::Duration};
 
 // Import the correct functions from libra-config
 
-use crate::{bootstrap, download_bundle, read_snapshot, restore};
+use crate::{
+    archive_producer::{ArchiveOpts, ArchiveProducer, DEFAULT_BACKUP_SERVICE},
+    archive_source::{archive_source, ArchiveSource, GithubSource},
+    bootstrap, download_bundle,
+    export_snapshot::{self, ResourceKind, SnapshotFilter},
+    export_transactions::{self, ExportFormat},
+    maintain::{self, MaintainOpts},
+    point_in_time, read_snapshot, restore, serve,
+    state_diff::{self, StateSource},
+    verify_bundle,
+};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -35,6 +46,10 @@ pub enum Sub {
         #[clap(short, long)]
         /// prevent bootstrap after restore, advanced
         prevent_bootstrap: bool,
+        #[clap(long)]
+        /// optional, restore to this version instead of the bundle's epoch, replaying
+        /// transactions from the closest snapshot below it
+        version: Option<u64>,
     },
     /// downloads the stat, epoch, and transaction
     /// restore files from the `epoch-archive` repo
@@ -48,19 +63,151 @@ pub enum Sub {
         #[clap(long, default_value = "v7.0.0")]
         /// branch of the archive, canonically: "v7.0.0"
         branch: String,
-        #[clap(short, long)]
-        /// required, the number of epoch to restore
-        epoch: u64,
+        #[clap(long, conflicts_with_all = ["owner", "repo", "branch"])]
+        /// download from another archive instead of GitHub: github:OWNER/REPO@BRANCH,
+        /// an http(s) url with an index.json, s3+https://HOST/BUCKET/PATH, or a local path
+        source: Option<String>,
+        #[clap(short, long, required_unless_present = "version")]
+        /// the number of epoch to restore
+        epoch: Option<u64>,
+        #[clap(long, conflicts_with = "epoch")]
+        /// download what is needed to restore to any version instead of an epoch
+        version: Option<u64>,
         #[clap(short, long)]
         /// required, the directory to download the restore bundle to
         destination: PathBuf,
     },
+    /// Produce an epoch archive from a local node's backup service, writing
+    /// each epoch as it ends
+    Archive {
+        #[clap(short, long)]
+        /// root of the archive, folders are written to `<archive-dir>/snapshots`
+        archive_dir: PathBuf,
+        #[clap(long, default_value = DEFAULT_BACKUP_SERVICE)]
+        /// the node's backup service, see `storage.backup_service_address`
+        backup_service_address: String,
+        #[clap(long)]
+        /// optional, first epoch of a new archive, defaults to the last completed epoch
+        start_epoch: Option<u64>,
+        #[clap(long, default_value_t = 60)]
+        /// seconds between checks for a new epoch
+        poll_secs: u64,
+        #[clap(long)]
+        /// archive the completed epochs and exit, e.g. from cron
+        once: bool,
+        #[clap(long, default_value_t = 134217728)]
+        /// max size of a chunk file in bytes
+        max_chunk_size: usize,
+        #[clap(long, default_value_t = 8)]
+        /// concurrent requests to the backup service
+        concurrent_data_requests: usize,
+        #[clap(long)]
+        /// leave the chunk files uncompressed
+        no_compress: bool,
+    },
+    /// Verify the signatures and proofs of a bundle, without restoring
+    VerifyBundle {
+        #[clap(short, long)]
+        /// the directory with the epoch_ending, state_epoch and transaction folders
+        bundle_path: PathBuf,
+        #[clap(short, long)]
+        /// trusted waypoint to anchor the epoch endings, e.g. from genesis or a
+        /// node you trust. Defaults to the archive's own waypoint
+        waypoint: Option<Waypoint>,
+        #[clap(long)]
+        /// print the report as json
+        json: bool,
+    },
     /// Read a snapshot, parse and export to JSON
     ExportSnapshot {
         #[clap(short, long)]
         manifest_path: PathBuf,
         #[clap(short, long)]
         out_path: Option<PathBuf>,
+        #[clap(long, value_enum, value_delimiter = ',')]
+        /// optional, only export these resources, e.g. balance,slow-wallet
+        resources: Vec<ResourceKind>,
+        #[clap(long, value_delimiter = ',')]
+        /// optional, only export these accounts
+        addresses: Vec<AccountAddress>,
+        #[clap(long, conflicts_with_all = ["resources", "addresses"])]
+        /// export a v6 snapshot in the legacy recovery format
+        legacy_v6: bool,
+    },
+    /// Read a transaction backup, export rows to NDJSON or Parquet.
+    /// Events are not decoded: each has its type_tag, and its data as hex
+    /// encoded BCS, decode downstream by type_tag
+    ExportTransactions {
+        #[clap(short, long)]
+        /// the transaction.manifest of a transaction backup folder
+        manifest_path: PathBuf,
+        #[clap(short, long)]
+        /// directory for the exported files
+        out_dir: PathBuf,
+        #[clap(short, long, value_enum, default_value = "ndjson")]
+        format: ExportFormat,
+        #[clap(long)]
+        /// optional, first version to export, defaults to the start of the backup
+        start_version: Option<u64>,
+        #[clap(long)]
+        /// optional, last version to export, defaults to the end of the backup
+        end_version: Option<u64>,
+    },
+    /// Diff the state of two snapshots or DB versions, per account and resource
+    StateDiff {
+        #[clap(long, required_unless_present = "before_db")]
+        /// state.manifest of the snapshot before
+        before_manifest: Option<PathBuf>,
+        #[clap(long, conflicts_with = "before_manifest")]
+        /// DB before, e.g. restored from an archive
+        before_db: Option<PathBuf>,
+        #[clap(long, requires = "before_db")]
+        /// optional, a state checkpoint version of the DB before, defaults to the latest
+        before_version: Option<u64>,
+        #[clap(long, required_unless_present = "after_db")]
+        /// state.manifest of the snapshot after
+        after_manifest: Option<PathBuf>,
+        #[clap(long, conflicts_with = "after_manifest")]
+        /// DB after, e.g. after a rescue
+        after_db: Option<PathBuf>,
+        #[clap(long, requires = "after_db")]
+        /// optional, a state checkpoint version of the DB after, defaults to the latest
+        after_version: Option<u64>,
+        #[clap(long, value_delimiter = ',')]
+        /// optional, only diff these accounts
+        addresses: Vec<AccountAddress>,
+        #[clap(short, long, default_value = "state_diff.json")]
+        /// file for the json diff
+        out_path: PathBuf,
+    },
+    /// Serve the REST API read-only over a restored DB, for `libra query --url`
+    Serve {
+        #[clap(long)]
+        /// path of the restored DB
+        db: PathBuf,
+        #[clap(long, default_value = "127.0.0.1:8080")]
+        /// address to listen on
+        address: SocketAddr,
+        #[clap(long, default_value = "mainnet")]
+        /// chain id reported by the API
+        chain_id: ChainId,
+    },
+    /// Maintain an offline DB: report column family sizes, prune old ledger
+    /// history and compact
+    Maintain {
+        #[clap(long)]
+        /// path of the DB, the node must be stopped
+        db: PathBuf,
+        #[clap(long)]
+        /// optional, prune the ledger history older than this many versions.
+        /// Epoch ending ledger infos are kept
+        prune_keep_versions: Option<u64>,
+        #[clap(long)]
+        /// compact every column family, e.g. to reclaim the pruned space
+        compact: bool,
+        #[clap(long, default_value = "3600")]
+        /// seconds to wait for the pruner
+        prune_timeout_secs: u64,
     },
     /// Bootstrap a restored DB with genesis and waypoint
     Bootstrap {
@@ -91,19 +238,100 @@ impl StorageCli {
             Some(Sub::Db(tool)) => {
                 tool.run().await?;
             }
+            Some(Sub::Archive {
+                archive_dir,
+                backup_service_address,
+                start_epoch,
+                poll_secs,
+                once,
+                max_chunk_size,
+                concurrent_data_requests,
+                no_compress,
+            }) => {
+                let producer = ArchiveProducer::new(ArchiveOpts {
+                    archive_dir,
+                    backup_service_address,
+                    start_epoch,
+                    max_chunk_size,
+                    concurrent_data_requests,
+                    no_compress,
+                })?;
+                if once {
+                    let count = producer.run_once().await?;
+                    println!("archived {count} epochs");
+                } else {
+                    producer.run(Duration::from_secs(poll_secs)).await?;
+                }
+            }
+            Some(Sub::VerifyBundle {
+                bundle_path,
+                waypoint,
+                json,
+            }) => {
+                let report = verify_bundle::verify_bundle(&bundle_path, waypoint)?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&report)?);
+                } else {
+                    println!("{report}");
+                }
+                if !report.is_ok() {
+                    bail!(
+                        "{} corrupted and {} unverified checks",
+                        report.failures().count(),
+                        report.unverified().count()
+                    );
+                }
+            }
             Some(Sub::ExportSnapshot {
                 manifest_path,
                 out_path,
+                resources,
+                addresses,
+                legacy_v6,
+            }) => {
+                if legacy_v6 {
+                    read_snapshot::manifest_to_json(manifest_path.to_owned(), out_path.to_owned())
+                        .await;
+                } else {
+                    let filter = SnapshotFilter {
+                        resources,
+                        addresses,
+                    };
+                    export_snapshot::snapshot_to_json(&manifest_path, out_path, &filter).await?;
+                }
+            }
+            Some(Sub::ExportTransactions {
+                manifest_path,
+                out_dir,
+                format,
+                start_version,
+                end_version,
             }) => {
-                read_snapshot::manifest_to_json(manifest_path.to_owned(), out_path.to_owned())
-                    .await;
+                let files = export_transactions::export_transactions(
+                    &manifest_path,
+                    &out_dir,
+                    format,
+                    start_version,
+                    end_version,
+                )
+                .await?;
+                println!("exported {} files to {}", files.len(), out_dir.display());
             }
             Some(Sub::EpochRestore {
                 bundle_path,
                 destination_db,
                 prevent_bootstrap,
+                version,
             }) => {
-                restore::epoch_restore(bundle_path, destination_db.clone()).await?;
+                match version {
+                    Some(v) => {
+                        point_in_time::version_restore(bundle_path, destination_db.clone(), v)
+                            .await?;
+                    }
+                    None => {
+                        restore::epoch_restore(bundle_path, destination_db.clone()).await?;
+                    }
+                }
                 // by default we want to bootstrap a restored db
                 // if you have an advanced case you can run with --prevent_bootstrap=true
                 // and later run the bootstrap command standalone
@@ -115,18 +343,64 @@ impl StorageCli {
                 owner,
                 repo,
                 branch,
+                source,
                 epoch,
+                version,
                 destination,
             }) => {
-                download_bundle::download_restore_bundle(
-                    &owner,
-                    &repo,
-                    &branch,
-                    &epoch,
-                    &destination,
+                let source: Box<dyn ArchiveSource> = match source {
+                    Some(location) => archive_source(&location)?,
+                    None => Box::new(GithubSource::new(&owner, &repo, &branch)),
+                };
+                match (epoch, version) {
+                    (_, Some(v)) => {
+                        download_bundle::download_version_bundle(&*source, v, &destination).await?;
+                    }
+                    (Some(e), None) => {
+                        download_bundle::download_restore_bundle(&*source, &e, &destination)
+                            .await?;
+                    }
+                    (None, None) => bail!("either --epoch or --version is required"),
+                }
+            }
+            Some(Sub::StateDiff {
+                before_manifest,
+                before_db,
+                before_version,
+                after_manifest,
+                after_db,
+                after_version,
+                addresses,
+                out_path,
+            }) => {
+                state_diff::state_diff_to_json(
+                    &StateSource::new(before_manifest, before_db, before_version)?,
+                    &StateSource::new(after_manifest, after_db, after_version)?,
+                    &addresses,
+                    &out_path,
                 )
                 .await?;
             }
+            Some(Sub::Serve {
+                db,
+                address,
+                chain_id,
+            }) => {
+                serve::serve_db(&db, address, chain_id).await?;
+            }
+            Some(Sub::Maintain {
+                db,
+                prune_keep_versions,
+                compact,
+                prune_timeout_secs,
+            }) => {
+                maintain::maintain(&MaintainOpts {
+                    db_path: db,
+                    prune_keep_versions,
+                    compact,
+                    prune_timeout: Duration::from_secs(prune_timeout_secs),
+                })?;
+            }
             Some(Sub::Bootstrap {
                 db_path,
                 home_path,
@@ -0,0 +1,561 @@
+//! Verify the files of a backup archive without restoring a DB.
+//! Epoch endings are chained from a trusted waypoint, each ledger info
+//! signed by the validator set announced in the previous one. The state
+//! snapshot and transaction proofs are then checked against those ledger
+//! infos.
+
+use anyhow::{bail, ensure, Context, Result};
+use diem_backup_cli::backup_types::{
+    epoch_ending::manifest::EpochEndingBackup,
+    state_snapshot::manifest::{StateSnapshotBackup, StateSnapshotChunk},
+    transaction::manifest::{TransactionBackup, TransactionChunk},
+};
+use diem_crypto::{hash::CryptoHash, HashValue};
+use diem_jellyfish_merkle::{
+    node_type::{LeafNode, Node, NodeKey},
+    restore::JellyfishMerkleRestore,
+    NodeBatch, TreeReader, TreeWriter,
+};
+use diem_types::{
+    contract_event::ContractEvent,
+    epoch_change::Verifier,
+    ledger_info::LedgerInfoWithSignatures,
+    proof::{SparseMerkleRangeProof, TransactionAccumulatorRangeProof, TransactionInfoWithProof},
+    state_store::{state_key::StateKey, state_value::StateValue},
+    transaction::{
+        Transaction, TransactionInfo, TransactionInfoListWithProof, TransactionListWithProof,
+    },
+    waypoint::Waypoint,
+    write_set::WriteSet,
+};
+use flate2::read::GzDecoder;
+use glob::glob;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fmt, fs,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    /// the file checked, relative to the bundle
+    pub file: PathBuf,
+    pub name: String,
+    /// none if it could not be checked, e.g. no trusted validator set
+    pub passed: Option<bool>,
+    pub detail: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct VerifyReport {
+    /// the waypoint was taken from the archive itself, not given by the user
+    pub self_anchored: bool,
+    pub checks: Vec<Check>,
+}
+
+impl VerifyReport {
+    fn push(&mut self, file: &Path, name: &str, result: Result<Option<String>>) {
+        let (passed, detail) = match result {
+            Ok(Some(detail)) => (Some(true), detail),
+            Ok(None) => (None, "no trusted validator set to check it with".to_owned()),
+            Err(e) => (Some(false), format!("{e:#}")),
+        };
+        self.checks.push(Check {
+            file: file.to_owned(),
+            name: name.to_owned(),
+            passed,
+            detail,
+        });
+    }
+
+    fn skip(&mut self, file: &Path, name: &str, reason: String) {
+        self.checks.push(Check {
+            file: file.to_owned(),
+            name: name.to_owned(),
+            passed: None,
+            detail: reason,
+        });
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|c| c.passed == Some(false))
+    }
+
+    /// checks which neither passed nor failed, e.g. not chained to the waypoint
+    pub fn unverified(&self) -> impl Iterator<Item = &Check> {
+        self.checks.iter().filter(|c| c.passed.is_none())
+    }
+
+    /// every check passed, an unverified check is not ok
+    pub fn is_ok(&self) -> bool {
+        self.checks.iter().all(|c| c.passed == Some(true))
+    }
+}
+
+impl fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in &self.checks {
+            let mark = match c.passed {
+                Some(true) => "[x]",
+                Some(false) => "[ ]",
+                None => "[-]",
+            };
+            writeln!(f, "{mark} {} {}: {}", c.file.display(), c.name, c.detail)?;
+        }
+        if self.self_anchored {
+            writeln!(
+                f,
+                "\nWARN: no --waypoint given, the epoch chain is anchored to the archive's own waypoint"
+            )?;
+        }
+        let unverified = self.unverified().count();
+        if unverified > 0 {
+            writeln!(
+                f,
+                "\nWARN: {unverified} checks could not be verified, the files are not trusted"
+            )?;
+        }
+        let failed = self.failures().count();
+        if failed > 0 {
+            write!(f, "\n{failed} checks failed")
+        } else if unverified > 0 {
+            write!(f, "\narchive not verified")
+        } else {
+            write!(f, "\narchive verified")
+        }
+    }
+}
+
+/// Read a file of the bundle, decompressing .gz
+fn load_file(bundle_dir: &Path, handle: &str) -> Result<Vec<u8>> {
+    let path = bundle_dir.join(handle);
+    let bytes = fs::read(&path).with_context(|| format!("cannot read {}", path.display()))?;
+    if handle.ends_with(".gz") {
+        let mut out = vec![];
+        GzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut out)
+            .with_context(|| format!("cannot decompress {}", path.display()))?;
+        return Ok(out);
+    }
+    Ok(bytes)
+}
+
+fn load_bcs<T: DeserializeOwned>(bundle_dir: &Path, handle: &str) -> Result<T> {
+    bcs::from_bytes(&load_file(bundle_dir, handle)?).context("cannot decode file")
+}
+
+/// The records of a chunk file, each prefixed with its u32 big endian length,
+/// as written by the backup service.
+fn load_records<T: DeserializeOwned>(bundle_dir: &Path, handle: &str) -> Result<Vec<T>> {
+    let bytes = load_file(bundle_dir, handle)?;
+    let mut records = vec![];
+    let mut rest = bytes.as_slice();
+    while !rest.is_empty() {
+        ensure!(rest.len() >= 4, "truncated record length");
+        let (len, tail) = rest.split_at(4);
+        let len = u32::from_be_bytes(len.try_into()?) as usize;
+        ensure!(tail.len() >= len, "truncated record");
+        let (record, tail) = tail.split_at(len);
+        records.push(bcs::from_bytes(record).context("cannot decode record")?);
+        rest = tail;
+    }
+    Ok(records)
+}
+
+fn find_manifests(bundle_dir: &Path, name: &str) -> Result<Vec<PathBuf>> {
+    let mut found: Vec<PathBuf> = glob(&format!("{}/*/{}", bundle_dir.display(), name))?
+        .flatten()
+        .collect();
+    found.sort();
+    Ok(found)
+}
+
+fn relative(bundle_dir: &Path, path: &Path) -> PathBuf {
+    path.strip_prefix(bundle_dir).unwrap_or(path).to_owned()
+}
+
+/// The epoch ending ledger infos verified so far
+#[derive(Default)]
+struct EpochHistory {
+    verified: Vec<LedgerInfoWithSignatures>,
+}
+
+impl EpochHistory {
+    /// Ok(None) if there is no validator set to check the signatures with
+    fn verify_ledger_info(&self, li: &LedgerInfoWithSignatures) -> Result<Option<String>> {
+        let epoch = li.ledger_info().epoch();
+        if self
+            .verified
+            .iter()
+            .any(|v| v.ledger_info() == li.ledger_info())
+        {
+            return Ok(Some(format!("same as the verified end of epoch {epoch}")));
+        }
+        // signed by the validators announced at the end of the previous epoch
+        match self
+            .verified
+            .iter()
+            .find(|v| v.ledger_info().epoch() + 1 == epoch)
+            .and_then(|v| v.ledger_info().next_epoch_state())
+        {
+            Some(state) => {
+                state
+                    .verify(li)
+                    .with_context(|| format!("bad signatures for epoch {epoch}"))?;
+                Ok(Some(format!("signed by the validators of epoch {epoch}")))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+fn check_epoch_ending(
+    li: &LedgerInfoWithSignatures,
+    epoch: u64,
+    manifest: &EpochEndingBackup,
+    history: &EpochHistory,
+    trusted: Option<&Waypoint>,
+) -> Result<Option<String>> {
+    let info = li.ledger_info();
+    ensure!(
+        info.ends_epoch() && info.epoch() == epoch,
+        "expected the end of epoch {epoch}, found epoch {}",
+        info.epoch()
+    );
+    let waypoint = Waypoint::new_epoch_boundary(info)?;
+    let index = (epoch - manifest.first_epoch) as usize;
+    ensure!(
+        manifest.waypoints.get(index) == Some(&waypoint),
+        "waypoint {waypoint} is not the one in the manifest"
+    );
+    if let Some(detail) = history.verify_ledger_info(li)? {
+        return Ok(Some(detail));
+    }
+    match trusted {
+        Some(t) if t.version() == info.version() => {
+            t.verify(info)?;
+            Ok(Some(format!("matches waypoint {t}")))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn verify_epoch_endings(
+    bundle_dir: &Path,
+    manifest_path: &Path,
+    trusted: &mut Option<Waypoint>,
+    history: &mut EpochHistory,
+    report: &mut VerifyReport,
+) -> Result<()> {
+    let manifest: EpochEndingBackup = serde_json::from_slice(&fs::read(manifest_path)?)?;
+    let file = relative(bundle_dir, manifest_path);
+    report.push(
+        &file,
+        "manifest",
+        manifest.verify().map(|_| Some("ok".to_owned())),
+    );
+
+    if trusted.is_none() {
+        *trusted = manifest.waypoints.first().cloned();
+        report.self_anchored = true;
+    }
+
+    for chunk in &manifest.chunks {
+        let file = PathBuf::from(&chunk.ledger_infos);
+        let lis: Vec<LedgerInfoWithSignatures> = match load_records(bundle_dir, &chunk.ledger_infos)
+        {
+            Ok(lis) => lis,
+            Err(e) => {
+                report.push(&file, "ledger infos", Err(e));
+                continue;
+            }
+        };
+
+        for (i, li) in lis.iter().enumerate() {
+            let epoch = chunk.first_epoch + i as u64;
+            let name = format!("epoch {epoch}");
+            let result = check_epoch_ending(li, epoch, &manifest, history, trusted.as_ref());
+            let verified = matches!(result, Ok(Some(_)));
+            report.push(&file, &name, result);
+            if verified {
+                history.verified.push(li.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_state_root(
+    bundle_dir: &Path,
+    manifest: &StateSnapshotBackup,
+) -> Result<LedgerInfoWithSignatures> {
+    let (txn_info, li): (TransactionInfoWithProof, LedgerInfoWithSignatures) =
+        load_bcs(bundle_dir, &manifest.proof)?;
+    txn_info.verify(li.ledger_info(), manifest.version)?;
+    let root_hash = txn_info.transaction_info().ensure_state_checkpoint_hash()?;
+    ensure!(
+        root_hash == manifest.root_hash,
+        "root hash {root_hash} does not match the manifest's {}",
+        manifest.root_hash
+    );
+    Ok(li)
+}
+
+/// The restore keeps the tree nodes it still needs to check the next proof
+/// in memory, the finished ones are dropped instead of written to a DB
+struct DiscardTree;
+
+impl TreeReader<StateKey> for DiscardTree {
+    fn get_node_option(&self, _node_key: &NodeKey) -> Result<Option<Node<StateKey>>> {
+        Ok(None)
+    }
+
+    fn get_rightmost_leaf(&self) -> Result<Option<(NodeKey, LeafNode<StateKey>)>> {
+        Ok(None)
+    }
+}
+
+impl TreeWriter<StateKey> for DiscardTree {
+    fn write_node_batch(&self, _node_batch: &NodeBatch<StateKey>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Check a chunk's keys, then prove its values against the root hash. The
+/// chunks must be added in order, each proof builds on the previous ones.
+fn check_state_chunk(
+    bundle_dir: &Path,
+    chunk: &StateSnapshotChunk,
+    expected_idx: usize,
+    restore: &mut JellyfishMerkleRestore<StateKey>,
+) -> Result<Option<String>> {
+    ensure!(
+        chunk.first_idx == expected_idx,
+        "chunk starts at {}, expected {expected_idx}",
+        chunk.first_idx
+    );
+    let records: Vec<(StateKey, StateValue)> = load_records(bundle_dir, &chunk.blobs)?;
+    ensure!(
+        records.len() == chunk.last_idx + 1 - chunk.first_idx,
+        "{} records, the manifest says {}",
+        records.len(),
+        chunk.last_idx + 1 - chunk.first_idx
+    );
+    let hashes: Vec<HashValue> = records.iter().map(|(k, _)| k.hash()).collect();
+    ensure!(
+        hashes.windows(2).all(|w| w[0] < w[1]),
+        "keys are not in order"
+    );
+    ensure!(
+        hashes.first() == Some(&chunk.first_key) && hashes.last() == Some(&chunk.last_key),
+        "keys do not match the manifest's range"
+    );
+    let proof: SparseMerkleRangeProof =
+        load_bcs(bundle_dir, &chunk.proof).with_context(|| format!("bad proof {}", chunk.proof))?;
+    // as the state restore does, the leaves are the hashes of the values
+    let leaves = records.iter().map(|(k, v)| (k, v.hash())).collect();
+    restore
+        .add_chunk_impl(leaves, proof)
+        .context("the values are not proven by the root hash")?;
+    Ok(Some(format!("{} values proven", records.len())))
+}
+
+fn verify_state_snapshot(
+    bundle_dir: &Path,
+    manifest_path: &Path,
+    history: &EpochHistory,
+    report: &mut VerifyReport,
+) -> Result<()> {
+    let manifest: StateSnapshotBackup = serde_json::from_slice(&fs::read(manifest_path)?)?;
+
+    let file = PathBuf::from(&manifest.proof);
+    let root = check_state_root(bundle_dir, &manifest);
+    match root {
+        Ok(li) => {
+            report.push(
+                &file,
+                "root hash",
+                Ok(Some(format!(
+                    "{} at version {}",
+                    manifest.root_hash, manifest.version
+                ))),
+            );
+            report.push(&file, "ledger info", history.verify_ledger_info(&li));
+        }
+        Err(e) => report.push(&file, "root hash", Err(e)),
+    }
+
+    let mut restore = Some(JellyfishMerkleRestore::new(
+        Arc::new(DiscardTree),
+        manifest.version,
+        manifest.root_hash,
+        false, /* async_commit */
+    )?);
+    let mut expected_idx = 0;
+    for chunk in &manifest.chunks {
+        let file = PathBuf::from(&chunk.blobs);
+        match restore.as_mut() {
+            Some(r) => {
+                let result = check_state_chunk(bundle_dir, chunk, expected_idx, r);
+                // the next proofs depend on this chunk's values
+                if result.is_err() {
+                    restore = None;
+                }
+                report.push(&file, "state chunk", result);
+            }
+            None => report.skip(
+                &file,
+                "state chunk",
+                "cannot be proven after a bad chunk".to_owned(),
+            ),
+        }
+        expected_idx = chunk.last_idx + 1;
+    }
+    Ok(())
+}
+
+fn check_transaction_chunk(
+    bundle_dir: &Path,
+    chunk: &TransactionChunk,
+) -> Result<LedgerInfoWithSignatures> {
+    let (range_proof, li): (TransactionAccumulatorRangeProof, LedgerInfoWithSignatures) =
+        load_bcs(bundle_dir, &chunk.proof).with_context(|| format!("bad proof {}", chunk.proof))?;
+    let records: Vec<(Transaction, TransactionInfo, Vec<ContractEvent>, WriteSet)> =
+        load_records(bundle_dir, &chunk.transactions)?;
+    ensure!(
+        records.len() as u64 == chunk.last_version + 1 - chunk.first_version,
+        "{} transactions, the manifest says {}",
+        records.len(),
+        chunk.last_version + 1 - chunk.first_version
+    );
+
+    let mut txns = vec![];
+    let mut infos = vec![];
+    let mut events = vec![];
+    for (i, (txn, info, event_vec, write_set)) in records.into_iter().enumerate() {
+        ensure!(
+            info.state_change_hash() == write_set.hash(),
+            "write set of version {} does not match its hash",
+            chunk.first_version + i as u64
+        );
+        txns.push(txn);
+        infos.push(info);
+        events.push(event_vec);
+    }
+    // reuses the accumulator proof checks of state sync
+    let list = TransactionListWithProof::new(
+        txns,
+        Some(events),
+        Some(chunk.first_version),
+        TransactionInfoListWithProof::new(range_proof, infos),
+    );
+    list.verify(li.ledger_info(), Some(chunk.first_version))?;
+    Ok(li)
+}
+
+fn verify_transactions(
+    bundle_dir: &Path,
+    manifest_path: &Path,
+    history: &EpochHistory,
+    report: &mut VerifyReport,
+) -> Result<()> {
+    let manifest: TransactionBackup = serde_json::from_slice(&fs::read(manifest_path)?)?;
+    let file = relative(bundle_dir, manifest_path);
+    report.push(
+        &file,
+        "manifest",
+        manifest.verify().map(|_| Some("ok".to_owned())),
+    );
+
+    for chunk in &manifest.chunks {
+        let file = PathBuf::from(&chunk.transactions);
+        let result = check_transaction_chunk(bundle_dir, chunk);
+        match result {
+            Ok(li) => {
+                report.push(
+                    &file,
+                    "transactions",
+                    Ok(Some(format!(
+                        "versions {} to {} in the accumulator",
+                        chunk.first_version, chunk.last_version
+                    ))),
+                );
+                report.push(
+                    &PathBuf::from(&chunk.proof),
+                    "ledger info",
+                    history.verify_ledger_info(&li),
+                );
+            }
+            Err(e) => report.push(&file, "transactions", Err(e)),
+        }
+    }
+    Ok(())
+}
+
+/// Verify every manifest in a bundle directory. Without a trusted waypoint
+/// the first waypoint of the archive is used.
+pub fn verify_bundle(bundle_dir: &Path, waypoint: Option<Waypoint>) -> Result<VerifyReport> {
+    let mut report = VerifyReport::default();
+    let mut trusted = waypoint;
+    let mut history = EpochHistory::default();
+
+    let epoch_manifests = find_manifests(bundle_dir, "epoch_ending.manifest")?;
+    if epoch_manifests.is_empty() {
+        bail!(
+            "no epoch_ending manifest in {}, nothing to verify against",
+            bundle_dir.display()
+        );
+    }
+    // in epoch order, so each links to the previous
+    let mut by_epoch = vec![];
+    for m in epoch_manifests {
+        let first: EpochEndingBackup = serde_json::from_slice(&fs::read(&m)?)
+            .with_context(|| format!("cannot parse {}", m.display()))?;
+        by_epoch.push((first.first_epoch, m));
+    }
+    by_epoch.sort();
+    for (_, m) in by_epoch {
+        verify_epoch_endings(bundle_dir, &m, &mut trusted, &mut history, &mut report)?;
+    }
+    if history.verified.is_empty() {
+        let file = PathBuf::from("epoch_ending.manifest");
+        report.skip(
+            &file,
+            "trust",
+            "no epoch ending could be chained to the waypoint".to_owned(),
+        );
+    }
+
+    for m in find_manifests(bundle_dir, "state.manifest")? {
+        verify_state_snapshot(bundle_dir, &m, &history, &mut report)
+            .with_context(|| format!("cannot parse {}", m.display()))?;
+    }
+    for m in find_manifests(bundle_dir, "transaction.manifest")? {
+        verify_transactions(bundle_dir, &m, &history, &mut report)
+            .with_context(|| format!("cannot parse {}", m.display()))?;
+    }
+    Ok(report)
+}
+
+#[test]
+fn verify_fixture_bundle() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/v7");
+    let report = verify_bundle(&dir, None).unwrap();
+    println!("{report}");
+    assert!(report.self_anchored);
+
+    let epoch = report
+        .checks
+        .iter()
+        .find(|c| c.name == "epoch 116")
+        .unwrap();
+    assert_eq!(epoch.passed, Some(true));
+
+    // the fixtures do not include the chunk files, which must be reported
+    let missing: Vec<_> = report.failures().map(|c| c.file.clone()).collect();
+    assert!(missing.contains(&PathBuf::from("state_epoch_116_ver_38180075.05af/0-.chunk")));
+    assert!(missing.contains(&PathBuf::from("transaction_38100001-.541f/38100001-.chunk")));
+    assert!(!report.is_ok());
+}
@@ -6,6 +6,7 @@ use async_trait::async_trait;
 use clap::Subcommand;
 use clap::{self, Parser};
 use diem::common::types::{CliCommand, CliTypedResult};
+use libra_storage::archive_source::archive_source;
 use libra_types::global_config_dir;
 use std::{fs, path::PathBuf};
 
@@ -29,6 +30,11 @@ pub struct TestnetCli {
     /// Data path to use for the restore files and twin db, defaults to $HOME/.libra/
     pub restore_out_dir: Option<PathBuf>,
 
+    #[clap(long, requires = "twin_epoch_restore")]
+    /// Archive to download the restore bundle from, defaults to the mainnet
+    /// archive on GitHub. See `libra ops storage download-restore-bundle --source`
+    pub restore_source: Option<String>,
+
     #[clap(short('r'), long, conflicts_with = "twin_epoch_restore")]
     /// You already have a reference db for twin (don't download and restore)
     pub twin_reference_db: Option<PathBuf>,
@@ -75,7 +81,12 @@ impl CliCommand<Vec<TestInfo>> for TestnetCli {
         } else if let Some(e) = self.twin_epoch_restore {
             let data_path = self.restore_out_dir.unwrap_or_else(global_config_dir);
             println!("downloading restore archive and creating a new db");
-            let out = one_step_restore_db(data_path, e, None, None, None).await?;
+            let source = self
+                .restore_source
+                .as_deref()
+                .map(archive_source)
+                .transpose()?;
+            let out = one_step_restore_db(data_path, e, source.as_deref()).await?;
             Some(out)
         } else {
             println!("configuring virgin network...");
@@ -1,5 +1,8 @@
 use anyhow::Result;
-use libra_storage::{download_bundle, restore};
+use libra_storage::{
+    archive_source::{ArchiveSource, GithubSource},
+    download_bundle, restore,
+};
 use std::path::PathBuf;
 
 /// Helper to do a single step operation to download a backup archive from epoch archive,
@@ -11,21 +14,16 @@ use std::path::PathBuf;
 /// 2. Restore from an existing bundle (if `epoch` is None)
 ///
 /// # Arguments
-/// * `bundle_path` - Path to the bundle (existing or to be downloaded)
-/// * `db_path` - Path where the new DB should be created
-/// * `epoch` - Optional epoch number to restore (when downloading)
-/// * `owner` - Optional GitHub organization or user (default: "0LNetworkCommunity")
-/// * `repo` - Optional repository name (default: "epoch-archive-mainnet")
-/// * `branch` - Optional branch name (default: "v7.0.0")
+/// * `data_path` - Path where the bundle is downloaded and the new DB is created
+/// * `epoch` - Epoch number to restore
+/// * `source` - Optional archive to download from (default: the mainnet archive on GitHub)
 ///
 /// # Returns
-/// * `Result<()>` - Ok if the operation is successful
+/// * `Result<PathBuf>` - the path of the restored DB
 pub async fn one_step_restore_db(
     data_path: PathBuf,
     epoch: u64,
-    owner: Option<String>,
-    repo: Option<String>,
-    branch: Option<String>,
+    source: Option<&dyn ArchiveSource>,
 ) -> Result<PathBuf> {
     println!(
         "restoring twin db for epoch {} at {}",
@@ -33,9 +31,8 @@ pub async fn one_step_restore_db(
         data_path.display()
     );
 
-    let owner_str = owner.unwrap_or_else(|| "0LNetworkCommunity".to_string());
-    let repo_str = repo.unwrap_or_else(|| "epoch-archive-mainnet".to_string());
-    let branch_str = branch.unwrap_or_else(|| "v7.0.0".to_string());
+    let default_source = GithubSource::default();
+    let source = source.unwrap_or(&default_source);
 
     println!(
         "Starting download process for epoch {} to {}",
@@ -43,14 +40,8 @@ pub async fn one_step_restore_db(
         data_path.display()
     );
 
-    let backup_files_dir = download_bundle::download_restore_bundle(
-        &owner_str,
-        &repo_str,
-        &branch_str,
-        &epoch,
-        &data_path,
-    )
-    .await?;
+    let backup_files_dir =
+        download_bundle::download_restore_bundle(source, &epoch, &data_path).await?;
 
     let destination_db = data_path.join(format!("db_{epoch}"));
     // Restore and bootstrap the DB using the EpochRestore subcommand
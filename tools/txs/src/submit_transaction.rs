@@ -12,21 +12,26 @@ use diem_sdk::{
     },
     transaction_builder::TransactionBuilder,
     types::{
+        account_address::AccountAddress,
         chain_id::ChainId,
         transaction::{ExecutionStatus, SignedTransaction, TransactionPayload},
         AccountKey, LocalAccount,
     },
 };
 use libra_types::{
-    core_types::app_cfg::{AppCfg, TxCost},
+    core_types::{
+        app_cfg::{AddressOrAlias, Aliases, AppCfg, TxCost},
+        failover_client::FailoverClient,
+    },
     exports::{AuthenticationKey, Ed25519PrivateKey},
     ol_progress::OLProgress,
     type_extensions::{
         cli_config_ext::CliConfigExt,
-        client_ext::{ClientExt, DEFAULT_TIMEOUT_SECS},
+        client_ext::{check_chain_id, ClientExt, DEFAULT_TIMEOUT_SECS},
     },
 };
 use std::{
+    future::Future,
     path::PathBuf,
     time::{SystemTime, UNIX_EPOCH},
 };
@@ -40,6 +45,10 @@ pub struct Sender {
     client: Client,
     chain_id: ChainId,
     pub response: Option<TransactionOnChainData>,
+    /// the other nodes of the playlist, to fail over to
+    upstreams: Option<FailoverClient>,
+    /// resolves `@nickname` arguments
+    aliases: Aliases,
 }
 
 impl Sender {
@@ -53,26 +62,61 @@ impl Sender {
             Some(c) => c,
             None => Client::default().await?,
         };
-        info!(
-            "chain ledger info: {:?}",
-            &client.get_ledger_information().await?
-        );
+        Self::init(account_key, chain_id, client, None, use_legacy_address).await
+    }
+
+    /// Like `new`, but all requests fail over to the other nodes of a
+    /// playlist. `client` is the node to start with.
+    pub async fn with_upstreams(
+        account_key: AccountKey,
+        chain_id: ChainId,
+        client: Client,
+        upstreams: FailoverClient,
+        use_legacy_address: bool,
+    ) -> anyhow::Result<Self> {
+        Self::init(
+            account_key,
+            chain_id,
+            client,
+            Some(upstreams),
+            use_legacy_address,
+        )
+        .await
+    }
+
+    async fn init(
+        account_key: AccountKey,
+        chain_id: ChainId,
+        mut client: Client,
+        upstreams: Option<FailoverClient>,
+        use_legacy_address: bool,
+    ) -> anyhow::Result<Self> {
+        let info = read_with(&mut client, upstreams.as_ref(), |c| async move {
+            Ok(c.get_ledger_information().await?)
+        })
+        .await?;
+        info!("chain ledger info: {:?}", &info);
 
         // Lookup the originating address and handle legacy address conversion if necessary
-        let address = client
-            .lookup_originating_address(account_key.authentication_key())
-            .await
-            .map(|address| {
-                if !use_legacy_address {
-                    return Ok(address);
-                }
+        let auth_key = account_key.authentication_key();
+        let address = read_with(&mut client, upstreams.as_ref(), |c| async move {
+            c.lookup_originating_address(auth_key).await
+        })
+        .await
+        .map(|address| {
+            if !use_legacy_address {
+                return Ok(address);
+            }
 
-                to_legacy_address(&address)
-            })??;
+            to_legacy_address(&address)
+        })??;
         info!("using address {}", &address);
 
         // Fetch sequence number for the account
-        let seq = client.get_sequence_number(address).await?;
+        let seq = read_with(&mut client, upstreams.as_ref(), |c| async move {
+            c.get_sequence_number(address).await
+        })
+        .await?;
         let local_account = LocalAccount::new(address, account_key, seq);
 
         Ok(Self {
@@ -81,9 +125,30 @@ impl Sender {
             local_account,
             chain_id,
             response: None,
+            upstreams,
+            aliases: Aliases::default(),
         })
     }
 
+    /// Fail over to the other nodes of a playlist, if the current one stops responding.
+    pub fn set_upstreams(&mut self, upstreams: FailoverClient) {
+        self.upstreams = Some(upstreams);
+    }
+
+    /// The nicknames of the config, for `@nickname` arguments.
+    pub fn set_aliases(&mut self, aliases: Aliases) {
+        self.aliases = aliases;
+    }
+
+    pub fn aliases(&self) -> &Aliases {
+        &self.aliases
+    }
+
+    /// An argument's address, looking up a `@nickname`.
+    pub fn resolve(&self, account: &AddressOrAlias) -> anyhow::Result<AccountAddress> {
+        account.resolve(&self.aliases)
+    }
+
     /// Sets the transaction cost for the `Sender`.
     pub fn set_tx_cost(&mut self, cost: &TxCost) {
         cost.clone_into(&mut self.tx_cost);
@@ -103,37 +168,42 @@ impl Sender {
 
         let temp_seq_num = 0;
 
-        // Create authentication key and initialize client with URL
+        // Create authentication key and initialize client with the
+        // healthiest node of the playlist
         let auth_key = AuthenticationKey::ed25519(&key.public_key());
-        let url = &app_cfg.pick_url(None)?;
-        let client = Client::new(url.clone());
-        let address = client
-            .lookup_originating_address(auth_key)
+        let chain_name = app_cfg.active_chain_id();
+        let upstreams = FailoverClient::new(&app_cfg.get_network_profile(Some(chain_name))?);
+
+        // check if we can connect to a client, or exit
+        let (mut client, chain_id) = upstreams
+            .connect()
             .await
-            .unwrap_or(profile.account);
+            .context("cannot connect to any upstream node")?;
+        check_chain_id(chain_name, chain_id)?;
+
+        let address = read_with(&mut client, Some(&upstreams), |c| async move {
+            c.lookup_originating_address(auth_key).await
+        })
+        .await
+        .unwrap_or(profile.account);
 
         let mut local_account = LocalAccount::new(address, key, temp_seq_num);
-        let seq_num = local_account.sequence_number_mut();
-
-        // check if we can connect to this client, or exit
-        let chain_id = match client.get_index().await {
-            Ok(metadata) => {
-                // update sequence number
-                *seq_num = client
-                    .get_sequence_number(address)
-                    .await
-                    .context("failed to get sequence number")?;
-                ChainId::new(metadata.into_inner().chain_id)
-            }
-            Err(_) => bail!("cannot connect to client at {:?}", &url),
-        };
+        // update sequence number
+        *local_account.sequence_number_mut() =
+            read_with(&mut client, Some(&upstreams), |c| async move {
+                c.get_sequence_number(address).await
+            })
+            .await
+            .context("failed to get sequence number")?;
 
         let s = Sender {
             client,
-            tx_cost: app_cfg.tx_configs.get_cost(None),
+            tx_cost: app_cfg.get_tx_cost(None),
             local_account,
             chain_id,
             response: None,
+            upstreams: Some(upstreams),
+            aliases: app_cfg.aliases(),
         };
 
         Ok(s)
@@ -190,6 +260,8 @@ impl Sender {
                 local_account,
                 chain_id,
                 response: None,
+                upstreams: None,
+                aliases: Aliases::default(),
             };
             return Ok(s);
         }
@@ -256,19 +328,45 @@ impl Sender {
         &mut self,
         signed_trans: &SignedTransaction,
     ) -> anyhow::Result<TransactionOnChainData> {
-        let pending_trans = self.client.submit(signed_trans).await?.into_inner();
+        let pending_trans = match self.client.submit(signed_trans).await {
+            Ok(r) => r.into_inner(),
+            // A signed transaction can safely be sent again to another node,
+            // the sequence number prevents it from executing twice.
+            Err(e) if self.upstreams.is_some() => {
+                warn!(
+                    "could not submit transaction, trying the next best node: {}",
+                    e
+                );
+                let upstreams = self.upstreams.as_ref().context("no upstreams")?;
+                self.client = upstreams.rotate()?;
+                self.client.submit(signed_trans).await?.into_inner()
+            }
+            Err(e) => return Err(e.into()),
+        };
 
         info!("pending tx hash: {}", &pending_trans.hash.to_string());
 
+        // any node can tell when the transaction is on chain
         let res = self
-            .client
-            .wait_for_transaction_bcs(&pending_trans)
-            .await?
-            .into_inner();
+            .read(|c| {
+                let pending = pending_trans.clone();
+                async move { Ok(c.wait_for_transaction_bcs(&pending).await?.into_inner()) }
+            })
+            .await?;
 
         Ok(res)
     }
 
+    /// Run an idempotent read, on the other nodes of the playlist if the
+    /// current one fails.
+    pub async fn read<T, F, Fut>(&mut self, f: F) -> anyhow::Result<T>
+    where
+        F: Fn(Client) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        read_with(&mut self.client, self.upstreams.as_ref(), f).await
+    }
+
     /// Evaluates the response of the last submitted transaction.
     pub fn eval_response(&self) -> anyhow::Result<ExecutionStatus, ExecutionStatus> {
         if self.response.is_none() {
@@ -312,3 +410,24 @@ impl Sender {
         &self.client
     }
 }
+
+/// Read through the upstreams if there are any, and keep the client on the
+/// node which answered. Otherwise read from the client.
+async fn read_with<T, F, Fut>(
+    client: &mut Client,
+    upstreams: Option<&FailoverClient>,
+    f: F,
+) -> anyhow::Result<T>
+where
+    F: Fn(Client) -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    match upstreams {
+        Some(u) => {
+            let res = u.read(f).await?;
+            *client = u.current_client()?;
+            Ok(res)
+        }
+        None => f(client.clone()).await,
+    }
+}
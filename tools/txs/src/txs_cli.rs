@@ -7,16 +7,19 @@ use anyhow::Result;
 use clap::Parser;
 use diem::common::types::MovePackageDir;
 use diem_sdk::{
-    crypto::{ed25519::Ed25519PrivateKey, ValidCryptoMaterialStringExt},
     rest_client::Client,
     types::{account_address::AccountAddress, AccountKey},
 };
 use indoc::indoc;
 use libra_types::{
-    core_types::app_cfg::{AppCfg, TxCost, TxType},
+    core_types::{
+        app_cfg::{AddressOrAlias, AppCfg, TxCost, TxType},
+        failover_client::FailoverClient,
+    },
     exports::{ChainId, NamedChain},
+    type_extensions::client_ext::check_chain_id,
 };
-use libra_wallet::account_keys::{get_keys_from_mnem, get_keys_from_prompt};
+use libra_wallet::secret_source::SecretSource;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use url::Url;
@@ -32,7 +35,8 @@ pub struct TxsCli {
     #[clap(short, long)]
     pub config_path: Option<PathBuf>,
 
-    /// optional, mnemonic to pass at runtime. Otherwise this will prompt for mnemonic
+    /// optional, mnemonic to pass at runtime. Otherwise this will prompt for
+    /// mnemonic. Deprecated, use --secret-source
     #[clap(short, long)]
     pub mnemonic: Option<String>,
 
@@ -40,6 +44,12 @@ pub struct TxsCli {
     #[clap(short, long)]
     pub test_private_key: Option<String>,
 
+    /// optional, where to read the mnemonic or private key from: prompt,
+    /// env:VAR, fd:N, file:PATH, or keystore[:DIR]. The keystore holds an
+    /// unencrypted mnemonic
+    #[clap(long, conflicts_with_all(["mnemonic", "test_private_key"]))]
+    pub secret_source: Option<SecretSource>,
+
     /// optional, Use a transaction profile used in libra-cli-config.yaml.
     /// Is mutually exclusive with --tx-cost
     #[clap(long)]
@@ -87,7 +97,7 @@ pub enum TxsSub {
     Transfer {
         /// Address of the recipient
         #[clap(short, long)]
-        to_account: AccountAddress,
+        to_account: AddressOrAlias,
 
         /// The amount of coins to transfer
         #[clap(short, long)]
@@ -148,43 +158,52 @@ impl TxsCli {
         let profile = app_cfg.get_profile(None)?;
 
         // Determine private key based on CLI options or prompts
-        let pri_key = if let Some(pk) = &self.test_private_key {
-            println!("using private key from cli args --test-private-key");
-            Ed25519PrivateKey::from_encoded_string(pk)?
-        } else if let Some(m) = &self.mnemonic {
-            println!("using private key from cli args --mnemonic");
-            let legacy = get_keys_from_mnem(m.to_string())?;
-            legacy.child_0_owner.pri_key
+        let pri_key = if let Some(source) = SecretSource::from_args(
+            self.secret_source.as_ref(),
+            self.mnemonic.as_ref(),
+            self.test_private_key.as_ref(),
+        ) {
+            println!("using private key from {source}");
+            source.account_key()?
         } else if let Ok(p) = profile.borrow_private_key() {
             println!("use private key from test libra-cli-config.yaml");
             p.to_owned()
         } else {
-            let legacy = get_keys_from_prompt()?;
-            legacy.child_0_owner.pri_key
+            SecretSource::Prompt.account_key()?
         };
 
         // Determine chain ID and URL for client
-        let chain_name = self
-            .chain_name
-            .unwrap_or(app_cfg.workspace.default_chain_id);
+        let chain_name = self.chain_name.unwrap_or(app_cfg.active_chain_id());
 
-        let url = if let Some(u) = self.url.as_ref() {
-            u.to_owned()
+        // Initialize sender. Unless a url was given, use the healthiest node
+        // of the playlist and fail over to the others.
+        let account_key = AccountKey::from_private_key(pri_key);
+        let mut send = if let Some(u) = self.url.as_ref() {
+            let client = Client::new(u.to_owned());
+            // check the client is on the expected chain
+            let index = client.get_index().await?.into_inner();
+            check_chain_id(chain_name, ChainId::new(index.chain_id))?;
+            Sender::new(
+                account_key,
+                ChainId::new(chain_name.id()),
+                Some(client),
+                self.legacy_address,
+            )
+            .await?
         } else {
-            app_cfg.pick_url(Some(chain_name))?
+            let upstreams = FailoverClient::new(&app_cfg.get_network_profile(Some(chain_name))?);
+            let (client, chain_id) = upstreams.connect().await?;
+            check_chain_id(chain_name, chain_id)?;
+            Sender::with_upstreams(
+                account_key,
+                ChainId::new(chain_name.id()),
+                client,
+                upstreams,
+                self.legacy_address,
+            )
+            .await?
         };
-
-        // Initialize client
-        let client = Client::new(url);
-
-        // Initialize sender
-        let mut send = Sender::new(
-            AccountKey::from_private_key(pri_key),
-            ChainId::new(chain_name.id()),
-            Some(client),
-            self.legacy_address,
-        )
-        .await?;
+        send.set_aliases(app_cfg.aliases());
 
         // Handle mutually exclusive options for transaction cost
         if self.tx_cost.is_some() && self.tx_profile.is_some() {
@@ -195,7 +214,7 @@ impl TxsCli {
         let tx_cost = self
             .tx_cost
             .clone()
-            .unwrap_or_else(|| app_cfg.tx_configs.get_cost(self.tx_profile.clone()));
+            .unwrap_or_else(|| app_cfg.get_tx_cost(self.tx_profile.clone()));
 
         // Set transaction cost for sender
         send.set_tx_cost(&tx_cost);
@@ -203,7 +222,8 @@ impl TxsCli {
         // Execute subcommand based on parsed input
         match &self.subcommand {
             Some(TxsSub::Transfer { to_account, amount }) => {
-                send.transfer(to_account.to_owned(), amount.to_owned(), self.estimate_only)
+                let to_account = send.resolve(to_account)?;
+                send.transfer(to_account, amount.to_owned(), self.estimate_only)
                     .await?;
                 Ok(())
             }
@@ -5,7 +5,7 @@ use diem_logger::error;
 use diem_types::account_address::AccountAddress;
 use libra_cached_packages::libra_stdlib;
 use libra_query::{account_queries, query_view};
-use libra_types::{exports::Client, move_resource::gas_coin};
+use libra_types::{core_types::app_cfg::AddressOrAlias, exports::Client, move_resource::gas_coin};
 use serde::{Deserialize, Serialize};
 use std::{collections::HashMap, fs, path::PathBuf};
 
@@ -80,7 +80,7 @@ impl CommunityTxs {
 pub struct InitTx {
     #[clap(short, long)]
     /// The initial admins of the multi-sig (cannot add self)
-    pub admins: Vec<AccountAddress>,
+    pub admins: Vec<AddressOrAlias>,
 
     #[clap(short, long)]
     /// Num of signatures needed for the n-of-m
@@ -89,10 +89,12 @@ pub struct InitTx {
 
 impl InitTx {
     pub async fn run(&self, sender: &mut Sender) -> anyhow::Result<()> {
-        let payload = libra_stdlib::community_wallet_init_init_community(
-            self.admins.clone(),
-            self.num_signers,
-        );
+        let admins = self
+            .admins
+            .iter()
+            .map(|a| sender.resolve(a))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let payload = libra_stdlib::community_wallet_init_init_community(admins, self.num_signers);
 
         sender.sign_submit_wait(payload).await?;
         println!("You have completed the first step in creating a community wallet, now the authorities you have proposed need to claim the offer.");
@@ -106,17 +108,19 @@ impl InitTx {
 pub struct OfferTx {
     #[clap(short, long)]
     /// The Community Wallet to propose the offer
-    pub admins: Vec<AccountAddress>,
+    pub admins: Vec<AddressOrAlias>,
     /// Num of signatures needed for the n-of-m
     pub num_signers: u64,
 }
 
 impl OfferTx {
     pub async fn run(&self, sender: &mut Sender) -> anyhow::Result<()> {
-        let payload = libra_stdlib::community_wallet_init_propose_offer(
-            self.admins.clone(),
-            self.num_signers,
-        );
+        let admins = self
+            .admins
+            .iter()
+            .map(|a| sender.resolve(a))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let payload = libra_stdlib::community_wallet_init_propose_offer(admins, self.num_signers);
         sender.sign_submit_wait(payload).await?;
         println!("You have proposed the community wallet offer to the authorities.");
         Ok(())
@@ -128,12 +132,13 @@ impl OfferTx {
 pub struct ClaimTx {
     #[clap(short, long)]
     /// The Community Wallet to claim the offer
-    pub community_wallet: AccountAddress,
+    pub community_wallet: AddressOrAlias,
 }
 
 impl ClaimTx {
     pub async fn run(&self, sender: &mut Sender) -> anyhow::Result<()> {
-        let payload = libra_stdlib::multi_action_claim_offer(self.community_wallet);
+        let community_wallet = sender.resolve(&self.community_wallet)?;
+        let payload = libra_stdlib::multi_action_claim_offer(community_wallet);
         sender.sign_submit_wait(payload).await?;
         println!("You have claimed the community wallet offer.");
         Ok(())
@@ -161,10 +166,10 @@ impl CageTx {
 pub struct AdminTx {
     #[clap(short, long)]
     /// The SlowWallet recipient of funds
-    pub community_wallet: AccountAddress,
+    pub community_wallet: AddressOrAlias,
     #[clap(short, long)]
     /// Admin to add (or remove) from the multisig
-    pub admin: AccountAddress,
+    pub admin: AddressOrAlias,
     #[clap(short, long)]
     /// Drops this admin from the multisig
     pub drop: Option<bool>,
@@ -178,12 +183,14 @@ pub struct AdminTx {
 
 impl AdminTx {
     pub async fn run(&self, sender: &mut Sender) -> anyhow::Result<()> {
+        let community_wallet = sender.resolve(&self.community_wallet)?;
+        let admin = sender.resolve(&self.admin)?;
         // Default to adding a signer if the `drop` flag is not provided
         let is_add_operation = self.drop.unwrap_or(true);
 
         let payload = libra_stdlib::community_wallet_init_change_signer_community_multisig(
-            self.community_wallet,
-            self.admin,
+            community_wallet,
+            admin,
             is_add_operation,
             self.n,
             self.epochs.unwrap_or(10), // todo: remo
@@ -197,10 +204,10 @@ impl AdminTx {
 pub struct ProposeTx {
     #[clap(short, long)]
     /// The Community Wallet to schedule transaction
-    pub community_wallet: AccountAddress,
+    pub community_wallet: AddressOrAlias,
     #[clap(short, long)]
     /// The SlowWallet recipient of funds
-    pub recipient: AccountAddress,
+    pub recipient: AddressOrAlias,
     #[clap(short, long)]
     /// Amount of coins (units) to transfer
     pub amount: u64,
@@ -214,9 +221,11 @@ pub struct ProposeTx {
 
 impl ProposeTx {
     pub async fn run(&self, sender: &mut Sender) -> anyhow::Result<()> {
+        let community_wallet = sender.resolve(&self.community_wallet)?;
+        let recipient = sender.resolve(&self.recipient)?;
         let payload = libra_stdlib::donor_voice_txs_propose_payment_tx(
-            self.community_wallet,
-            self.recipient,
+            community_wallet,
+            recipient,
             gas_coin::cast_decimal_to_coin(self.amount as f64),
             self.description.clone().into_bytes(),
             self.unlocked_advance,
@@ -230,7 +239,7 @@ impl ProposeTx {
 pub struct BatchTx {
     #[clap(short, long)]
     /// The Community Wallet to schedule transaction
-    pub community_wallet: AccountAddress,
+    pub community_wallet: AddressOrAlias,
     #[clap(short, long)]
     /// JSON file with batch payments
     pub file: PathBuf,
@@ -263,11 +272,12 @@ struct ProposePay {
 // (an atomic batch).
 impl BatchTx {
     pub async fn run(&self, sender: &mut Sender) -> anyhow::Result<()> {
+        let community_wallet = sender.resolve(&self.community_wallet)?;
         let data = fs::read_to_string(&self.file)?;
         let mut list: Vec<ProposePay> = serde_json::from_str(&data)?;
 
         let ballots =
-            account_queries::multi_auth_ballots(sender.client(), self.community_wallet).await?;
+            account_queries::multi_auth_ballots(sender.client(), community_wallet).await?;
 
         let mut pending_or_approved = HashMap::new();
         if let Some(d) = ballots.as_object() {
@@ -398,7 +408,7 @@ impl BatchTx {
 
             println!("scheduling tx");
 
-            match propose_one_in_batch(sender, &self.community_wallet, inst).await {
+            match propose_one_in_batch(sender, &community_wallet, inst).await {
                 Ok(_) => {
                     inst.proposed = Some(true);
                 }
@@ -460,7 +470,7 @@ async fn propose_one_in_batch(
 pub struct VetoTx {
     #[clap(short, long)]
     /// The Slow Wallet recipient of funds
-    pub community_wallet: AccountAddress,
+    pub community_wallet: AddressOrAlias,
     #[clap(short, long)]
     /// Proposal number
     pub proposal_id: u64,
@@ -468,8 +478,9 @@ pub struct VetoTx {
 
 impl VetoTx {
     pub async fn run(&self, sender: &mut Sender) -> anyhow::Result<()> {
+        let community_wallet = sender.resolve(&self.community_wallet)?;
         let payload =
-            libra_stdlib::donor_voice_txs_propose_veto_tx(self.community_wallet, self.proposal_id);
+            libra_stdlib::donor_voice_txs_propose_veto_tx(community_wallet, self.proposal_id);
         sender.sign_submit_wait(payload).await?;
         Ok(())
     }
@@ -479,24 +490,24 @@ impl VetoTx {
 pub struct ReauthVoteTx {
     #[clap(short, long)]
     /// The Slow Wallet recipient of funds
-    pub community_wallet: AccountAddress,
+    pub community_wallet: AddressOrAlias,
 }
 
 impl ReauthVoteTx {
     pub async fn run(&self, sender: &mut Sender) -> anyhow::Result<()> {
+        let community_wallet = sender.resolve(&self.community_wallet)?;
         // Submit the reauthorization vote
-        let payload = libra_stdlib::donor_voice_txs_vote_reauth_tx(self.community_wallet);
+        let payload = libra_stdlib::donor_voice_txs_vote_reauth_tx(community_wallet);
         sender.sign_submit_wait(payload).await?;
 
         // First, check if we have pending reauthorization ballots
-        let ballot_id =
-            fetch_pending_reauth_ballots(sender.client(), self.community_wallet).await?;
+        let ballot_id = fetch_pending_reauth_ballots(sender.client(), community_wallet).await?;
 
         // Display tally results based on the ballot ID
         if let Some(id) = ballot_id {
             println!("Found reauthorization ballot with ID: {}", id);
 
-            let args = format!("{}, {}", self.community_wallet.to_canonical_string(), id);
+            let args = format!("{}, {}", community_wallet.to_canonical_string(), id);
             // Call the view function with the specific ballot ID, which is required by the Move function
             let res = query_view::get_view(
                 sender.client(),
@@ -14,6 +14,7 @@ use diem_types::{
 use libra_cached_packages::libra_stdlib;
 use libra_query;
 use libra_types::{
+    core_types::app_cfg::AddressOrAlias,
     exports::{AuthenticationKey, Ed25519PrivateKey},
     type_extensions::client_ext::ClientExt,
 };
@@ -93,7 +94,7 @@ pub struct RotateKeyTx {
     pub new_private_key: Option<String>,
     #[clap(short, long)]
     /// Account being claimed, if being transferred
-    pub claim_address: Option<AccountAddress>,
+    pub claim_address: Option<AddressOrAlias>,
 }
 
 impl RotateKeyTx {
@@ -101,8 +102,13 @@ impl RotateKeyTx {
         println!("\nWARN: you will be rotating the keys for an account, this could permanently lock you out!");
 
         let sender_account = sender.local_account.address();
+        let claim_address = self
+            .claim_address
+            .as_ref()
+            .map(|a| sender.resolve(a))
+            .transpose()?;
 
-        let rotating_account = if let Some(claim) = self.claim_address {
+        let rotating_account = if let Some(claim) = claim_address {
             println!("You have set --claim-address, this means you are claiming someone else's account: {}", &claim);
             println!("IMPORTANT: The account submitting the transaction {} must have previously been delegated responsibility for rotating the keys of the address being claimed", &sender_account.short_str_lossless());
 
@@ -132,7 +138,7 @@ impl RotateKeyTx {
         };
 
         let seq = sender.client().get_sequence_number(sender_account).await?;
-        let payload = if let Some(target_account_address) = self.claim_address {
+        let payload = if let Some(target_account_address) = claim_address {
             let target_account = sender
                 .client()
                 .get_account(target_account_address)
@@ -267,7 +273,7 @@ pub struct RotationCapabilityTx {
     pub revoke: bool,
 
     #[clap(short, long)]
-    pub delegate_address: AccountAddress,
+    pub delegate_address: AddressOrAlias,
 }
 
 impl RotationCapabilityTx {
@@ -277,16 +283,12 @@ impl RotationCapabilityTx {
 
         let chain_id = index_response.into_inner().chain_id;
 
+        let delegate_address = sender.resolve(&self.delegate_address)?;
         let seq = sender.client().get_sequence_number(user_account).await?;
         let payload = if self.revoke {
-            revoke_rotation_capability(self.delegate_address)
+            revoke_rotation_capability(delegate_address)
         } else {
-            offer_rotation_capability_v2(
-                &sender.local_account,
-                self.delegate_address,
-                chain_id,
-                seq,
-            )
+            offer_rotation_capability_v2(&sender.local_account, delegate_address, chain_id, seq)
         }?;
 
         sender.sign_submit_wait(payload).await?;
@@ -339,7 +341,7 @@ pub fn revoke_rotation_capability(
 pub struct VouchTx {
     #[clap(short, long)]
     /// Vouch for another account, usually for validators
-    vouch_for: AccountAddress,
+    vouch_for: AddressOrAlias,
     #[clap(short, long)]
     /// Revoke a vouch for an account
     revoke: bool,
@@ -348,6 +350,7 @@ pub struct VouchTx {
 impl VouchTx {
     pub async fn run(&self, sender: &mut Sender) -> anyhow::Result<()> {
         let sender_address = sender.local_account.address();
+        let vouch_for = sender.resolve(&self.vouch_for)?;
 
         // Show vouch reports BEFORE the transaction
         println!("\n=== BEFORE TRANSACTION ===");
@@ -362,11 +365,9 @@ impl VouchTx {
         }
 
         println!("\nTarget account vouch report:");
-        if let Err(e) = libra_query::account_queries::account_vouch_report_console(
-            sender.client(),
-            self.vouch_for,
-        )
-        .await
+        if let Err(e) =
+            libra_query::account_queries::account_vouch_report_console(sender.client(), vouch_for)
+                .await
         {
             println!("Could not get target account vouch report: {}", e);
         }
@@ -375,13 +376,13 @@ impl VouchTx {
         let action = if self.revoke { "revoke" } else { "vouch for" };
         println!(
             "\nExecuting transaction to {} address {}...",
-            action, self.vouch_for
+            action, vouch_for
         );
 
         let payload = if self.revoke {
-            libra_stdlib::vouch_txs_revoke(self.vouch_for)
+            libra_stdlib::vouch_txs_revoke(vouch_for)
         } else {
-            libra_stdlib::vouch_txs_vouch_for(self.vouch_for)
+            libra_stdlib::vouch_txs_vouch_for(vouch_for)
         };
         sender.sign_submit_wait(payload).await?;
 
@@ -398,11 +399,9 @@ impl VouchTx {
         }
 
         println!("\nTarget account vouch report:");
-        if let Err(e) = libra_query::account_queries::account_vouch_report_console(
-            sender.client(),
-            self.vouch_for,
-        )
-        .await
+        if let Err(e) =
+            libra_query::account_queries::account_vouch_report_console(sender.client(), vouch_for)
+                .await
         {
             println!("Could not get target account vouch report: {}", e);
         }
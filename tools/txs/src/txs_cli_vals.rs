@@ -1,18 +1,41 @@
 //! Validator subcommands
 
-use crate::submit_transaction::Sender;
+use crate::{
+    submit_transaction::Sender,
+    txs_cli_user::{offer_rotation_capability_v2, rotate_key},
+};
 use anyhow::{bail, Context};
-use diem_genesis::config::OperatorConfiguration;
-use diem_types::account_address::AccountAddress;
+use diem_genesis::config::{OperatorConfiguration, OwnerConfiguration};
+use diem_sdk::{
+    crypto::PrivateKey,
+    move_types::{ident_str, language_storage::ModuleId},
+};
+use diem_types::{
+    account_address::AccountAddress,
+    account_config::CORE_CODE_ADDRESS,
+    transaction::{EntryFunction, TransactionPayload},
+};
 use libra_cached_packages::libra_stdlib::EntryFunctionCall::{
     self, JailUnjailByVoucher, ProofOfFeePofRetractBid, ProofOfFeePofUpdateBid,
     ProofOfFeePofUpdateBidNetReward, StakeUpdateNetworkAndFullnodeAddresses,
     ValidatorUniverseRegisterValidator,
 };
 use libra_config::validator_registration;
-use libra_types::global_config_dir;
-use libra_wallet::validator_files::OPERATOR_FILE;
-use std::{fs, path::PathBuf};
+use libra_types::{
+    core_types::app_cfg::{AddressOrAlias, Aliases},
+    exports::{AuthenticationKey, Ed25519PrivateKey},
+    global_config_dir,
+    type_extensions::client_ext::ClientExt,
+};
+use libra_wallet::{
+    account_keys::get_keys_from_prompt,
+    key_rotation::{PendingRotation, RotationSource, PENDING_ROTATION_FILE},
+    validator_files::{read_operator_key, OPERATOR_FILE, OPERATOR_KEY_FILE, OWNER_FILE},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 
 #[derive(clap::Subcommand)]
 pub enum ValidatorTxs {
@@ -38,7 +61,7 @@ pub enum ValidatorTxs {
     Jail {
         #[clap(short, long)]
         /// Un-jail this validator. Used by any validators which are vouching for a validator which is jailed
-        unjail_acct: AccountAddress,
+        unjail_acct: AddressOrAlias,
     },
 
     // commit note: deduplicated in favor of txs_cli_user
@@ -54,17 +77,103 @@ pub enum ValidatorTxs {
         /// optional, Path to files with registration files
         operator_file: Option<PathBuf>,
     },
+    /// Hand the validator's operations to the operator key, so the owner's
+    /// mnemonic can be kept offline. Sent with the owner's key: the rotation
+    /// capability of the validator account is offered to a recovery account
+    /// the owner controls, then the validator account's authentication key
+    /// is rotated to the operator key. Bids, updates and key rotations are
+    /// then signed with the operator key. The recovery account takes the
+    /// validator account back with `libra txs user rotate-key --claim-address`
+    Delegate {
+        #[clap(long)]
+        /// the account offered the rotation capability, it must already
+        /// exist on chain and its keys should be kept offline
+        recovery_account: AddressOrAlias,
+
+        #[clap(long)]
+        /// the operator key, defaults to operator-account.key in the config dir
+        operator_key_file: Option<PathBuf>,
+    },
+    /// Rotate the consensus and/or network keys. The new keys are staged
+    /// locally, submitted on chain, and the local identity files are switched
+    /// once the next epoch begins, keeping a copy of the old files.
+    /// If interrupted, run again to resume with the same keys.
+    /// Note: --consensus needs a framework upgrade which makes
+    /// stake::rotate_consensus_key an entry function. Until the chain has it,
+    /// --consensus is refused before anything is submitted.
+    RotateKeys {
+        #[clap(long)]
+        /// rotate the BLS consensus key, needs the framework upgrade with the
+        /// stake::rotate_consensus_key entry function
+        consensus: bool,
+        #[clap(long)]
+        /// rotate the validator and fullnode network keys
+        network: bool,
+        #[clap(short, long, conflicts_with = "random")]
+        /// derive the new keys from the mnemonic at this rotation index. Use a
+        /// new index for each rotation, starting at 1
+        index: Option<u64>,
+        #[clap(long)]
+        /// use fresh random keys which cannot be recovered from the mnemonic
+        random: bool,
+        #[clap(long)]
+        /// optional, path to the validator key files, defaults to $HOME/.libra
+        home_path: Option<PathBuf>,
+        #[clap(long)]
+        /// submit the change without waiting for the epoch to end. Run the
+        /// command again after the epoch to switch the local files
+        no_wait: bool,
+    },
 }
 
 impl ValidatorTxs {
     pub async fn run(&self, sender: &mut Sender) -> anyhow::Result<()> {
-        let payload = self.make_payload()?;
+        if let ValidatorTxs::RotateKeys {
+            consensus,
+            network,
+            index,
+            random,
+            home_path,
+            no_wait,
+        } = self
+        {
+            let home = home_path.clone().unwrap_or_else(global_config_dir);
+            let source = if *random {
+                Some(RotationSource::Random)
+            } else {
+                index
+                    .map(|i| {
+                        println!("enter the validator's mnemonic to derive the new keys");
+                        get_keys_from_prompt().map(|k| RotationSource::Mnemonic {
+                            seed: k.seed,
+                            index: i,
+                        })
+                    })
+                    .transpose()?
+            };
+            return rotate_keys(sender, &home, source, *consensus, *network, *no_wait).await;
+        }
+
+        if let ValidatorTxs::Delegate {
+            recovery_account,
+            operator_key_file,
+        } = self
+        {
+            let recovery = sender.resolve(recovery_account)?;
+            let key_file = operator_key_file
+                .clone()
+                .unwrap_or_else(|| global_config_dir().join(OPERATOR_KEY_FILE));
+            let operator_key = read_operator_key(&key_file)?;
+            return delegate(sender, recovery, &operator_key).await;
+        }
+
+        let payload = self.make_payload(sender.aliases())?;
         sender.sign_submit_wait(payload.encode()).await?;
         Ok(())
     }
 
     //  Create the Entry function which the txs will run.
-    pub fn make_payload(&self) -> anyhow::Result<EntryFunctionCall> {
+    pub fn make_payload(&self, aliases: &Aliases) -> anyhow::Result<EntryFunctionCall> {
         let p = match self {
             ValidatorTxs::Pof {
                 net_reward,
@@ -97,7 +206,7 @@ impl ValidatorTxs {
                 }
             }
             ValidatorTxs::Jail { unjail_acct } => JailUnjailByVoucher {
-                addr: unjail_acct.to_owned(),
+                addr: unjail_acct.resolve(aliases)?,
             },
 
             // commit note: deduplicated in favor of txs_cli_user
@@ -112,13 +221,16 @@ impl ValidatorTxs {
                     fullnode_addresses: reg.fullnode_addresses,
                 }
             }
+            ValidatorTxs::RotateKeys { .. } | ValidatorTxs::Delegate { .. } => {
+                bail!("this is several transactions, use `run`")
+            }
             ValidatorTxs::Update { operator_file } => {
                 let file = operator_file.to_owned().unwrap_or_else(|| {
                     let a = global_config_dir();
                     a.join(OPERATOR_FILE)
                 });
 
-                let yaml_str = fs::read_to_string(file)?;
+                let yaml_str = fs::read_to_string(&file)?;
 
                 let oc: OperatorConfiguration = serde_yaml::from_str(&yaml_str)?;
 
@@ -134,8 +246,17 @@ impl ValidatorTxs {
                         "cannot find fullnode network public key operator config file",
                     )?)?;
 
+                // the validator is the owner's account
+                let owner_file = file.with_file_name(OWNER_FILE);
+                let validator_address = match fs::read_to_string(&owner_file) {
+                    Ok(s) => serde_yaml::from_str::<OwnerConfiguration>(&s)?
+                        .owner_account_address
+                        .into(),
+                    Err(_) => oc.operator_account_address.into(),
+                };
+
                 StakeUpdateNetworkAndFullnodeAddresses {
-                    validator_address: oc.operator_account_address.into(),
+                    validator_address,
                     new_network_addresses: bcs::to_bytes(&vec![val_net_protocol])?,
                     new_fullnode_addresses: bcs::to_bytes(&vec![vfn_fullnode_protocol])?,
                 }
@@ -145,3 +266,199 @@ impl ValidatorTxs {
         Ok(p)
     }
 }
+
+/// how often to check if the epoch has changed
+const EPOCH_POLL_SECS: u64 = 60;
+
+/// Consensus key rotation needs a framework upgrade, the released frameworks
+/// don't have `stake::rotate_consensus_key` as an entry function
+async fn has_consensus_rotation(sender: &Sender) -> anyhow::Result<bool> {
+    let stake = sender
+        .client()
+        .get_account_module(CORE_CODE_ADDRESS, "stake")
+        .await?
+        .into_inner()
+        .try_parse_abi()?;
+    Ok(stake.abi.is_some_and(|m| {
+        m.exposed_functions
+            .iter()
+            .any(|f| f.is_entry && f.name.to_string() == "rotate_consensus_key")
+    }))
+}
+
+/// `stake::rotate_consensus_key`, which is not in the released framework's
+/// sdk builder
+fn rotate_consensus_key_payload(
+    validator_address: AccountAddress,
+    new_consensus_pubkey: Vec<u8>,
+    proof_of_possession: Vec<u8>,
+) -> anyhow::Result<TransactionPayload> {
+    Ok(TransactionPayload::EntryFunction(EntryFunction::new(
+        ModuleId::new(CORE_CODE_ADDRESS, ident_str!("stake").to_owned()),
+        ident_str!("rotate_consensus_key").to_owned(),
+        vec![],
+        vec![
+            bcs::to_bytes(&validator_address)?,
+            bcs::to_bytes(&new_consensus_pubkey)?,
+            bcs::to_bytes(&proof_of_possession)?,
+        ],
+    )))
+}
+
+/// Sent with the owner's key. Offer the rotation capability to the recovery
+/// account, then rotate the validator account's authentication key to the
+/// operator key.
+async fn delegate(
+    sender: &mut Sender,
+    recovery: AccountAddress,
+    operator_key: &Ed25519PrivateKey,
+) -> anyhow::Result<()> {
+    let validator = sender.local_account.address();
+    if recovery == validator {
+        bail!("the recovery account must be another account than the validator");
+    }
+    if sender.client().get_account(recovery).await.is_err() {
+        bail!("the recovery account {recovery} does not exist yet, send it some coins first");
+    }
+    if AuthenticationKey::ed25519(&operator_key.public_key())
+        == sender.local_account.authentication_key()
+    {
+        bail!("{validator} already signs with the operator key");
+    }
+
+    // the offer must be in place before the owner's key stops working
+    let chain_id = sender.client().get_index().await?.into_inner().chain_id;
+    let seq = sender.client().get_sequence_number(validator).await?;
+    let payload = offer_rotation_capability_v2(&sender.local_account, recovery, chain_id, seq)?;
+    sender.sign_submit_wait(payload).await?;
+    println!("{recovery} can now rotate the authentication key of {validator}");
+
+    let seq = sender.client().get_sequence_number(validator).await?;
+    let payload = rotate_key(
+        validator,
+        sender.local_account.private_key().to_owned(),
+        sender.local_account.authentication_key(),
+        seq,
+        operator_key,
+    )?;
+    sender.sign_submit_wait(payload).await?;
+    println!("{validator} is now operated with the operator key");
+    println!("on the node host, sign validator transactions with the operator key, e.g. `--secret-source file:PATH`");
+    println!("to take the account back, from {recovery}: `libra txs user rotate-key --claim-address {validator}`");
+    Ok(())
+}
+
+async fn current_epoch(sender: &Sender) -> anyhow::Result<u64> {
+    Ok(sender.client().get_index().await?.into_inner().epoch.into())
+}
+
+/// Stage new keys, submit them on chain, wait for the epoch boundary, then
+/// switch the local identity files.
+async fn rotate_keys(
+    sender: &mut Sender,
+    home: &Path,
+    source: Option<RotationSource>,
+    consensus: bool,
+    network: bool,
+    no_wait: bool,
+) -> anyhow::Result<()> {
+    let mut pending = match PendingRotation::load(home)? {
+        Some(p) => {
+            println!(
+                "resuming the key rotation staged in {}",
+                PENDING_ROTATION_FILE
+            );
+            p
+        }
+        None => {
+            if !consensus && !network {
+                bail!("choose the keys to rotate with --consensus and/or --network");
+            }
+            if consensus && !has_consensus_rotation(sender).await? {
+                bail!("this chain's framework cannot rotate consensus keys yet, it needs the upgrade which makes stake::rotate_consensus_key an entry function. Use --network only");
+            }
+            let source = source.context("choose a rotation --index, or --random keys")?;
+            let p =
+                PendingRotation::new(sender.local_account.address(), &source, consensus, network)?;
+            p.save(home)?;
+            println!(
+                "new keys staged in {}",
+                home.join(PENDING_ROTATION_FILE).display()
+            );
+            p
+        }
+    };
+
+    if pending.submitted_epoch.is_none() {
+        let epoch = current_epoch(sender).await?;
+
+        // on a retry after the network update failed, the consensus key is
+        // already on chain
+        let consensus = pending
+            .consensus_registration()
+            .filter(|_| !pending.consensus_submitted);
+        if let Some((pubkey, pop)) = consensus {
+            if !has_consensus_rotation(sender).await? {
+                bail!("this chain's framework cannot rotate consensus keys yet, it needs the upgrade which makes stake::rotate_consensus_key an entry function");
+            }
+            let payload = rotate_consensus_key_payload(
+                pending.account,
+                pubkey.to_bytes().to_vec(),
+                pop.to_bytes().to_vec(),
+            )?;
+            sender.sign_submit_wait(payload).await?;
+            pending.consensus_submitted = true;
+            pending.save(home)?;
+            println!("consensus key submitted");
+        }
+
+        if let (Some(val_key), Some(vfn_key)) = (
+            pending.validator_network_public_key(),
+            pending.full_node_network_public_key(),
+        ) {
+            let oc: OperatorConfiguration =
+                serde_yaml::from_str(&fs::read_to_string(home.join(OPERATOR_FILE))?)?;
+            let fullnode_host = oc
+                .full_node_host
+                .context("cannot find fullnode host in operator config file")?;
+            let payload = StakeUpdateNetworkAndFullnodeAddresses {
+                validator_address: pending.account,
+                new_network_addresses: bcs::to_bytes(&vec![oc
+                    .validator_host
+                    .as_network_address(val_key)?])?,
+                new_fullnode_addresses: bcs::to_bytes(&vec![
+                    fullnode_host.as_network_address(vfn_key)?
+                ])?,
+            };
+            sender.sign_submit_wait(payload.encode()).await?;
+            println!("network addresses with the new keys submitted");
+        }
+
+        pending.submitted_epoch = Some(epoch);
+        pending.save(home)?;
+    }
+
+    let submitted = pending.submitted_epoch.expect("submitted epoch is set");
+    if no_wait && current_epoch(sender).await? <= submitted {
+        println!(
+            "the new keys take effect after epoch {submitted}, run this command again then to switch the local files"
+        );
+        return Ok(());
+    }
+
+    // the node must keep the old keys until the new ones are active
+    while current_epoch(sender).await? <= submitted {
+        println!("waiting for epoch {} to end...", submitted);
+        tokio::time::sleep(std::time::Duration::from_secs(EPOCH_POLL_SECS)).await;
+    }
+
+    let rollback = pending.apply(home)?;
+    println!("identity files switched to the new keys");
+    println!("NOTE: restart the validator and VFN now to load the new keys");
+    println!(
+        "the previous files are kept in {}, copy them back to {} to roll back",
+        rollback.display(),
+        home.display()
+    );
+    Ok(())
+}
@@ -853,14 +853,15 @@ async fn add_community_wallet_admin() -> Result<(), anyhow::Error> {
 
     let cli_add_new_admin_proposal = TxsCli {
         subcommand: Some(TxsSub::Community(CommunityTxs::GovAdmin(AdminTx {
-            community_wallet: comm_wallet_addr,
-            admin: new_admin_address,
+            community_wallet: comm_wallet_addr.into(),
+            admin: new_admin_address.into(),
             drop: Some(true),
             n: 3,
             epochs: Some(10),
         }))),
         mnemonic: None,
         test_private_key: Some(private_key_of_first_signer),
+        secret_source: None,
         chain_name: None,
         config_path: Some(dir.path().to_owned().join("libra-cli-config.yaml")),
         url: Some(api_endpoint.clone()),
@@ -913,14 +914,15 @@ async fn add_community_wallet_admin() -> Result<(), anyhow::Error> {
             .expect("cannot decode pri key");
         let cli_add_new_admin_proposal = TxsCli {
             subcommand: Some(TxsSub::Community(CommunityTxs::GovAdmin(AdminTx {
-                community_wallet: comm_wallet_addr,
-                admin: new_admin_address,
+                community_wallet: comm_wallet_addr.into(),
+                admin: new_admin_address.into(),
                 drop: Some(true),
                 n: 3,
                 epochs: Some(10),
             }))),
             mnemonic: None,
             test_private_key: Some(private_key_of_signer),
+            secret_source: None,
             chain_name: None,
             config_path: Some(dir.path().to_owned().join("libra-cli-config.yaml")),
             url: Some(api_endpoint.clone()),
@@ -1091,14 +1093,15 @@ async fn remove_community_wallet_admin() -> Result<(), anyhow::Error> {
 
     let cli_add_new_admin_proposal = TxsCli {
         subcommand: Some(TxsSub::Community(CommunityTxs::GovAdmin(AdminTx {
-            community_wallet: comm_wallet_addr,
-            admin: admin_to_remove,
+            community_wallet: comm_wallet_addr.into(),
+            admin: admin_to_remove.into(),
             drop: Some(false),
             n: 2,
             epochs: Some(10),
         }))),
         mnemonic: None,
         test_private_key: Some(private_key_of_first_signer),
+        secret_source: None,
         chain_name: None,
         config_path: Some(dir.path().to_owned().join("libra-cli-config.yaml")),
         url: Some(api_endpoint.clone()),
@@ -1152,14 +1155,15 @@ async fn remove_community_wallet_admin() -> Result<(), anyhow::Error> {
             .expect("cannot decode pri key");
         let cli_add_new_admin_proposal = TxsCli {
             subcommand: Some(TxsSub::Community(CommunityTxs::GovAdmin(AdminTx {
-                community_wallet: comm_wallet_addr,
-                admin: admin_to_remove,
+                community_wallet: comm_wallet_addr.into(),
+                admin: admin_to_remove.into(),
                 drop: Some(false),
                 n: 2,
                 epochs: Some(10),
             }))),
             mnemonic: None,
             test_private_key: Some(private_key_of_signer),
+            secret_source: None,
             chain_name: None,
             config_path: Some(dir.path().to_owned().join("libra-cli-config.yaml")),
             url: Some(api_endpoint.clone()),
@@ -1536,11 +1540,12 @@ async fn setup_environment() -> (LibraSmoke, TempPath, AccountAddress, String, A
     // Transfer funds to ensure the account exists on-chain
     let cli_transfer = TxsCli {
         subcommand: Some(Transfer {
-            to_account: account_address_wrapped,
+            to_account: account_address_wrapped.into(),
             amount: 100.0,
         }),
         mnemonic: None,
         test_private_key: Some(s.encoded_pri_key.clone()),
+        secret_source: None,
         chain_name: None,
         config_path: Some(dir.path().to_owned().join("libra-cli-config.yaml")),
         url: Some(s.api_endpoint.clone()),
@@ -1589,9 +1594,13 @@ async fn run_cli_transfer(
 ) {
     // Build the CLI command
     let cli_transfer = TxsCli {
-        subcommand: Some(Transfer { to_account, amount }),
+        subcommand: Some(Transfer {
+            to_account: to_account.into(),
+            amount,
+        }),
         mnemonic: None,
         test_private_key: Some(private_key),
+        secret_source: None,
         chain_name: None,
         config_path: Some(config_path),
         url: Some(api_endpoint),
@@ -1618,11 +1627,12 @@ async fn run_cli_community_init(
     // Build the CLI command
     let cli_set_community_wallet = TxsCli {
         subcommand: Some(TxsSub::Community(CommunityTxs::GovInit(InitTx {
-            admins,
+            admins: admins.into_iter().map(Into::into).collect(),
             num_signers,
         }))),
         mnemonic: None,
         test_private_key: Some(donor_private_key),
+        secret_source: None,
         chain_name: None,
         config_path: Some(config_path),
         url: Some(api_endpoint),
@@ -1647,10 +1657,11 @@ async fn run_cli_claim_offer(
 ) {
     let cli_claim_offer = TxsCli {
         subcommand: Some(TxsSub::Community(CommunityTxs::GovClaim(ClaimTx {
-            community_wallet: community_address,
+            community_wallet: community_address.into(),
         }))),
         mnemonic: None,
         test_private_key: Some(signer_pk),
+        secret_source: None,
         chain_name: None,
         config_path: Some(config_path),
         url: Some(api_endpoint),
@@ -1678,6 +1689,7 @@ async fn run_cli_community_cage(
         }))),
         mnemonic: None,
         test_private_key: Some(donor_private_key),
+        secret_source: None,
         chain_name: None,
         config_path: Some(config_path),
         url: Some(api_endpoint),
@@ -1702,11 +1714,12 @@ async fn run_cli_community_propose_offer(
 ) {
     let cli_propose_offer = TxsCli {
         subcommand: Some(TxsSub::Community(CommunityTxs::GovOffer(OfferTx {
-            admins,
+            admins: admins.into_iter().map(Into::into).collect(),
             num_signers,
         }))),
         mnemonic: None,
         test_private_key: Some(donor_private_key),
+        secret_source: None,
         chain_name: None,
         config_path: Some(config_path),
         url: Some(api_endpoint),
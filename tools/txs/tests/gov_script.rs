@@ -38,6 +38,7 @@ async fn smoke_gov_script() {
         })),
         mnemonic: None,
         test_private_key: Some(s.encoded_pri_key.clone()),
+        secret_source: None,
         chain_name: None,
         config_path: Some(d.path().to_owned().join("libra-cli-config.yaml")),
         url: Some(s.api_endpoint.clone()),
@@ -144,7 +144,7 @@ async fn offer_rotation_capability() -> anyhow::Result<()> {
 
     // allow bob to rotate keys for alice
     let cli = RotationCapabilityTx {
-        delegate_address: bob_sender.local_account.address(),
+        delegate_address: bob_sender.local_account.address().into(),
         revoke: false,
     };
 
@@ -165,7 +165,7 @@ async fn offer_rotation_capability() -> anyhow::Result<()> {
 
     let cli = RotateKeyTx {
         new_private_key: Some(generated_private_key_encoded.unwrap()),
-        claim_address: Some(alice_acct.to_owned()),
+        claim_address: Some(alice_acct.to_owned().into()),
     };
 
     let res_rotation = cli.run(&mut bob_sender, false).await;
@@ -245,7 +245,7 @@ async fn revoke_rotation_capability() -> anyhow::Result<()> {
 
     // allow bob to rotate keys for alice
     let cli = RotationCapabilityTx {
-        delegate_address: bob_sender.local_account.address(),
+        delegate_address: bob_sender.local_account.address().into(),
         revoke: false,
     };
 
@@ -260,7 +260,7 @@ async fn revoke_rotation_capability() -> anyhow::Result<()> {
 
     // revoke rotation capability from bob
     let cli = RotationCapabilityTx {
-        delegate_address: bob_sender.local_account.address(),
+        delegate_address: bob_sender.local_account.address().into(),
         revoke: true,
     };
 
@@ -281,7 +281,7 @@ async fn revoke_rotation_capability() -> anyhow::Result<()> {
 
     let cli = RotateKeyTx {
         new_private_key: Some(generated_private_key_encoded.unwrap()),
-        claim_address: Some(alice_acct.to_owned()),
+        claim_address: Some(alice_acct.to_owned().into()),
     };
 
     let res_rotation = cli.run(&mut bob_sender, false).await;
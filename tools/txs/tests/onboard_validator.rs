@@ -40,11 +40,12 @@ async fn smoke_onboard_validator() -> anyhow::Result<()> {
     // 1. CREATE THE ACCOUNT
     let alice_cli = TxsCli {
         subcommand: Some(Transfer {
-            to_account: new_val_address,
+            to_account: new_val_address.into(),
             amount: 1.0,
         }),
         mnemonic: None,
         test_private_key: Some(s.encoded_pri_key.clone()),
+        secret_source: None,
         chain_name: None,
         config_path: Some(d.path().to_owned().join("libra-cli-config.yaml")),
         url: Some(s.api_endpoint.clone()),
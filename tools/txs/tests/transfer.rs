@@ -31,11 +31,12 @@ async fn smoke_transfer_existing_account() {
     let recipient = s.swarm.validators().nth(1).unwrap().peer_id(); // sending to second genesis node.
     let cli = TxsCli {
         subcommand: Some(Transfer {
-            to_account: recipient,
+            to_account: recipient.into(),
             amount: 1.0,
         }),
         mnemonic: None,
         test_private_key: Some(s.encoded_pri_key.clone()),
+        secret_source: None,
         chain_name: None,
         config_path: Some(d.path().to_owned().join("libra-cli-config.yaml")),
         url: Some(s.api_endpoint.clone()),
@@ -71,11 +72,12 @@ async fn smoke_transfer_create_account() -> Result<(), anyhow::Error> {
     // case 2. Account does not yet exist.
     let cli = TxsCli {
         subcommand: Some(Transfer {
-            to_account: marlon,
+            to_account: marlon.into(),
             amount: 1.0,
         }),
         mnemonic: None,
         test_private_key: Some(s.encoded_pri_key.clone()),
+        secret_source: None,
         chain_name: None,
         config_path: Some(d.path().to_owned().join("libra-cli-config.yaml")),
         url: Some(s.api_endpoint.clone()),
@@ -114,11 +116,12 @@ async fn smoke_transfer_estimate() {
     // case 2. Account does not yet exist.
     let cli = TxsCli {
         subcommand: Some(Transfer {
-            to_account: s.marlon_rando().address(),
+            to_account: s.marlon_rando().address().into(),
             amount: 1.0,
         }),
         mnemonic: None,
         test_private_key: Some(s.encoded_pri_key.clone()),
+        secret_source: None,
         chain_name: None,
         config_path: Some(d.path().to_owned().join("libra-cli-config.yaml")),
         url: Some(s.api_endpoint.clone()),
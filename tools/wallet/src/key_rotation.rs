@@ -0,0 +1,339 @@
+//! Rotate a validator's consensus and network keys.
+//!
+//! The new keys are staged in a pending file before anything is submitted, so
+//! an interrupted rotation can be resumed with the same keys. Only after the
+//! on-chain change takes effect (the next epoch) are the identity files
+//! switched, and a copy of the old files is kept to roll back.
+
+use crate::{
+    keys::{PRIVATE_KEYS_FILE, PUBLIC_KEYS_FILE, VALIDATOR_FILE, VFN_FILE},
+    utils::{from_yaml, read_from_file, to_yaml, write_to_user_only_file},
+    validator_files::OPERATOR_FILE,
+};
+use anyhow::{anyhow, Context};
+use diem_config::config::IdentityBlob;
+use diem_crypto::{
+    bls12381, compat::Sha3_256, hkdf::Hkdf, traits::PrivateKey, x25519, ValidCryptoMaterial,
+};
+use diem_genesis::{
+    config::OperatorConfiguration,
+    keys::{PrivateIdentity, PublicIdentity},
+};
+use libra_types::exports::AccountAddress;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// the staged keys of a rotation in progress
+pub const PENDING_ROTATION_FILE: &str = "pending-key-rotation.yaml";
+/// directory where the replaced identity files are kept
+pub const ROLLBACK_DIR: &str = "key-rollback";
+
+const CONSENSUS_ROTATION_INFO: &[u8] = b"0L VALIDATOR KEY ROTATION: consensus$";
+const VAL_NETWORK_ROTATION_INFO: &[u8] = b"0L VALIDATOR KEY ROTATION: validator network$";
+const VFN_NETWORK_ROTATION_INFO: &[u8] = b"0L VALIDATOR KEY ROTATION: fullnode network$";
+
+/// the files a rotation may change
+const ROTATED_FILES: [&str; 5] = [
+    VALIDATOR_FILE,
+    VFN_FILE,
+    PRIVATE_KEYS_FILE,
+    PUBLIC_KEYS_FILE,
+    OPERATOR_FILE,
+];
+
+/// Where new keys come from
+pub enum RotationSource {
+    /// Derived from the mnemonic seed at a rotation index, so the keys can be
+    /// recovered from the mnemonic. Use a new index for each rotation.
+    Mnemonic { seed: Vec<u8>, index: u64 },
+    /// Fresh random keys. These must be backed up, they cannot be recovered.
+    Random,
+}
+
+impl RotationSource {
+    fn key_material(&self, info: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match self {
+            RotationSource::Mnemonic { seed, index } => {
+                let mut info = info.to_vec();
+                info.extend_from_slice(&index.to_le_bytes());
+                Hkdf::<Sha3_256>::expand(seed, Some(&info), 32)
+                    .map_err(|e| anyhow!("cannot derive rotation key: {:?}", e))
+            }
+            RotationSource::Random => {
+                let mut bytes = vec![0u8; 32];
+                rand::rngs::OsRng.fill_bytes(&mut bytes);
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+/// Keys staged for a rotation. Only the keys being rotated are set.
+#[derive(Serialize, Deserialize)]
+pub struct PendingRotation {
+    pub account: AccountAddress,
+    pub consensus_private_key: Option<bls12381::PrivateKey>,
+    pub validator_network_private_key: Option<x25519::PrivateKey>,
+    pub full_node_network_private_key: Option<x25519::PrivateKey>,
+    /// the consensus key is on chain, a retry after a failed network
+    /// update must not send it again
+    #[serde(default)]
+    pub consensus_submitted: bool,
+    /// the epoch the on-chain change was submitted in, the local files are
+    /// switched once the chain is past it
+    pub submitted_epoch: Option<u64>,
+}
+
+impl PendingRotation {
+    pub fn new(
+        account: AccountAddress,
+        source: &RotationSource,
+        consensus: bool,
+        network: bool,
+    ) -> anyhow::Result<Self> {
+        let consensus_private_key = if consensus {
+            let ikm = source.key_material(CONSENSUS_ROTATION_INFO)?;
+            let sk = blst::min_pk::SecretKey::key_gen(&ikm, &[])
+                .map_err(|e| anyhow!("blst key gen failed: {:?}", e))?;
+            Some(bls12381::PrivateKey::try_from(sk.to_bytes().as_ref())?)
+        } else {
+            None
+        };
+
+        let (validator_network_private_key, full_node_network_private_key) = if network {
+            let v = source.key_material(VAL_NETWORK_ROTATION_INFO)?;
+            let f = source.key_material(VFN_NETWORK_ROTATION_INFO)?;
+            (
+                Some(x25519::PrivateKey::try_from(v.as_slice())?),
+                Some(x25519::PrivateKey::try_from(f.as_slice())?),
+            )
+        } else {
+            (None, None)
+        };
+
+        Ok(Self {
+            account,
+            consensus_private_key,
+            validator_network_private_key,
+            full_node_network_private_key,
+            consensus_submitted: false,
+            submitted_epoch: None,
+        })
+    }
+
+    /// the public consensus key and its proof of possession, for the
+    /// on-chain update
+    pub fn consensus_registration(
+        &self,
+    ) -> Option<(bls12381::PublicKey, bls12381::ProofOfPossession)> {
+        self.consensus_private_key
+            .as_ref()
+            .map(|k| (k.public_key(), bls12381::ProofOfPossession::create(k)))
+    }
+
+    pub fn validator_network_public_key(&self) -> Option<x25519::PublicKey> {
+        self.validator_network_private_key
+            .as_ref()
+            .map(|k| k.public_key())
+    }
+
+    pub fn full_node_network_public_key(&self) -> Option<x25519::PublicKey> {
+        self.full_node_network_private_key
+            .as_ref()
+            .map(|k| k.public_key())
+    }
+
+    /// load a rotation in progress, if any
+    pub fn load(home: &Path) -> anyhow::Result<Option<Self>> {
+        let path = home.join(PENDING_ROTATION_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bytes = read_from_file(&path)?;
+        Ok(Some(from_yaml(&String::from_utf8(bytes)?)?))
+    }
+
+    pub fn save(&self, home: &Path) -> anyhow::Result<()> {
+        write_to_user_only_file(
+            &home.join(PENDING_ROTATION_FILE),
+            PENDING_ROTATION_FILE,
+            to_yaml(self)?.as_bytes(),
+        )
+    }
+
+    /// Switch the local identity files to the new keys. The current files are
+    /// first copied to a rollback directory, then the new files are all
+    /// written before any of them is moved into place.
+    /// Returns the rollback directory.
+    pub fn apply(&self, home: &Path) -> anyhow::Result<PathBuf> {
+        let mut val_blob: IdentityBlob = read_yaml(&home.join(VALIDATOR_FILE))?;
+        let mut vfn_blob: IdentityBlob = read_yaml(&home.join(VFN_FILE))?;
+        let mut private_id: PrivateIdentity = read_yaml(&home.join(PRIVATE_KEYS_FILE))?;
+        let mut public_id: PublicIdentity = read_yaml(&home.join(PUBLIC_KEYS_FILE))?;
+        let operator_path = home.join(OPERATOR_FILE);
+        let mut operator: Option<OperatorConfiguration> = if operator_path.exists() {
+            Some(read_yaml(&operator_path)?)
+        } else {
+            None
+        };
+
+        if let Some(k) = &self.consensus_private_key {
+            let (pubkey, pop) = self.consensus_registration().expect("consensus key is set");
+            val_blob.consensus_private_key = Some(copy_key(k));
+            private_id.consensus_private_key = copy_key(k);
+            public_id.consensus_public_key = Some(pubkey.clone());
+            public_id.consensus_proof_of_possession = Some(pop.clone());
+            if let Some(o) = operator.as_mut() {
+                o.consensus_public_key = pubkey;
+                o.consensus_proof_of_possession = pop;
+            }
+        }
+
+        if let Some(k) = &self.validator_network_private_key {
+            val_blob.network_private_key = copy_key(k);
+            private_id.validator_network_private_key = copy_key(k);
+            public_id.validator_network_public_key = Some(k.public_key());
+            if let Some(o) = operator.as_mut() {
+                o.validator_network_public_key = k.public_key();
+            }
+        }
+
+        if let Some(k) = &self.full_node_network_private_key {
+            // the VFN address is derived from its network key, see
+            // generate_key_objects_from_legacy
+            vfn_blob.account_address = Some(k.public_key().to_string().parse()?);
+            vfn_blob.network_private_key = copy_key(k);
+            private_id.full_node_network_private_key = copy_key(k);
+            public_id.full_node_network_public_key = Some(k.public_key());
+            if let Some(o) = operator.as_mut() {
+                o.full_node_network_public_key = Some(k.public_key());
+            }
+        }
+
+        let rollback = self.backup(home)?;
+
+        let mut staged = vec![
+            (VALIDATOR_FILE, to_yaml(&val_blob)?),
+            (VFN_FILE, to_yaml(&vfn_blob)?),
+            (PRIVATE_KEYS_FILE, to_yaml(&private_id)?),
+            (PUBLIC_KEYS_FILE, to_yaml(&public_id)?),
+        ];
+        if let Some(o) = &operator {
+            staged.push((OPERATOR_FILE, to_yaml(o)?));
+        }
+
+        for (name, yaml) in &staged {
+            write_to_user_only_file(&staged_path(home, name), name, yaml.as_bytes())?;
+        }
+        for (name, _) in &staged {
+            std::fs::rename(staged_path(home, name), home.join(name))
+                .context(format!("could not move new {name} into place"))?;
+        }
+
+        std::fs::remove_file(home.join(PENDING_ROTATION_FILE))?;
+        Ok(rollback)
+    }
+
+    /// copy the current files to `key-rollback/epoch-<n>`
+    fn backup(&self, home: &Path) -> anyhow::Result<PathBuf> {
+        let label = match self.submitted_epoch {
+            Some(e) => format!("epoch-{e}"),
+            None => "manual".to_string(),
+        };
+        let dir = home.join(ROLLBACK_DIR).join(label);
+        std::fs::create_dir_all(&dir)?;
+        for name in ROTATED_FILES {
+            let src = home.join(name);
+            if src.exists() {
+                std::fs::copy(&src, dir.join(name))?;
+            }
+        }
+        Ok(dir)
+    }
+}
+
+/// restore identity files from a rollback directory made by `apply`
+pub fn rollback_identity_files(home: &Path, rollback_dir: &Path) -> anyhow::Result<()> {
+    for name in ROTATED_FILES {
+        let src = rollback_dir.join(name);
+        if src.exists() {
+            std::fs::copy(&src, home.join(name)).context(format!("could not restore {name}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// private keys are not Clone outside of tests
+fn copy_key<T: ValidCryptoMaterial>(k: &T) -> T {
+    T::try_from(k.to_bytes().as_slice()).expect("key bytes round trip")
+}
+
+fn staged_path(home: &Path, name: &str) -> PathBuf {
+    home.join(format!("{name}.new"))
+}
+
+fn read_yaml<T: serde::de::DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let bytes = read_from_file(path)?;
+    from_yaml(&String::from_utf8(bytes)?).context(format!("cannot parse {}", path.display()))
+}
+
+#[test]
+fn rotate_and_roll_back() {
+    use crate::{account_keys::get_keys_from_mnem, keys::generate_key_objects_from_legacy};
+
+    let alice_mnem = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    let keys = get_keys_from_mnem(alice_mnem.to_string()).unwrap();
+    let (val_blob, vfn_blob, private_id, public_id) =
+        generate_key_objects_from_legacy(&keys).unwrap();
+
+    let home = diem_temppath::TempPath::new();
+    home.create_as_dir().unwrap();
+    let home = home.path();
+    std::fs::write(home.join(VALIDATOR_FILE), to_yaml(&val_blob).unwrap()).unwrap();
+    std::fs::write(home.join(VFN_FILE), to_yaml(&vfn_blob).unwrap()).unwrap();
+    std::fs::write(home.join(PRIVATE_KEYS_FILE), to_yaml(&private_id).unwrap()).unwrap();
+    std::fs::write(home.join(PUBLIC_KEYS_FILE), to_yaml(&public_id).unwrap()).unwrap();
+
+    let source = RotationSource::Mnemonic {
+        seed: keys.seed.clone(),
+        index: 1,
+    };
+    let pending = PendingRotation::new(keys.child_0_owner.account, &source, true, true).unwrap();
+
+    // derivation is deterministic per index, and differs from the original keys
+    let again = PendingRotation::new(keys.child_0_owner.account, &source, true, true).unwrap();
+    assert_eq!(
+        pending.consensus_registration().unwrap().0,
+        again.consensus_registration().unwrap().0
+    );
+    assert_ne!(
+        Some(pending.consensus_registration().unwrap().0),
+        public_id.consensus_public_key
+    );
+
+    // a partly submitted rotation resumes where it stopped
+    let mut pending = pending;
+    pending.consensus_submitted = true;
+    pending.save(home).unwrap();
+    let loaded = PendingRotation::load(home).unwrap().unwrap();
+    assert!(loaded.consensus_submitted);
+    assert!(loaded.submitted_epoch.is_none());
+    let rollback = loaded.apply(home).unwrap();
+    assert!(!home.join(PENDING_ROTATION_FILE).exists());
+
+    let new_blob: IdentityBlob = read_yaml(&home.join(VALIDATOR_FILE)).unwrap();
+    assert_eq!(
+        new_blob.network_private_key.public_key(),
+        pending.validator_network_public_key().unwrap()
+    );
+    // the account key is untouched
+    assert_eq!(new_blob.account_address, val_blob.account_address);
+
+    rollback_identity_files(home, &rollback).unwrap();
+    let restored: IdentityBlob = read_yaml(&home.join(VALIDATOR_FILE)).unwrap();
+    assert_eq!(
+        restored.network_private_key.public_key(),
+        val_blob.network_private_key.public_key()
+    );
+}
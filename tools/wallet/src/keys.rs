@@ -24,12 +24,12 @@ use diem_genesis::keys::{PrivateIdentity, PublicIdentity};
 use std::path::{Path, PathBuf};
 
 // These are consistent with Vendor
-const PRIVATE_KEYS_FILE: &str = "private-keys.yaml";
+pub const PRIVATE_KEYS_FILE: &str = "private-keys.yaml";
 pub const PUBLIC_KEYS_FILE: &str = "public-keys.yaml";
 pub const VALIDATOR_FILE: &str = "validator-identity.yaml";
-const VFN_FILE: &str = "validator-full-node-identity.yaml";
+pub const VFN_FILE: &str = "validator-full-node-identity.yaml";
 // This is Libra specific
-const USER_FILE: &str = "danger-user-private-keys.yaml";
+pub const USER_FILE: &str = "danger-user-private-keys.yaml";
 
 // Generate new keys for user
 pub fn user_keygen(output_opt: Option<PathBuf>) -> anyhow::Result<()> {
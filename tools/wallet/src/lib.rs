@@ -3,9 +3,12 @@
 
 pub mod account_keys;
 pub mod core;
+pub mod key_rotation;
 pub mod key_gen;
 pub mod keys;
 pub mod load_keys;
+pub mod secret_source;
+pub mod sign_message;
 pub mod utils;
 pub mod validator_files;
 pub mod wallet_cli;
@@ -0,0 +1,244 @@
+//! Where the cli tools read a mnemonic or private key from.
+//! Passing secrets as plain arguments leaks them into the shell history and
+//! `ps` output, so all tools accept a `--secret-source` instead:
+//!   prompt          ask on the terminal (the default)
+//!   env:VAR         an environment variable
+//!   fd:N            an open file descriptor, e.g. from a pipe
+//!   file:PATH       a file which only the user can read
+//!   keystore[:DIR]  the keys saved by `keygen`, defaults to the config dir
+//! Environment variables are only read when asked for with env:VAR.
+//! Note the keystore is the plaintext yaml written by `keygen`, the mnemonic
+//! in it is not encrypted. It is only as safe as the file's permissions.
+
+use crate::{
+    account_keys::{get_keys_from_mnem, KeyChain},
+    keys::USER_FILE,
+    load_keys::get_account_from_prompt,
+};
+use anyhow::{bail, Context};
+use diem_crypto::{ed25519::Ed25519PrivateKey, ValidCryptoMaterialStringExt};
+use libra_types::global_config_dir;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// A secret as read from a source
+pub enum Secret {
+    Mnemonic(String),
+    PrivateKey(Ed25519PrivateKey),
+}
+
+impl Secret {
+    /// Keys are hex encoded, anything else is taken to be a mnemonic
+    fn parse(text: &str) -> Self {
+        let text = text.trim();
+        match Ed25519PrivateKey::from_encoded_string(text) {
+            Ok(k) => Secret::PrivateKey(k),
+            Err(_) => Secret::Mnemonic(text.to_owned()),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub enum SecretSource {
+    Prompt,
+    Env(String),
+    Fd(u32),
+    File(PathBuf),
+    /// a directory with the user keys file, or the file itself. The
+    /// mnemonic in it is stored unencrypted
+    Keystore(PathBuf),
+    /// a secret passed as a plain cli argument, kept for compatibility
+    Arg(String),
+}
+
+impl FromStr for SecretSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = s.split_once(':').unwrap_or((s, ""));
+        Ok(match (kind, value) {
+            ("prompt", "") => SecretSource::Prompt,
+            ("env", v) if !v.is_empty() => SecretSource::Env(v.to_owned()),
+            ("fd", v) => SecretSource::Fd(v.parse().context("fd: needs a descriptor number")?),
+            ("file", v) if !v.is_empty() => SecretSource::File(PathBuf::from(v)),
+            ("keystore", "") => SecretSource::Keystore(global_config_dir()),
+            ("keystore", v) => SecretSource::Keystore(PathBuf::from(v)),
+            _ => bail!(
+                "unknown secret source '{s}', use prompt, env:VAR, fd:N, file:PATH or keystore[:DIR]"
+            ),
+        })
+    }
+}
+
+// never print the secret itself
+impl fmt::Display for SecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SecretSource::Prompt => write!(f, "prompt"),
+            SecretSource::Env(v) => write!(f, "env:{v}"),
+            SecretSource::Fd(n) => write!(f, "fd:{n}"),
+            SecretSource::File(p) => write!(f, "file:{}", p.display()),
+            SecretSource::Keystore(p) => write!(f, "keystore:{}", p.display()),
+            SecretSource::Arg(_) => write!(f, "cli argument"),
+        }
+    }
+}
+
+impl fmt::Debug for SecretSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretSource({self})")
+    }
+}
+
+impl SecretSource {
+    /// Pick the source from a tool's cli flags. Secrets passed as plain
+    /// arguments are still accepted with a warning. Returns `None` when the
+    /// caller should fall back to its own default, e.g. the prompt or the
+    /// profile's test key.
+    pub fn from_args(
+        source: Option<&SecretSource>,
+        mnemonic: Option<&String>,
+        test_private_key: Option<&String>,
+    ) -> Option<SecretSource> {
+        if let Some(s) = source {
+            return Some(s.to_owned());
+        }
+        if let Some(a) = test_private_key.or(mnemonic) {
+            eprintln!(
+                "WARN: secrets passed as arguments end up in your shell history, use --secret-source instead"
+            );
+            return Some(SecretSource::Arg(a.to_owned()));
+        }
+        None
+    }
+
+    pub fn read(&self) -> anyhow::Result<Secret> {
+        let text = match self {
+            SecretSource::Prompt => {
+                let (_, _, wallet) = get_account_from_prompt();
+                return Ok(Secret::Mnemonic(wallet.mnemonic()));
+            }
+            SecretSource::Env(v) => {
+                std::env::var(v).context(format!("env variable {v} is not set"))?
+            }
+            SecretSource::Fd(n) => std::fs::read_to_string(format!("/dev/fd/{n}"))
+                .context(format!("cannot read file descriptor {n}"))?,
+            SecretSource::File(p) => {
+                check_user_only(p)?;
+                std::fs::read_to_string(p).context(format!("cannot read {}", p.display()))?
+            }
+            SecretSource::Keystore(p) => {
+                let path = if p.is_dir() {
+                    p.join(USER_FILE)
+                } else {
+                    p.to_owned()
+                };
+                check_user_only(&path)?;
+                let keys: serde_yaml::Value = serde_yaml::from_str(
+                    &std::fs::read_to_string(&path)
+                        .context(format!("cannot read keystore {}", path.display()))?,
+                )?;
+                keys.get("mnemonic")
+                    .and_then(|m| m.as_str())
+                    .context(format!("no mnemonic in keystore {}", path.display()))?
+                    .to_owned()
+            }
+            SecretSource::Arg(a) => a.to_owned(),
+        };
+        if text.trim().is_empty() {
+            bail!("no secret found in {self}");
+        }
+        Ok(Secret::parse(&text))
+    }
+
+    /// the account's signing key, from a private key or the mnemonic
+    pub fn account_key(&self) -> anyhow::Result<Ed25519PrivateKey> {
+        Ok(match self.read()? {
+            Secret::PrivateKey(k) => k,
+            Secret::Mnemonic(m) => get_keys_from_mnem(m)?.child_0_owner.pri_key,
+        })
+    }
+
+    /// For tools which derive more than the account key, e.g. validator keys
+    pub fn mnemonic(&self) -> anyhow::Result<String> {
+        match self.read()? {
+            Secret::Mnemonic(m) => Ok(m),
+            Secret::PrivateKey(_) => {
+                bail!("{self} holds a private key, but a mnemonic is needed here")
+            }
+        }
+    }
+
+    pub fn key_chain(&self) -> anyhow::Result<KeyChain> {
+        get_keys_from_mnem(self.mnemonic()?)
+    }
+}
+
+/// secret files must not be readable by other users
+fn check_user_only(path: &Path) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path)
+            .context(format!("cannot find {}", path.display()))?
+            .permissions()
+            .mode();
+        if mode & 0o077 != 0 {
+            bail!(
+                "{} can be read by other users, run `chmod 600 {}`",
+                path.display(),
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn secret_sources() {
+    use diem_crypto::PrivateKey;
+    use std::os::unix::fs::PermissionsExt;
+
+    assert_eq!(
+        SecretSource::from_str("env:MY_MNEM").unwrap(),
+        SecretSource::Env("MY_MNEM".to_owned())
+    );
+    assert_eq!(SecretSource::from_str("fd:3").unwrap(), SecretSource::Fd(3));
+    assert!(SecretSource::from_str("env:").is_err());
+    assert!(SecretSource::from_str("paste").is_err());
+
+    // the environment is never read unless asked for
+    std::env::set_var("LIBRA_MNEMONIC", "not used");
+    assert!(SecretSource::from_args(None, None, None).is_none());
+
+    // the secret is never displayed
+    let arg = SecretSource::Arg(
+        "0x74f18da2b80b1820b58116197b1c41f8a36e1b37a15c7fb434bb42dd7bdaa66b".to_owned(),
+    );
+    assert!(!format!("{arg:?}").contains("74f18da2"));
+    assert!(matches!(arg.read().unwrap(), Secret::PrivateKey(_)));
+    assert!(arg.mnemonic().is_err());
+
+    let dir = diem_temppath::TempPath::new();
+    dir.create_as_dir().unwrap();
+    let path = dir.path().join("mnem.txt");
+    let mnem = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    std::fs::write(&path, mnem).unwrap();
+    let file = SecretSource::File(path.clone());
+
+    // refuses files others can read
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+    assert!(file.read().is_err());
+
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+    assert_eq!(file.mnemonic().unwrap(), mnem);
+    let from_file = file.account_key().unwrap();
+    let from_mnem = get_keys_from_mnem(mnem.to_owned()).unwrap();
+    assert_eq!(
+        from_file.public_key(),
+        from_mnem.child_0_owner.pri_key.public_key()
+    );
+}
@@ -0,0 +1,246 @@
+//! Sign and verify arbitrary messages, to prove control of an account.
+use crate::{account_keys::get_account_from_private, secret_source::SecretSource};
+use anyhow::{bail, Context};
+use diem_crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519PublicKey, Ed25519Signature},
+    PrivateKey, Signature, SigningKey, ValidCryptoMaterialStringExt,
+};
+use libra_types::{
+    core_types::app_cfg::AppCfg,
+    exports::{AccountAddress, AuthenticationKey, Client},
+    type_extensions::client_ext::ClientExt,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use url::Url;
+
+/// Domain separation prefix prepended to every message before signing.
+/// This prevents a signed message from ever being replayed as a transaction
+/// or any other structure the same key may sign.
+pub const SIGNED_MESSAGE_PREFIX: &[u8] = b"LIBRA::SignedMessage::";
+
+/// Output of `libra wallet sign-message`, can be shared publicly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedMessage {
+    /// the account claiming to sign the message
+    pub account: AccountAddress,
+    /// the public key used to sign
+    pub public_key: Ed25519PublicKey,
+    /// the signature over the prefixed message
+    pub signature: Ed25519Signature,
+    /// the message as it was provided
+    pub message: String,
+    /// if the message was provided as hex
+    pub is_hex: bool,
+}
+
+/// decode the message from a UTF-8 string or a hex string
+pub fn message_bytes(message: &str, is_hex: bool) -> anyhow::Result<Vec<u8>> {
+    if is_hex {
+        let trimmed = message.trim_start_matches("0x");
+        return hex::decode(trimmed).context("message is not valid hex");
+    }
+    Ok(message.as_bytes().to_vec())
+}
+
+/// the bytes which are actually signed: the domain prefix and the message
+pub fn signing_bytes(message: &[u8]) -> Vec<u8> {
+    let mut bytes = SIGNED_MESSAGE_PREFIX.to_vec();
+    bytes.extend_from_slice(message);
+    bytes
+}
+
+/// sign a message with the private key of an account
+pub fn sign_message(
+    account: AccountAddress,
+    pri_key: &Ed25519PrivateKey,
+    message: &str,
+    is_hex: bool,
+) -> anyhow::Result<SignedMessage> {
+    let bytes = signing_bytes(&message_bytes(message, is_hex)?);
+    let signature = pri_key.sign_arbitrary_message(&bytes);
+
+    Ok(SignedMessage {
+        account,
+        public_key: pri_key.public_key(),
+        signature,
+        message: message.to_owned(),
+        is_hex,
+    })
+}
+
+/// check the signature against the public key, without looking up the chain
+pub fn verify_signature(signed: &SignedMessage) -> anyhow::Result<()> {
+    let bytes = signing_bytes(&message_bytes(&signed.message, signed.is_hex)?);
+    signed
+        .signature
+        .verify_arbitrary_msg(&bytes, &signed.public_key)
+        .context("signature does not match the public key and message")
+}
+
+/// check the signature, and that the public key is the one currently
+/// authorized for the account on chain. Accounts which have rotated keys will
+/// have an authentication key which differs from the address.
+pub async fn verify_message(client: &Client, signed: &SignedMessage) -> anyhow::Result<()> {
+    verify_signature(signed)?;
+
+    let onchain_auth_key = client
+        .get_account(signed.account)
+        .await
+        .context("could not fetch account from chain, does it exist?")?
+        .into_inner()
+        .authentication_key;
+
+    let signer_auth_key = AuthenticationKey::ed25519(&signed.public_key);
+    if onchain_auth_key != signer_auth_key {
+        bail!(
+            "public key does not control account {}, on-chain authentication key is {}, but the message was signed by {}",
+            signed.account,
+            onchain_auth_key,
+            signer_auth_key
+        );
+    }
+    Ok(())
+}
+
+/// find the key to sign with. Uses the secret source if one is given,
+/// otherwise the profile's key if there is one in the config, or prompts for
+/// a mnemonic.
+pub fn get_signing_key(
+    config_path: Option<PathBuf>,
+    profile: Option<String>,
+    secret: Option<SecretSource>,
+) -> anyhow::Result<(AccountAddress, Ed25519PrivateKey)> {
+    let maybe_profile = match AppCfg::load(config_path) {
+        Ok(cfg) => {
+            let p = cfg.get_profile(profile)?;
+            if secret.is_none() {
+                if let Ok(k) = p.borrow_private_key() {
+                    println!("using private key from libra-cli-config.yaml");
+                    return Ok((p.account, k.to_owned()));
+                }
+            }
+            Some((p.account, p.auth_key))
+        }
+        Err(_) => None,
+    };
+
+    let pri_key = secret.unwrap_or(SecretSource::Prompt).account_key()?;
+    Ok((signing_account(maybe_profile, &pri_key), pri_key))
+}
+
+/// The account a key signs for. The profile's account is only used if the key
+/// is the profile's authentication key, which may differ from the derived
+/// address if the keys were rotated. Otherwise the account is derived from the
+/// key.
+fn signing_account(
+    profile: Option<(AccountAddress, AuthenticationKey)>,
+    pri_key: &Ed25519PrivateKey,
+) -> AccountAddress {
+    let derived = get_account_from_private(pri_key);
+    match profile {
+        Some((account, auth_key)) if auth_key == derived.auth_key => account,
+        Some((account, _)) => {
+            println!(
+                "the key does not match the profile for {}, signing for its derived account {}",
+                account, derived.account
+            );
+            derived.account
+        }
+        None => derived.account,
+    }
+}
+
+/// get a client from the url or the config file
+pub async fn get_client(url: Option<Url>, config_path: Option<PathBuf>) -> anyhow::Result<Client> {
+    if let Some(u) = url {
+        return Ok(Client::new(u));
+    }
+    let app_cfg = AppCfg::load(config_path)?;
+    let (client, _) = Client::from_libra_config(&app_cfg, None).await?;
+    Ok(client)
+}
+
+/// parse the signature and public key from the hex strings given in the cli
+pub fn parse_signed_message(
+    account: AccountAddress,
+    public_key: &str,
+    signature: &str,
+    message: &str,
+    is_hex: bool,
+) -> anyhow::Result<SignedMessage> {
+    Ok(SignedMessage {
+        account,
+        public_key: Ed25519PublicKey::from_encoded_string(public_key)
+            .context("cannot parse public key")?,
+        signature: Ed25519Signature::from_encoded_string(signature)
+            .context("cannot parse signature")?,
+        message: message.to_owned(),
+        is_hex,
+    })
+}
+
+#[test]
+fn sign_and_verify_roundtrip() {
+    use crate::account_keys::get_keys_from_mnem;
+    let alice_mnem = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    let keys = get_keys_from_mnem(alice_mnem.to_string()).unwrap();
+    let acc = keys.child_0_owner.account;
+
+    let signed = sign_message(acc, &keys.child_0_owner.pri_key, "hello genesis", false).unwrap();
+    verify_signature(&signed).unwrap();
+
+    // the message cannot be tampered with
+    let mut tampered = signed.clone();
+    tampered.message = "hello genesis!".to_string();
+    assert!(verify_signature(&tampered).is_err());
+
+    // the same bytes as hex produce the same signature
+    let hex_msg = hex::encode("hello genesis");
+    let signed_hex = sign_message(acc, &keys.child_0_owner.pri_key, &hex_msg, true).unwrap();
+    assert_eq!(signed.signature, signed_hex.signature);
+
+    // the signature is not over the raw message, but the prefixed one
+    assert!(signed
+        .signature
+        .verify_arbitrary_msg(b"hello genesis", &signed.public_key)
+        .is_err());
+
+    // round trip through the cli string encodings
+    let parsed = parse_signed_message(
+        acc,
+        &signed.public_key.to_encoded_string().unwrap(),
+        &signed.signature.to_encoded_string().unwrap(),
+        "hello genesis",
+        false,
+    )
+    .unwrap();
+    verify_signature(&parsed).unwrap();
+}
+
+#[test]
+fn signing_account_matches_profile_key() {
+    use crate::account_keys::get_keys_from_mnem;
+    use std::str::FromStr;
+    let alice_mnem = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    let alice = get_keys_from_mnem(alice_mnem.to_string())
+        .unwrap()
+        .child_0_owner;
+    let rotated = AccountAddress::from_hex_literal("0x1234").unwrap();
+
+    // a rotated account in the profile, with this key as its auth key
+    assert_eq!(
+        signing_account(Some((rotated, alice.auth_key)), &alice.pri_key),
+        rotated
+    );
+    // a profile for another key, the account comes from the key
+    let other = AuthenticationKey::from_str(
+        "8603ba96e87b810cebbec1a0fd7ea06285f9eb352a3eabde992a5594fe80af40",
+    )
+    .unwrap();
+    assert_eq!(
+        signing_account(Some((rotated, other)), &alice.pri_key),
+        alice.account
+    );
+    assert_eq!(signing_account(None, &alice.pri_key), alice.account);
+}
@@ -1,6 +1,11 @@
-use anyhow::{bail, Result};
-use std::path::PathBuf;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 
+use diem_crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
+    PrivateKey, ValidCryptoMaterialStringExt,
+};
 use diem_genesis::config::{HostAndPort, OperatorConfiguration, OwnerConfiguration};
 use libra_types::global_config_dir;
 
@@ -14,6 +19,8 @@ use crate::{
 pub const OPERATOR_FILE: &str = "operator.yaml";
 pub const OWNER_FILE: &str = "owner.yaml";
 pub const VALIDATOR_IDENTITY_FILE: &str = "validator-identity.yaml";
+/// the operator key, which signs for the validator account once delegated
+pub const OPERATOR_KEY_FILE: &str = "operator-account.key";
 
 // copied from crate/diem/src/genesis/keys.rs
 pub struct SetValidatorConfiguration {
@@ -157,3 +164,118 @@ impl SetValidatorConfiguration {
         Ok((operator_config, owner_config))
     }
 }
+
+/// operator.yaml. Its `operator_account_address` is the validator's own
+/// account, which registration and the rescue tools read. The key of a
+/// delegated operator is kept in its own field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperatorFile {
+    #[serde(flatten)]
+    pub config: OperatorConfiguration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delegated_operator: Option<DelegatedOperator>,
+}
+
+/// A key which signs the validator account's operations instead of the
+/// owner's, so the owner's mnemonic can be kept offline. The validator
+/// account's authentication key is rotated to it with
+/// `libra txs validator delegate`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DelegatedOperator {
+    pub account_public_key: Ed25519PublicKey,
+}
+
+impl OperatorFile {
+    pub fn read(home_dir: &Path) -> Result<Self> {
+        let path = home_dir.join(OPERATOR_FILE);
+        from_yaml(&String::from_utf8(
+            read_from_file(&path).context(format!("cannot read {}", path.display()))?,
+        )?)
+    }
+}
+
+/// Record the operator key in operator.yaml, keeping the validator account,
+/// hosts and node keys. The key can be saved for the node host, to use with
+/// `--secret-source file:PATH`, while the owner's mnemonic stays offline.
+pub fn set_operator_key(
+    home_dir: &Path,
+    operator_key: &Ed25519PrivateKey,
+    save_key: bool,
+) -> Result<()> {
+    let mut file = OperatorFile::read(home_dir)?;
+    file.delegated_operator = Some(DelegatedOperator {
+        account_public_key: operator_key.public_key(),
+    });
+    write_to_user_only_file(
+        &home_dir.join(OPERATOR_FILE),
+        OPERATOR_FILE,
+        to_yaml(&file)?.as_bytes(),
+    )?;
+
+    if save_key {
+        write_to_user_only_file(
+            &home_dir.join(OPERATOR_KEY_FILE),
+            OPERATOR_KEY_FILE,
+            operator_key.to_encoded_string()?.as_bytes(),
+        )?;
+    }
+    Ok(())
+}
+
+/// read a key saved by `set_operator_key`
+pub fn read_operator_key(path: &Path) -> Result<Ed25519PrivateKey> {
+    let s = String::from_utf8(
+        read_from_file(path).context(format!("cannot read {}", path.display()))?,
+    )?;
+    Ok(Ed25519PrivateKey::from_encoded_string(s.trim())?)
+}
+
+#[test]
+fn separate_operator_key() {
+    use crate::{account_keys::get_keys_from_mnem, keys::generate_key_objects_from_legacy};
+
+    let alice_mnem = "talent sunset lizard pill fame nuclear spy noodle basket okay critic grow sleep legend hurry pitch blanket clerk impose rough degree sock insane purse";
+    let keys = get_keys_from_mnem(alice_mnem.to_string()).unwrap();
+    let (_, _, _, public_id) = generate_key_objects_from_legacy(&keys).unwrap();
+
+    let home = diem_temppath::TempPath::new();
+    home.create_as_dir().unwrap();
+    let home = home.path();
+    std::fs::write(home.join(PUBLIC_KEYS_FILE), to_yaml(&public_id).unwrap()).unwrap();
+
+    let host: HostAndPort = "validator.example.com:6180".parse().unwrap();
+    let (operator, owner) =
+        SetValidatorConfiguration::new(Some(home.to_owned()), "alice".to_owned(), host, None)
+            .set_config_files()
+            .unwrap();
+    assert_eq!(owner.operator_account_address, owner.owner_account_address);
+    assert!(OperatorFile::read(home)
+        .unwrap()
+        .delegated_operator
+        .is_none());
+
+    let operator_key = keys.child_1_operator.pri_key;
+    set_operator_key(home, &operator_key, true).unwrap();
+
+    // the validator account is unchanged, registration still reads it
+    let (new_operator, new_owner) =
+        SetValidatorConfiguration::read_configs_from_file(Some(home.to_owned())).unwrap();
+    assert_eq!(new_owner.owner_account_address, owner.owner_account_address);
+    assert_eq!(
+        new_operator.operator_account_address,
+        operator.operator_account_address
+    );
+    assert_eq!(
+        new_operator.consensus_public_key,
+        operator.consensus_public_key
+    );
+    assert_eq!(new_operator.full_node_host, operator.full_node_host);
+
+    let delegated = OperatorFile::read(home)
+        .unwrap()
+        .delegated_operator
+        .unwrap();
+    assert_eq!(delegated.account_public_key, operator_key.public_key());
+    let saved = read_operator_key(&home.join(OPERATOR_KEY_FILE)).unwrap();
+    assert_eq!(saved.public_key(), operator_key.public_key());
+}
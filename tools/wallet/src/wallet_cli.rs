@@ -1,8 +1,17 @@
-use crate::{account_keys, whoami::who_am_i};
+use crate::{
+    account_keys,
+    secret_source::SecretSource,
+    sign_message::{
+        get_client, get_signing_key, parse_signed_message, sign_message, verify_message,
+    },
+    whoami::who_am_i,
+};
 
 use anyhow::Result;
 use clap::{Args, Parser, Subcommand};
+use libra_types::exports::AccountAddress;
 use std::path::PathBuf;
+use url::Url;
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -17,10 +26,14 @@ pub struct WalletCli {
 enum WalletSub {
     /// Generate keys and account address locally
     Keygen {
-        /// Recover account from the given mnemonic
+        /// Recover account from the given mnemonic. Deprecated, use --secret-source
         #[clap(short, long)]
         mnemonic: Option<String>,
 
+        /// Recover account from a mnemonic read from: prompt, env:VAR, fd:N, file:PATH, or keystore[:DIR]
+        #[clap(long, conflicts_with("mnemonic"))]
+        secret_source: Option<SecretSource>,
+
         /// Path of the directory to store yaml files
         #[clap(short, long)]
         output_dir: Option<String>,
@@ -29,6 +42,10 @@ enum WalletSub {
     Legacy,
     /// use mnemonic to see what account keys are generated
     Whoami(WhoamiOpts),
+    /// Sign a message with the account's key, to prove you control the account
+    SignMessage(SignMessageOpts),
+    /// Verify a signed message against the account's on-chain authentication key
+    VerifyMessage(VerifyMessageOpts),
 }
 
 #[derive(Args, Debug)]
@@ -41,8 +58,72 @@ struct WhoamiOpts {
     #[clap(short('l'), long, default_value = "false")]
     legacy_address: bool,
 
+    /// optional, mnemonic to pass at runtime. Deprecated, use --secret-source
     #[clap(short('m'), long)]
     mnemonic: Option<String>,
+
+    /// optional, where to read the mnemonic from: prompt, env:VAR, fd:N, file:PATH, or keystore[:DIR]
+    #[clap(long, conflicts_with("mnemonic"))]
+    secret_source: Option<SecretSource>,
+}
+
+#[derive(Args, Debug)]
+struct SignMessageOpts {
+    /// the message to sign
+    #[clap(short('s'), long)]
+    message: String,
+
+    /// the message is a hex string, and the decoded bytes will be signed
+    #[clap(long, default_value = "false")]
+    hex: bool,
+
+    /// optional, path to the libra cli config file
+    #[clap(short, long)]
+    config_path: Option<PathBuf>,
+
+    /// optional, name of the profile in the config file to sign with
+    #[clap(short, long)]
+    profile: Option<String>,
+
+    /// optional, mnemonic to pass at runtime. Deprecated, use --secret-source
+    #[clap(short, long)]
+    mnemonic: Option<String>,
+
+    /// optional, where to read the mnemonic or private key from: prompt,
+    /// env:VAR, fd:N, file:PATH, or keystore[:DIR]
+    #[clap(long, conflicts_with("mnemonic"))]
+    secret_source: Option<SecretSource>,
+}
+
+#[derive(Args, Debug)]
+struct VerifyMessageOpts {
+    /// the account which claims to have signed the message
+    #[clap(short, long)]
+    account: AccountAddress,
+
+    /// the message which was signed
+    #[clap(short('s'), long)]
+    message: String,
+
+    /// the message is a hex string
+    #[clap(long, default_value = "false")]
+    hex: bool,
+
+    /// the ed25519 public key of the signer, hex encoded
+    #[clap(short('k'), long)]
+    public_key: String,
+
+    /// the signature, hex encoded
+    #[clap(short('g'), long)]
+    signature: String,
+
+    /// optional, path to the libra cli config file
+    #[clap(short, long)]
+    config_path: Option<PathBuf>,
+
+    /// optional, URL of the upstream node to look up the authentication key
+    #[clap(short, long)]
+    url: Option<Url>,
 }
 
 impl WalletCli {
@@ -51,10 +132,38 @@ impl WalletCli {
             WalletSub::Whoami(args) => {
                 who_am_i(
                     args.legacy_address,
-                    args.mnemonic.clone(),
+                    mnemonic_from(&args.secret_source, &args.mnemonic)?,
                     args.show_validator,
                 )?;
             }
+            WalletSub::SignMessage(args) => {
+                let (account, pri_key) = get_signing_key(
+                    args.config_path.clone(),
+                    args.profile.clone(),
+                    SecretSource::from_args(
+                        args.secret_source.as_ref(),
+                        args.mnemonic.as_ref(),
+                        None,
+                    ),
+                )?;
+                let signed = sign_message(account, &pri_key, &args.message, args.hex)?;
+                println!("{}", serde_json::to_string_pretty(&signed)?);
+            }
+            WalletSub::VerifyMessage(args) => {
+                let signed = parse_signed_message(
+                    args.account,
+                    &args.public_key,
+                    &args.signature,
+                    &args.message,
+                    args.hex,
+                )?;
+                let client = get_client(args.url.clone(), args.config_path.clone()).await?;
+                verify_message(&client, &signed).await?;
+                println!(
+                    "SUCCESS: message was signed by the key which controls account {}",
+                    signed.account
+                );
+            }
             WalletSub::Legacy => {
                 println!("this command will generate legacy keys and addresses from v5 addresses. You should only be using this for testing or debugging purposes");
 
@@ -62,12 +171,13 @@ impl WalletCli {
             }
             WalletSub::Keygen {
                 mnemonic,
+                secret_source,
                 output_dir,
             } => {
                 println!(
                     "{}",
                     crate::key_gen::run(
-                        mnemonic.to_owned(),
+                        mnemonic_from(secret_source, mnemonic)?,
                         output_dir.as_ref().map(PathBuf::from)
                     )
                     .await?
@@ -77,3 +187,13 @@ impl WalletCli {
         Ok(())
     }
 }
+
+/// the mnemonic from --secret-source, or the deprecated --mnemonic
+fn mnemonic_from(
+    secret_source: &Option<SecretSource>,
+    mnemonic: &Option<String>,
+) -> Result<Option<String>> {
+    SecretSource::from_args(secret_source.as_ref(), mnemonic.as_ref(), None)
+        .map(|s| s.mnemonic())
+        .transpose()
+}
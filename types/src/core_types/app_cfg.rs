@@ -6,13 +6,21 @@ use crate::{
     move_resource::gas_coin::SlowWalletBalance,
 };
 use anyhow::{bail, Context};
-use diem_crypto::ed25519::Ed25519PrivateKey;
+use diem_crypto::{
+    ed25519::{Ed25519PrivateKey, Ed25519PublicKey},
+    HashValue,
+};
 use diem_global_constants::{GAS_UNIT_PRICE, MAX_GAS_AMOUNT};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use url::Url;
 
-use std::{fs, io::Write, path::PathBuf, str::FromStr};
+use std::{
+    fmt, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use super::{
     mode_ol::MODE_0L,
@@ -35,6 +43,16 @@ pub struct AppCfg {
     pub network_playlist: Vec<NetworkPlaylist>,
     /// Transaction configurations
     pub tx_configs: TxConfigs,
+    /// Named addresses, including watch-only accounts the user does not control
+    #[serde(default)]
+    pub address_book: Vec<AddressBookEntry>,
+    /// Named networks, each with their own playlist, default account and tx costs
+    #[serde(default)]
+    pub networks: Vec<NamedNetwork>,
+    /// Keys of playlist maintainers. If any are set, fetched playlists must be
+    /// signed by one of them.
+    #[serde(default)]
+    pub trusted_playlist_keys: Vec<Ed25519PublicKey>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -135,19 +153,24 @@ impl AppCfg {
     }
     /// save the config file to 0L.toml to the workspace home path
     pub fn save_file(&self) -> anyhow::Result<PathBuf> {
-        // let toml = toml::to_string(&self)?;
+        let toml_path = self.workspace.node_home.join(CONFIG_FILE_NAME);
+        self.save_file_to(&toml_path)
+    }
+
+    /// save the config file to a path, e.g. back to the file it was loaded from
+    pub fn save_file_to(&self, path: &Path) -> anyhow::Result<PathBuf> {
         let yaml = serde_yaml::to_string(&self)?;
-        let home_path = &self.workspace.node_home.clone();
-        // create home path if doesn't exist, usually only in dev/ci environments.
-        if !home_path.exists() {
-            fs::create_dir_all(home_path)?;
+        // create the dir if doesn't exist, usually only in dev/ci environments.
+        if let Some(dir) = path.parent() {
+            if !dir.exists() {
+                fs::create_dir_all(dir)?;
+            }
         }
 
-        let toml_path = home_path.join(CONFIG_FILE_NAME);
-        let mut file = fs::File::create(&toml_path)?;
+        let mut file = fs::File::create(path)?;
         file.write_all(yaml.as_bytes())?;
 
-        Ok(toml_path)
+        Ok(path.to_path_buf())
     }
 
     // commit note: cleanup deprecated
@@ -172,8 +195,12 @@ impl AppCfg {
         };
 
         // try to use the default profile unless one was requested
+        // the active network may have its own default account
         if nickname.is_none() {
-            nickname.clone_from(&self.workspace.default_profile)
+            nickname = self
+                .get_active_network()
+                .and_then(|n| n.default_profile.clone())
+                .or_else(|| self.workspace.default_profile.clone());
         };
 
         if let Some(n) = nickname {
@@ -242,14 +269,79 @@ impl AppCfg {
         self.workspace.default_chain_id = chain_id;
     }
 
+    /// the named network in use, if any was selected with `use_network`
+    pub fn get_active_network(&self) -> Option<&NamedNetwork> {
+        let name = self.workspace.active_network.as_ref()?;
+        self.networks.iter().find(|n| &n.name == name)
+    }
+
+    fn get_active_network_mut(&mut self) -> Option<&mut NamedNetwork> {
+        let name = self.workspace.active_network.clone()?;
+        self.networks.iter_mut().find(|n| n.name == name)
+    }
+
+    /// the chain of the active network, otherwise the workspace default
+    pub fn active_chain_id(&self) -> NamedChain {
+        self.get_active_network()
+            .map(|n| n.playlist.chain_name)
+            .unwrap_or(self.workspace.default_chain_id)
+    }
+
+    /// add a named network, replacing any with the same name
+    pub fn add_named_network(&mut self, network: NamedNetwork) {
+        if let Some(n) = self.networks.iter_mut().find(|n| n.name == network.name) {
+            *n = network;
+        } else {
+            self.networks.push(network);
+        }
+    }
+
+    /// switch all tools to use a named network
+    pub fn use_network(&mut self, name: &str) -> anyhow::Result<()> {
+        let network = self
+            .networks
+            .iter()
+            .find(|n| n.name == name)
+            .context(format!("no network named '{name}' found"))?;
+
+        if let Some(p) = &network.default_profile {
+            anyhow::ensure!(
+                self.user_profiles
+                    .iter()
+                    .any(|e| &e.nickname == p || e.account.to_string().contains(p)),
+                "network '{name}' has a default profile '{p}' which does not exist"
+            );
+        }
+
+        self.workspace.active_network = Some(name.to_owned());
+        Ok(())
+    }
+
+    /// the tx costs of the active network, otherwise the global settings
+    pub fn get_tx_cost(&self, tx_type: Option<TxType>) -> TxCost {
+        self.get_active_network()
+            .and_then(|n| n.tx_configs.as_ref())
+            .unwrap_or(&self.tx_configs)
+            .get_cost(tx_type)
+    }
+
     pub async fn update_network_playlist(
         &mut self,
         chain_id: Option<NamedChain>,
         playlist_url: Option<Url>,
     ) -> anyhow::Result<NetworkPlaylist> {
-        let url = playlist_url.unwrap_or(network_playlist::find_default_playlist(chain_id)?);
-
-        let np = NetworkPlaylist::from_playlist_url(url, chain_id).await?;
+        // without a chain, update the playlist of the network in use
+        let chain = chain_id.unwrap_or(self.active_chain_id());
+        let url = playlist_url.unwrap_or(network_playlist::find_default_playlist(Some(chain))?);
+
+        let genesis = self.configured_genesis(chain);
+        let np = NetworkPlaylist::from_verified_playlist_url(
+            url,
+            Some(chain),
+            &self.trusted_playlist_keys,
+            genesis,
+        )
+        .await?;
 
         self.maybe_add_custom_playlist(&np);
         Ok(np)
@@ -260,10 +352,17 @@ impl AppCfg {
         &self,
         chain_id: Option<NamedChain>,
     ) -> anyhow::Result<NetworkPlaylist> {
+        // the active network's playlist takes precedence for its chain
+        let chain_id = chain_id.unwrap_or(self.active_chain_id());
+        if let Some(n) = self.get_active_network() {
+            if n.playlist.chain_name == chain_id {
+                return Ok(n.playlist.clone());
+            }
+        }
+
         // TODO: avoid clone
         let np = self.network_playlist.clone();
 
-        let chain_id = chain_id.unwrap_or(self.workspace.default_chain_id);
         anyhow::ensure!(!np.is_empty(), "no network profiles available");
         let profile = np.into_iter().find(|each| each.chain_name == chain_id);
 
@@ -274,9 +373,19 @@ impl AppCfg {
         &mut self,
         chain_id: Option<NamedChain>,
     ) -> anyhow::Result<&mut NetworkPlaylist> {
+        let chain_id = chain_id.unwrap_or(self.active_chain_id());
+        if self
+            .get_active_network()
+            .is_some_and(|n| n.playlist.chain_name == chain_id)
+        {
+            let n = self
+                .get_active_network_mut()
+                .context("cannot get active network")?;
+            return Ok(&mut n.playlist);
+        }
+
         let np = &mut self.network_playlist;
 
-        let chain_id = chain_id.unwrap_or(self.workspace.default_chain_id);
         anyhow::ensure!(!np.is_empty(), "no network profiles found");
         let profile = np
             .iter_mut()
@@ -296,19 +405,106 @@ impl AppCfg {
         Ok(np)
     }
 
+    /// the genesis hash configured for a chain's playlist, if any
+    pub fn configured_genesis(&self, chain: NamedChain) -> Option<HashValue> {
+        self.network_playlist
+            .iter()
+            .find(|p| p.chain_name == chain)
+            .and_then(|p| p.genesis_hash)
+    }
+
     /// if there is a custom playlist update it
     pub fn maybe_add_custom_playlist(&mut self, new_playlist: &NetworkPlaylist) {
         let mut found = false;
         self.network_playlist.iter_mut().for_each(|play| {
             if play.chain_name == new_playlist.chain_name {
                 found = true;
+                let genesis_hash = play.genesis_hash;
                 new_playlist.clone_into(play);
+                // keep a configured genesis if the new playlist has none
+                play.genesis_hash = play.genesis_hash.or(genesis_hash);
             }
         });
         if !found {
             self.network_playlist.push(new_playlist.to_owned());
         }
     }
+
+    /// trust a playlist maintainer's key, no-op if already trusted
+    pub fn add_trusted_playlist_key(&mut self, key: Ed25519PublicKey) {
+        if !self.trusted_playlist_keys.contains(&key) {
+            self.trusted_playlist_keys.push(key);
+        }
+    }
+
+    /// add an address book entry, replacing any entry with the same nickname
+    pub fn add_address_book_entry(&mut self, entry: AddressBookEntry) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            !entry.nickname.is_empty() && !entry.nickname.starts_with('@'),
+            "nickname cannot be empty or start with '@'"
+        );
+
+        if let Some(e) = self
+            .address_book
+            .iter_mut()
+            .find(|e| e.nickname == entry.nickname)
+        {
+            *e = entry;
+        } else {
+            self.address_book.push(entry);
+        }
+        Ok(())
+    }
+
+    /// remove an address book entry by nickname
+    pub fn try_remove_address_book_entry(&mut self, nickname: &str) -> anyhow::Result<()> {
+        let idx = self
+            .address_book
+            .iter()
+            .position(|e| e.nickname == nickname)
+            .context(format!("no address book entry named '{nickname}'"))?;
+        self.address_book.remove(idx);
+        Ok(())
+    }
+
+    /// find the address for a nickname, from the address book or the user profiles.
+    /// The nickname must be an exact match, since a partial match on an
+    /// address could send funds to the wrong account.
+    pub fn resolve_alias(&self, nickname: &str) -> anyhow::Result<AccountAddress> {
+        self.aliases().resolve(nickname)
+    }
+
+    /// the nicknames which can be used in place of an address, address book
+    /// entries first
+    pub fn aliases(&self) -> Aliases {
+        let book = self
+            .address_book
+            .iter()
+            .map(|e| (e.nickname.clone(), e.account));
+        let profiles = self
+            .user_profiles
+            .iter()
+            .map(|p| (p.nickname.clone(), p.account));
+        Aliases(book.chain(profiles).collect())
+    }
+
+    /// all accounts to include in a portfolio: the user's profiles and the
+    /// watch-only entries of the address book
+    pub fn watched_accounts(&self) -> Vec<(String, AccountAddress)> {
+        let mut list: Vec<(String, AccountAddress)> = self
+            .user_profiles
+            .iter()
+            .map(|p| (p.nickname.clone(), p.account))
+            .collect();
+
+        self.address_book.iter().filter(|e| e.watch).for_each(|e| {
+            if !list.iter().any(|(_, a)| *a == e.account) {
+                list.push((e.nickname.clone(), e.account));
+            }
+        });
+        list
+    }
+
     // TODO: always use CHAIN_ID from AppCfg
     ///fetch a network profile, optionally by profile name
     pub fn pick_url(&self, chain_id: Option<NamedChain>) -> anyhow::Result<Url> {
@@ -325,10 +521,134 @@ impl Default for AppCfg {
             user_profiles: vec![],
             network_playlist: vec![],
             tx_configs: TxConfigs::default(),
+            address_book: vec![],
+            networks: vec![],
+            trusted_playlist_keys: vec![],
+        }
+    }
+}
+
+/// A named network, e.g. "mainnet", "testnet" or "local-swarm". Selected with
+/// `libra config use-network <name>`, after which all tools will connect to
+/// its playlist, use its default account and its transaction costs.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct NamedNetwork {
+    /// name to select the network by
+    pub name: String,
+    /// the nodes of this network, and its chain id
+    pub playlist: NetworkPlaylist,
+    /// default profile to use on this network, by nickname or address fragment
+    #[serde(default)]
+    pub default_profile: Option<String>,
+    /// transaction costs for this network, otherwise the global settings are used
+    #[serde(default)]
+    pub tx_configs: Option<TxConfigs>,
+}
+
+impl NamedNetwork {
+    pub fn new(name: String, playlist: NetworkPlaylist) -> Self {
+        Self {
+            name,
+            playlist,
+            default_profile: None,
+            tx_configs: None,
         }
     }
 }
 
+/// A named account in the address book. Can be used in place of an address
+/// in the cli tools as `@nickname`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AddressBookEntry {
+    /// name to refer to this account by
+    pub nickname: String,
+    /// the account address
+    pub account: AccountAddress,
+    /// include the account in the portfolio view, even though we do not
+    /// control its keys
+    #[serde(default)]
+    pub watch: bool,
+    /// optional description, e.g. "donor voice: infra grants"
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+impl AddressBookEntry {
+    pub fn new(nickname: String, account: AccountAddress, watch: bool) -> Self {
+        Self {
+            nickname,
+            account,
+            watch,
+            note: None,
+        }
+    }
+}
+
+/// Nicknames and their addresses, from `AppCfg::aliases`
+#[derive(Clone, Debug, Default)]
+pub struct Aliases(Vec<(String, AccountAddress)>);
+
+impl Aliases {
+    /// exact match of a nickname, with or without the leading '@'
+    pub fn resolve(&self, nickname: &str) -> anyhow::Result<AccountAddress> {
+        let nickname = nickname.trim_start_matches('@');
+        self.0
+            .iter()
+            .find(|(n, _)| n == nickname)
+            .map(|(_, a)| *a)
+            .context(format!(
+                "no address book entry or profile named '{nickname}'"
+            ))
+    }
+}
+
+/// An account address, or an `@nickname` from the address book. Arguments
+/// are parsed before the config is loaded, so nicknames are resolved later
+/// with the config the command was given.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AddressOrAlias {
+    Address(AccountAddress),
+    Alias(String),
+}
+
+impl AddressOrAlias {
+    pub fn resolve(&self, aliases: &Aliases) -> anyhow::Result<AccountAddress> {
+        match self {
+            AddressOrAlias::Address(a) => Ok(*a),
+            AddressOrAlias::Alias(nickname) => aliases.resolve(nickname),
+        }
+    }
+}
+
+impl FromStr for AddressOrAlias {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if let Some(nickname) = s.strip_prefix('@') {
+            anyhow::ensure!(!nickname.is_empty(), "empty address alias");
+            return Ok(AddressOrAlias::Alias(nickname.to_owned()));
+        }
+        let a =
+            AccountAddress::from_str(s).context(format!("cannot parse account address: {s}"))?;
+        Ok(AddressOrAlias::Address(a))
+    }
+}
+
+impl fmt::Display for AddressOrAlias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressOrAlias::Address(a) => write!(f, "{a}"),
+            AddressOrAlias::Alias(n) => write!(f, "@{n}"),
+        }
+    }
+}
+
+impl From<AccountAddress> for AddressOrAlias {
+    fn from(a: AccountAddress) -> Self {
+        AddressOrAlias::Address(a)
+    }
+}
+
 /// Information about the Chain to mined for
 #[serde_as]
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -342,6 +662,10 @@ pub struct Workspace {
 
     /// home directory of the diem node, may be the same as miner.
     pub node_home: PathBuf,
+
+    /// the named network in use, see `AppCfg::networks`
+    #[serde(default)]
+    pub active_network: Option<String>,
 }
 
 impl Default for Workspace {
@@ -350,6 +674,7 @@ impl Default for Workspace {
             default_profile: None,
             default_chain_id: NamedChain::MAINNET,
             node_home: crate::global_config_dir(),
+            active_network: None,
         }
     }
 }
@@ -675,6 +1000,126 @@ async fn test_init() {
     assert!(p.auth_key == b);
 }
 
+#[test]
+fn address_book_aliases() {
+    let mut cfg = AppCfg {
+        user_profiles: vec![Profile::new(
+            AuthenticationKey::from_str(
+                "8603ba96e87b810cebbec1a0fd7ea06285f9eb352a3eabde992a5594fe80af40",
+            )
+            .unwrap(),
+            AccountAddress::from_hex_literal(
+                "0x8603ba96e87b810cebbec1a0fd7ea06285f9eb352a3eabde992a5594fe80af40",
+            )
+            .unwrap(),
+        )],
+        ..Default::default()
+    };
+
+    let treasury = AccountAddress::from_hex_literal("0x1234").unwrap();
+    cfg.add_address_book_entry(AddressBookEntry::new(
+        "treasury".to_string(),
+        treasury,
+        true,
+    ))
+    .unwrap();
+    cfg.add_address_book_entry(AddressBookEntry::new(
+        "friend".to_string(),
+        AccountAddress::from_hex_literal("0x5678").unwrap(),
+        false,
+    ))
+    .unwrap();
+    assert!(cfg
+        .add_address_book_entry(AddressBookEntry::new("@bad".to_string(), treasury, false))
+        .is_err());
+
+    assert_eq!(cfg.resolve_alias("@treasury").unwrap(), treasury);
+    // profile nicknames also resolve
+    assert_eq!(
+        cfg.resolve_alias("8603").unwrap(),
+        cfg.user_profiles[0].account
+    );
+    // no partial matches
+    assert!(cfg.resolve_alias("treas").is_err());
+
+    // only watched entries and own profiles are in the portfolio
+    let watched = cfg.watched_accounts();
+    assert_eq!(watched.len(), 2);
+    assert!(watched.iter().any(|(_, a)| *a == treasury));
+
+    // aliases are parsed without the config, and resolved with it
+    let arg: AddressOrAlias = "@treasury".parse().unwrap();
+    assert_eq!(arg, AddressOrAlias::Alias("treasury".to_string()));
+    assert_eq!(arg.resolve(&cfg.aliases()).unwrap(), treasury);
+    assert!(arg.resolve(&Aliases::default()).is_err());
+    let arg: AddressOrAlias = "0x1234".parse().unwrap();
+    assert_eq!(arg.resolve(&Aliases::default()).unwrap(), treasury);
+
+    cfg.try_remove_address_book_entry("treasury").unwrap();
+    assert!(cfg.resolve_alias("treasury").is_err());
+}
+
+#[test]
+fn save_to_loaded_path() {
+    let d = diem_temppath::TempPath::new();
+    d.create_as_dir().unwrap();
+    let cfg = AppCfg {
+        user_profiles: vec![Profile::default()],
+        ..Default::default()
+    };
+
+    // a config file outside of node_home is written back where it was read
+    let path = d.path().join("custom").join(CONFIG_FILE_NAME);
+    cfg.save_file_to(&path).unwrap();
+    let mut loaded = AppCfg::load(Some(path.clone())).unwrap();
+    loaded
+        .add_address_book_entry(AddressBookEntry::new(
+            "treasury".to_string(),
+            AccountAddress::from_hex_literal("0x1234").unwrap(),
+            true,
+        ))
+        .unwrap();
+    loaded.save_file_to(&path).unwrap();
+
+    let reloaded = AppCfg::load(Some(path)).unwrap();
+    assert_eq!(reloaded.address_book.len(), 1);
+}
+
+#[test]
+fn named_networks() {
+    let mut cfg = AppCfg {
+        user_profiles: vec![Profile::default()],
+        network_playlist: vec![NetworkPlaylist::new(
+            Some("http://mainnet.example:8080".parse().unwrap()),
+            Some(NamedChain::MAINNET),
+        )],
+        ..Default::default()
+    };
+    assert_eq!(cfg.active_chain_id(), NamedChain::MAINNET);
+
+    let mut local = NamedNetwork::new(
+        "local-swarm".to_string(),
+        NetworkPlaylist::localhost(Some(NamedChain::TESTING)),
+    );
+    local.tx_configs = Some(TxConfigs {
+        baseline_cost: TxCost::new(1_000, 1.0, None),
+        ..Default::default()
+    });
+    cfg.add_named_network(local);
+
+    assert!(cfg.use_network("nope").is_err());
+    cfg.use_network("local-swarm").unwrap();
+
+    assert_eq!(cfg.active_chain_id(), NamedChain::TESTING);
+    let url = cfg.pick_url(None).unwrap();
+    assert!(url.host_str().unwrap().contains("localhost"));
+    assert_eq!(cfg.get_tx_cost(None).max_gas_unit_for_tx, 1_000);
+
+    // can still explicitly pick another chain
+    let url = cfg.pick_url(Some(NamedChain::MAINNET)).unwrap();
+    assert!(url.host_str().unwrap().contains("mainnet.example"));
+}
+
 #[test]
 fn read_write() {
     let raw_yaml = r"
@@ -0,0 +1,277 @@
+//! A REST client which keeps a health score of each upstream in a
+//! NetworkPlaylist, and fails over to the next best node.
+use super::network_playlist::{HostProfile, NetworkPlaylist};
+use crate::exports::{ChainId, Client};
+use anyhow::{bail, Context};
+use diem_sdk::rest_client::Response;
+use futures::{stream::FuturesUnordered, StreamExt};
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use url::Url;
+
+/// how many different nodes to try a read on before giving up
+pub const MAX_ATTEMPTS: usize = 3;
+/// latency assumed for nodes we have not measured yet
+const UNKNOWN_LATENCY_MS: u64 = 1_000;
+/// penalty for each recent error, roughly a slow request
+const ERROR_PENALTY_MS: u64 = 2_000;
+/// how many versions behind the highest known node equal one ms of latency
+const VERSIONS_PER_MS: u64 = 10;
+
+/// The live health of an upstream node
+#[derive(Clone, Debug)]
+pub struct HostHealth {
+    /// the node as described in the playlist, with its last known version
+    pub profile: HostProfile,
+    /// latency of the last successful request, if any
+    pub latency_ms: Option<u64>,
+    /// errors since the node was last healthy, decays on success
+    pub recent_errors: u32,
+}
+
+impl HostHealth {
+    fn new(profile: HostProfile) -> Self {
+        Self {
+            profile,
+            latency_ms: None,
+            recent_errors: 0,
+        }
+    }
+
+    /// Lower is better. Combines the latency, how far behind the node is
+    /// from the highest version we know of, and the recent errors.
+    pub fn penalty(&self, highest_version: u64) -> u64 {
+        let latency = self.latency_ms.unwrap_or(UNKNOWN_LATENCY_MS);
+        let lag = highest_version.saturating_sub(self.profile.version) / VERSIONS_PER_MS;
+        let errors = self.recent_errors as u64 * ERROR_PENALTY_MS;
+        // nodes which were previously found in sync are preferred
+        let unsynced = if self.profile.is_sync { 0 } else { 1 };
+
+        latency
+            .saturating_add(lag)
+            .saturating_add(errors)
+            .saturating_add(unsynced)
+    }
+
+    fn record_success(&mut self, latency_ms: u64, version: Option<u64>) {
+        self.latency_ms = Some(latency_ms);
+        self.recent_errors /= 2;
+        self.profile.is_api = true;
+        if let Some(v) = version {
+            self.profile.version = v;
+        }
+    }
+
+    fn record_error(&mut self) {
+        self.recent_errors = self.recent_errors.saturating_add(1);
+    }
+}
+
+/// Wraps the nodes of a playlist. Reads are sent to the healthiest node, and
+/// retried on the next best node if they fail.
+#[derive(Clone, Debug)]
+pub struct FailoverClient {
+    hosts: Arc<Mutex<Vec<HostHealth>>>,
+    current: Arc<Mutex<Option<Url>>>,
+}
+
+impl FailoverClient {
+    pub fn new(playlist: &NetworkPlaylist) -> Self {
+        let hosts = playlist
+            .nodes
+            .iter()
+            .cloned()
+            .map(HostHealth::new)
+            .collect();
+        Self {
+            hosts: Arc::new(Mutex::new(hosts)),
+            current: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// a snapshot of the health of all nodes
+    pub fn hosts(&self) -> Vec<HostHealth> {
+        self.hosts.lock().expect("poisoned lock").clone()
+    }
+
+    /// the urls of all nodes, best first
+    pub fn ranked(&self) -> Vec<Url> {
+        let mut hosts = self.hosts();
+        let highest = hosts.iter().map(|h| h.profile.version).max().unwrap_or(0);
+        // stable sort, ties keep the playlist order
+        hosts.sort_by_key(|h| h.penalty(highest));
+        hosts.into_iter().map(|h| h.profile.url).collect()
+    }
+
+    /// the node which was last used
+    pub fn current_url(&self) -> Option<Url> {
+        self.current.lock().expect("poisoned lock").clone()
+    }
+
+    /// a client for the node which was last used
+    pub fn current_client(&self) -> anyhow::Result<Client> {
+        let url = self.current_url().context("no node was used yet")?;
+        Ok(Client::new(url))
+    }
+
+    /// a client for the healthiest node
+    pub fn best_client(&self) -> anyhow::Result<Client> {
+        let url = self
+            .ranked()
+            .into_iter()
+            .next()
+            .context("no nodes in playlist")?;
+        *self.current.lock().expect("poisoned lock") = Some(url.clone());
+        Ok(Client::new(url))
+    }
+
+    /// mark the node last used as failing, and get a client for the next best
+    pub fn rotate(&self) -> anyhow::Result<Client> {
+        if let Some(u) = self.current_url() {
+            self.record_error(&u);
+        }
+        self.best_client()
+    }
+
+    pub fn record_error(&self, url: &Url) {
+        let mut hosts = self.hosts.lock().expect("poisoned lock");
+        if let Some(h) = hosts.iter_mut().find(|h| &h.profile.url == url) {
+            h.record_error();
+        }
+    }
+
+    pub fn record_success(&self, url: &Url, latency_ms: u64, version: Option<u64>) {
+        let mut hosts = self.hosts.lock().expect("poisoned lock");
+        if let Some(h) = hosts.iter_mut().find(|h| &h.profile.url == url) {
+            h.record_success(latency_ms, version);
+        }
+    }
+
+    /// probe all nodes concurrently for their latency and ledger version
+    pub async fn refresh_health(&self) {
+        let futures = FuturesUnordered::new();
+        self.ranked().into_iter().for_each(|url| {
+            futures.push(async move {
+                let start = Instant::now();
+                let res = Client::new(url.clone()).get_index().await;
+                (url, start.elapsed().as_millis() as u64, res)
+            })
+        });
+
+        let results = futures.collect::<Vec<_>>().await;
+        for (url, latency_ms, res) in results {
+            match res {
+                Ok(r) => self.record_success(
+                    &url,
+                    latency_ms,
+                    Some(r.into_inner().ledger_version.into()),
+                ),
+                Err(_) => self.record_error(&url),
+            }
+        }
+    }
+
+    /// Probe all nodes, then connect to the healthiest one which answers.
+    /// Returns its client and the chain id it reports.
+    pub async fn connect(&self) -> anyhow::Result<(Client, ChainId)> {
+        self.refresh_health().await;
+        let index = self
+            .read_response(|c| async move { Ok(c.get_index().await?) })
+            .await?
+            .into_inner();
+        Ok((self.current_client()?, ChainId::new(index.chain_id)))
+    }
+
+    /// Run an idempotent read against the healthiest node. If it fails the
+    /// node is penalized, and the read is retried on the next best node.
+    /// Do not use for non-idempotent requests.
+    pub async fn read<T, F, Fut>(&self, f: F) -> anyhow::Result<T>
+    where
+        F: Fn(Client) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        self.read_with_version(f, |_| None).await
+    }
+
+    /// Like `read`, for API responses. The ledger version each response
+    /// reports is recorded for the node.
+    pub async fn read_response<T, F, Fut>(&self, f: F) -> anyhow::Result<Response<T>>
+    where
+        F: Fn(Client) -> Fut,
+        Fut: Future<Output = anyhow::Result<Response<T>>>,
+    {
+        self.read_with_version(f, |r| Some(r.state().version)).await
+    }
+
+    async fn read_with_version<T, F, Fut>(
+        &self,
+        f: F,
+        version: impl Fn(&T) -> Option<u64>,
+    ) -> anyhow::Result<T>
+    where
+        F: Fn(Client) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let ranked = self.ranked();
+        if ranked.is_empty() {
+            bail!("no nodes in playlist");
+        }
+
+        let mut last_err = None;
+        for url in ranked.into_iter().take(MAX_ATTEMPTS) {
+            let start = Instant::now();
+            match f(Client::new(url.clone())).await {
+                Ok(res) => {
+                    let latency_ms = start.elapsed().as_millis() as u64;
+                    self.record_success(&url, latency_ms, version(&res));
+                    *self.current.lock().expect("poisoned lock") = Some(url);
+                    return Ok(res);
+                }
+                Err(e) => {
+                    self.record_error(&url);
+                    last_err = Some(e.context(format!("request to {url} failed")));
+                }
+            }
+        }
+
+        Err(last_err.expect("at least one attempt"))
+    }
+}
+
+#[test]
+fn ranks_by_health() {
+    let np = NetworkPlaylist {
+        nodes: vec![
+            HostProfile::new("http://a.example:8080".parse().unwrap()),
+            HostProfile::new("http://b.example:8080".parse().unwrap()),
+            HostProfile::new("http://c.example:8080".parse().unwrap()),
+        ],
+        ..Default::default()
+    };
+    let fc = FailoverClient::new(&np);
+    let a: Url = "http://a.example:8080".parse().unwrap();
+    let b: Url = "http://b.example:8080".parse().unwrap();
+    let c: Url = "http://c.example:8080".parse().unwrap();
+
+    // unknown nodes keep the playlist order
+    assert_eq!(fc.ranked(), vec![a.clone(), b.clone(), c.clone()]);
+
+    // a fast node which is far behind is worse than a slower one in sync
+    fc.record_success(&a, 50, Some(0));
+    fc.record_success(&b, 200, Some(1_000_000));
+    fc.record_success(&c, 100, Some(1_000_000));
+    assert_eq!(fc.ranked(), vec![c.clone(), b.clone(), a.clone()]);
+
+    // errors push a node down the list
+    let _ = fc.best_client().unwrap();
+    assert_eq!(fc.current_url(), Some(c.clone()));
+    let _ = fc.rotate().unwrap();
+    assert_eq!(fc.current_url(), Some(b.clone()));
+
+    // and decay when it recovers
+    fc.record_success(&c, 100, None);
+    assert_eq!(fc.ranked().first(), Some(&c));
+}
@@ -1,5 +1,6 @@
 pub mod app_cfg;
 pub mod block;
+pub mod failover_client;
 pub mod fixtures;
 pub mod legacy_currency_info;
 pub mod mode_ol;
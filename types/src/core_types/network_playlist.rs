@@ -1,11 +1,62 @@
 //! network configs
-use crate::exports::{Client, NamedChain};
+use crate::exports::{Client, Ed25519PrivateKey, Ed25519PublicKey, NamedChain};
 use anyhow::{bail, Context};
+use diem_crypto::{ed25519::Ed25519Signature, HashValue, PrivateKey, Signature, SigningKey};
 use futures::{stream::FuturesUnordered, StreamExt};
 use rand::{seq::SliceRandom, thread_rng};
 use serde_with::{serde_as, DisplayFromStr};
 use url::Url;
 
+/// Domain separation prefix for playlist signatures, so that a maintainer's
+/// signature over a playlist cannot be confused with any other signed message.
+pub const PLAYLIST_SIGNATURE_PREFIX: &[u8] = b"LIBRA::NetworkPlaylist::";
+
+/// A detached signature over the exact bytes of a playlist file. Published
+/// next to the playlist, at the same url with a `.sig` extension.
+#[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
+pub struct PlaylistSignature {
+    pub public_key: Ed25519PublicKey,
+    pub signature: Ed25519Signature,
+}
+
+impl PlaylistSignature {
+    /// sign the raw bytes of a playlist file
+    pub fn sign(playlist_bytes: &[u8], key: &Ed25519PrivateKey) -> Self {
+        Self {
+            public_key: key.public_key(),
+            signature: key.sign_arbitrary_message(&playlist_signing_bytes(playlist_bytes)),
+        }
+    }
+
+    /// check the signature is valid, and made by one of the trusted keys
+    pub fn verify(
+        &self,
+        playlist_bytes: &[u8],
+        trusted_keys: &[Ed25519PublicKey],
+    ) -> anyhow::Result<()> {
+        if !trusted_keys.contains(&self.public_key) {
+            bail!(
+                "playlist was signed by {}, which is not a trusted key",
+                self.public_key
+            );
+        }
+        self.signature
+            .verify_arbitrary_msg(&playlist_signing_bytes(playlist_bytes), &self.public_key)
+            .context("playlist signature is invalid")
+    }
+}
+
+fn playlist_signing_bytes(playlist_bytes: &[u8]) -> Vec<u8> {
+    let mut bytes = PLAYLIST_SIGNATURE_PREFIX.to_vec();
+    bytes.extend_from_slice(playlist_bytes);
+    bytes
+}
+
+/// where the detached signature of a playlist is published
+pub fn signature_url(playlist_url: &Url) -> anyhow::Result<Url> {
+    Ok(format!("{}.sig", playlist_url.as_str()).parse()?)
+}
+
 #[derive(serde::Deserialize, serde::Serialize, Debug, Clone)]
 pub struct HostProfile {
     pub url: Url,
@@ -71,6 +122,11 @@ pub struct NetworkPlaylist {
     #[serde_as(as = "DisplayFromStr")]
     pub chain_name: NamedChain,
     pub nodes: Vec<HostProfile>,
+    /// The expected genesis, as the accumulator root hash of the genesis
+    /// transaction. The REST API does not serve the waypoint, this is what
+    /// it can prove instead. Nodes with a different genesis are dropped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub genesis_hash: Option<HashValue>,
 }
 fn default_chain() -> NamedChain {
     NamedChain::MAINNET
@@ -82,6 +138,7 @@ impl Default for NetworkPlaylist {
         NetworkPlaylist {
             chain_name: NamedChain::MAINNET,
             nodes: vec![HostProfile::default()],
+            genesis_hash: None,
         }
     }
 }
@@ -124,6 +181,7 @@ impl NetworkPlaylist {
                 is_api: true,
                 is_sync: true,
             }],
+            genesis_hash: None,
         }
     }
 
@@ -137,17 +195,115 @@ impl NetworkPlaylist {
         Self::from_playlist_url(url, chain_id).await
     }
 
+    /// Fetch a playlist without any trusted keys. The signature is not
+    /// checked, but nodes on another chain are still dropped.
     pub async fn from_playlist_url(
         playlist_url: Url,
         chain_id: Option<NamedChain>,
     ) -> anyhow::Result<NetworkPlaylist> {
-        let res = reqwest::get(playlist_url).await?;
-        let out = res.text().await?;
-        let mut play: NetworkPlaylist = serde_json::from_str(&out)?;
-        if let Some(c) = chain_id {
-            play.chain_name = c;
+        Self::from_verified_playlist_url(playlist_url, chain_id, &[], None).await
+    }
+
+    /// Fetch a playlist. If there are trusted keys, require a valid detached
+    /// signature from one of them. Then drop any listed node which is not on
+    /// the playlist's chain and genesis. A configured `genesis_hash` takes
+    /// precedence over the published one. Without trusted keys the signature
+    /// is not fetched.
+    pub async fn from_verified_playlist_url(
+        playlist_url: Url,
+        chain_id: Option<NamedChain>,
+        trusted_keys: &[Ed25519PublicKey],
+        genesis_hash: Option<HashValue>,
+    ) -> anyhow::Result<NetworkPlaylist> {
+        let res = reqwest::get(playlist_url.clone())
+            .await?
+            .error_for_status()?;
+        let bytes = res.bytes().await?;
+
+        let parse = || -> anyhow::Result<NetworkPlaylist> {
+            let mut play: NetworkPlaylist = serde_json::from_slice(&bytes)?;
+            if let Some(c) = chain_id {
+                play.chain_name = c;
+            }
+            if genesis_hash.is_some() {
+                play.genesis_hash = genesis_hash;
+            }
+            Ok(play)
+        };
+        // nodes are checked against the chain and genesis whether or not the
+        // playlist is signed
+        if trusted_keys.is_empty() {
+            return parse()?.reject_mismatched_nodes().await;
         }
-        Ok(play)
+
+        let sig_res = reqwest::get(signature_url(&playlist_url)?).await;
+        let sig = match sig_res {
+            Ok(r) if r.status().is_success() => {
+                serde_json::from_slice::<PlaylistSignature>(&r.bytes().await?).context(format!(
+                    "malformed signature for playlist at {playlist_url}"
+                ))?
+            }
+            _ => bail!(
+                "no signature found for playlist at {}, and trusted keys are configured",
+                playlist_url
+            ),
+        };
+        sig.verify(&bytes, trusted_keys)?;
+
+        parse()?.reject_mismatched_nodes().await
+    }
+
+    /// Drops nodes which report a different chain id than the playlist, or a
+    /// different genesis than `genesis_hash`, if it is set.
+    /// Unreachable nodes, and nodes which have pruned genesis, are kept since
+    /// they may only be down temporarily.
+    pub async fn reject_mismatched_nodes(mut self) -> anyhow::Result<Self> {
+        let futures = FuturesUnordered::new();
+        let check_genesis = self.genesis_hash.is_some();
+        self.nodes.iter().for_each(|p| {
+            let url = p.url.clone();
+            futures.push(async move {
+                let client = Client::new(url.clone());
+                let chain_id = client
+                    .get_index()
+                    .await
+                    .ok()
+                    .map(|r| r.into_inner().chain_id);
+                let genesis = if check_genesis {
+                    match client.get_transaction_by_version(0).await {
+                        Ok(r) => r
+                            .into_inner()
+                            .transaction_info()
+                            .ok()
+                            .map(|i| HashValue::from(i.accumulator_root_hash)),
+                        Err(_) => None,
+                    }
+                } else {
+                    None
+                };
+                (url, chain_id, genesis)
+            });
+        });
+        let probes = futures.collect::<Vec<_>>().await;
+
+        let expected_id = self.chain_name.id();
+        let rejected: Vec<Url> = probes
+            .into_iter()
+            .filter_map(|(url, chain_id, genesis)| {
+                let wrong_chain = chain_id.is_some_and(|id| id != expected_id);
+                let wrong_genesis = genesis.is_some() && genesis != self.genesis_hash;
+                (wrong_chain || wrong_genesis).then_some(url)
+            })
+            .collect();
+
+        self.nodes.retain(|p| !rejected.contains(&p.url));
+        if self.nodes.is_empty() {
+            bail!(
+                "no nodes in playlist match chain {} and its genesis",
+                self.chain_name
+            );
+        }
+        Ok(self)
     }
 
     pub fn add_url(&mut self, url: Url) {
@@ -278,3 +434,30 @@ impl NetworkPlaylist {
         Ok(self)
     }
 }
+
+#[test]
+fn playlist_signature() {
+    let maintainer = Ed25519PrivateKey::try_from(&[1u8; 32][..]).unwrap();
+    let stranger = Ed25519PrivateKey::try_from(&[2u8; 32][..]).unwrap();
+
+    let playlist = serde_json::to_vec(&NetworkPlaylist::default()).unwrap();
+    let sig = PlaylistSignature::sign(&playlist, &maintainer);
+
+    let trusted = vec![maintainer.public_key()];
+    sig.verify(&playlist, &trusted).unwrap();
+
+    // tampered playlist
+    let mut tampered = playlist.clone();
+    tampered.push(b' ');
+    assert!(sig.verify(&tampered, &trusted).is_err());
+
+    // untrusted signer
+    let sig = PlaylistSignature::sign(&playlist, &stranger);
+    assert!(sig.verify(&playlist, &trusted).is_err());
+
+    let url: Url = "https://example.com/playlist.json".parse().unwrap();
+    assert_eq!(
+        signature_url(&url).unwrap().as_str(),
+        "https://example.com/playlist.json.sig"
+    );
+}
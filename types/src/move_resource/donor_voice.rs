@@ -24,3 +24,23 @@ impl MoveStructType for RegistryResource {
 }
 
 impl MoveResource for RegistryResource {}
+
+/// the freeze policy of a donor voice account
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FreezeResource {
+    pub is_frozen: bool,
+    pub consecutive_rejections: u64,
+    pub unfreeze_votes: Vec<AccountAddress>,
+    pub liquidate_to_match_index: bool,
+}
+
+impl MoveStructType for FreezeResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("donor_voice_txs");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("Freeze");
+
+    fn type_params() -> Vec<TypeTag> {
+        vec![]
+    }
+}
+
+impl MoveResource for FreezeResource {}
@@ -13,6 +13,7 @@ pub mod jail;
 pub mod libra_coin;
 pub mod match_index;
 pub mod ol_account;
+pub mod originating_address;
 pub mod pledge_account;
 pub mod proof_of_fee;
 pub mod receipts;
@@ -0,0 +1,22 @@
+//! the table of rotated authentication keys at 0x1
+use diem_types::state_store::table::TableHandle;
+use move_core_types::{
+    ident_str,
+    identifier::IdentStr,
+    move_resource::{MoveResource, MoveStructType},
+};
+use serde::{Deserialize, Serialize};
+
+/// Maps an authentication key, as an address, to the account it was
+/// rotated on. The entries are table items, not part of the resource.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OriginatingAddressResource {
+    pub address_map: TableHandle,
+}
+
+impl MoveStructType for OriginatingAddressResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("account");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("OriginatingAddress");
+}
+
+impl MoveResource for OriginatingAddressResource {}
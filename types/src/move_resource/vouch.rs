@@ -26,3 +26,38 @@ impl MoveStructType for MyVouchesResource {
 }
 
 impl MoveResource for MyVouchesResource {}
+
+/// incoming vouches, replaces MyVouches in v7
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReceivedVouchesResource {
+    pub incoming_vouches: Vec<AccountAddress>,
+    pub epoch_vouched: Vec<u64>,
+}
+
+impl MoveStructType for ReceivedVouchesResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("vouch");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("ReceivedVouches");
+
+    fn type_params() -> Vec<TypeTag> {
+        vec![]
+    }
+}
+
+impl MoveResource for ReceivedVouchesResource {}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GivenVouchesResource {
+    pub outgoing_vouches: Vec<AccountAddress>,
+    pub epoch_vouched: Vec<u64>,
+}
+
+impl MoveStructType for GivenVouchesResource {
+    const MODULE_NAME: &'static IdentStr = ident_str!("vouch");
+    const STRUCT_NAME: &'static IdentStr = ident_str!("GivenVouches");
+
+    fn type_params() -> Vec<TypeTag> {
+        vec![]
+    }
+}
+
+impl MoveResource for GivenVouchesResource {}
@@ -13,6 +13,14 @@ impl OLProgress {
         .unwrap()
         .tick_strings(&ol_ticks())
     }
+    /// bar for downloads, in bytes
+    pub fn bytes() -> ProgressStyle {
+        ProgressStyle::with_template(
+            "{msg} {spinner:.blue} [{elapsed_precise}] [{bar:25.blue}] ({bytes}/{total_bytes}, {bytes_per_sec}, ETA {eta})",
+        )
+        .unwrap()
+        .tick_strings(&ol_ticks())
+    }
     /// who knows how long this will take
     pub fn spinner() -> ProgressStyle {
         ProgressStyle::with_template("[{elapsed_precise}] {msg} {spinner:.blue}")
@@ -1,6 +1,8 @@
 use crate::{
-    core_types::app_cfg::AppCfg, exports::AuthenticationKey,
-    type_extensions::cli_config_ext::CliConfigExt, util::parse_function_id,
+    core_types::{app_cfg::AppCfg, failover_client::FailoverClient},
+    exports::AuthenticationKey,
+    type_extensions::cli_config_ext::CliConfigExt,
+    util::parse_function_id,
 };
 
 use anyhow::{anyhow, Context};
@@ -87,17 +89,20 @@ impl ClientExt for Client {
         Ok(client)
     }
 
-    /// Finds a good working upstream based on the list in a config file
+    /// Finds the healthiest upstream based on the list in a config file.
+    /// The client is a single node, tools which should keep failing over
+    /// hold on to a FailoverClient instead, as the Sender does.
     async fn from_libra_config(
         app_cfg: &AppCfg,
         chain_id_opt: Option<NamedChain>,
     ) -> anyhow::Result<(Client, ChainId)> {
         // check if we can connect to this client, or exit
-        let url = &app_cfg.pick_url(chain_id_opt)?;
-        let client = Client::new(url.to_owned());
-        let res = client.get_index().await?;
+        let chain_name = chain_id_opt.unwrap_or(app_cfg.active_chain_id());
+        let upstreams = FailoverClient::new(&app_cfg.get_network_profile(Some(chain_name))?);
+        let (client, chain_id) = upstreams.connect().await?;
+        check_chain_id(chain_name, chain_id)?;
 
-        Ok((client, ChainId::new(res.inner().chain_id)))
+        Ok((client, chain_id))
     }
 
     fn from_vendor_config() -> anyhow::Result<Client> {
@@ -269,6 +274,19 @@ pub struct TransactionOptions {
     pub timeout_secs: u64,
 }
 
+/// Check that the node we connected to is on the chain the config expects.
+/// Prevents e.g. signing testnet transactions with mainnet settings.
+pub fn check_chain_id(expected: NamedChain, reported: ChainId) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        expected.id() == reported.id(),
+        "the node reports chain id {}, but the config expects {} ({}). Check your network settings with `libra config use-network` or --chain-name",
+        reported,
+        expected,
+        expected.id()
+    );
+    Ok(())
+}
+
 pub fn entry_function_id(
     module_name: &str,
     function_name: &str,